@@ -1,17 +1,21 @@
 use crate::db::queries;
-use crate::model::database::{Folder, FolderData, Media, Setting, Tag};
-use crate::model::parser::MediaItem;
+use crate::model::database::{Collection, Folder, FolderData, Media, ReadingState, Setting, Tag};
+use crate::model::parser::{
+    CoverFormat, FileFingerprint, MediaItem, MediaProbe, MediaType, ScanResult,
+};
 use log::{debug, error};
 use serde_json::{json, Value};
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
 use sqlx::{
-    migrate::MigrateDatabase, sqlite::SqlitePoolOptions, Pool, QueryBuilder, Row, Sqlite,
-    SqlitePool,
+    migrate::MigrateDatabase, sqlite::SqlitePoolOptions, AssertSqlSafe, FromRow, Pool,
+    QueryBuilder, Row, Sqlite, SqlitePool,
 };
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::result::Result;
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tauri::{Manager, Runtime};
 
 pub fn initialize<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), String> {
@@ -34,25 +38,49 @@ pub fn initialize<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), String> {
                 error!("{:?}", err_msg);
                 return Err(err_msg);
             }
-            let pool = SqlitePool::connect(&db_url)
-                .await
-                .map_err(|e| format!("Fail to connect to db at {:?}. Error: {:?}", &db_url, e))?;
-            if let Err(e) = create_tables(&pool).await {
-                let err_msg = format!("Fail to create tables. Error: {:?}", e);
-                error!("{:?}", err_msg);
-                pool.close().await;
-                return Err(err_msg);
-            }
+        }
+
+        // tables are created idempotently so existing databases pick up pending migrations
+        let pool = SqlitePool::connect(&db_url)
+            .await
+            .map_err(|e| format!("Fail to connect to db at {:?}. Error: {:?}", &db_url, e))?;
+        if let Err(e) = create_tables(&pool).await {
+            let err_msg = format!("Fail to create tables. Error: {:?}", e);
+            error!("{:?}", err_msg);
             pool.close().await;
+            return Err(err_msg);
         }
+        pool.close().await;
         Ok(())
     })
 }
 
-async fn create_tables(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+pub(crate) async fn create_tables(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     sqlx::query(queries::CREATE_TABLE_QUERY)
         .execute(pool)
         .await?;
+    migrate(pool).await
+}
+
+async fn migrate(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(pool)
+        .await?;
+
+    for (i, migration) in queries::MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(version.max(0) as usize)
+    {
+        debug!("Applying database migration {}", i + 1);
+        let mut tx = pool.begin().await?;
+        sqlx::query(*migration).execute(&mut *tx).await?;
+        // PRAGMA does not accept bind parameters, the version is a plain integer
+        sqlx::query(AssertSqlSafe(format!("PRAGMA user_version = {}", i + 1)))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
     Ok(())
 }
 
@@ -91,6 +119,30 @@ pub async fn get_skip_folders(pool: &Pool<Sqlite>) -> Result<Vec<String>, sqlx::
         .collect())
 }
 
+pub async fn get_cover_format(pool: &Pool<Sqlite>) -> Result<CoverFormat, sqlx::Error> {
+    let cover_format: String = sqlx::query_scalar(queries::GET_COVER_FORMAT)
+        .fetch_one(pool)
+        .await?;
+    Ok(cover_format.parse().unwrap_or_default())
+}
+
+pub async fn update_cover_format(
+    pool: &Pool<Sqlite>,
+    cover_format: CoverFormat,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(queries::UPDATE_COVER_FORMAT)
+        .bind(cover_format.as_str())
+        .execute(&mut *tx)
+        .await?;
+    // covers are only converted for changed images, so force a full rescan of every folder
+    sqlx::query(queries::CLEAR_ALL_FINGERPRINTS)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
 pub async fn update_hide_side_panel(
     pool: &Pool<Sqlite>,
     hide_panel: &i32,
@@ -133,6 +185,13 @@ pub async fn get_folder_list(pool: &Pool<Sqlite>) -> Result<Vec<Folder>, sqlx::E
     Ok(folder_list)
 }
 
+pub async fn get_watched_folders(pool: &Pool<Sqlite>) -> Result<Vec<Folder>, sqlx::Error> {
+    let folder_list = sqlx::query_as::<_, Folder>(queries::GET_WATCHED_FOLDERS)
+        .fetch_all(pool)
+        .await?;
+    Ok(folder_list)
+}
+
 pub async fn get_folder_data(
     pool: &Pool<Sqlite>,
     position: &i32,
@@ -144,29 +203,162 @@ pub async fn get_folder_data(
     Ok(folder_data)
 }
 
-pub async fn insert_new_media(
+pub async fn get_fingerprints(
     pool: &Pool<Sqlite>,
     folder_name: &str,
-    data: &[MediaItem],
+) -> Result<HashMap<String, FileFingerprint>, sqlx::Error> {
+    let fingerprints = sqlx::query_as::<_, FileFingerprint>(queries::GET_FINGERPRINTS)
+        .bind(folder_name)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|o| (o.path().to_string(), o))
+        .collect();
+    Ok(fingerprints)
+}
+
+/// Cached ffprobe output of the videos in the folder, keyed by path.
+pub async fn get_probes(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+) -> Result<HashMap<String, MediaProbe>, sqlx::Error> {
+    let probes = sqlx::query_as::<_, MediaProbe>(queries::GET_PROBES)
+        .bind(folder_name)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|o| (o.path().to_string(), o))
+        .collect();
+    Ok(probes)
+}
+
+/// Apply a scan to the folder in one transaction. A full scan replaces every row of the folder,
+/// otherwise only the rows owned by the dirty directories are replaced.
+pub async fn update_media(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+    scan: &ScanResult,
 ) -> Result<(), sqlx::Error> {
-    if data.is_empty() {
+    if scan.is_unchanged() {
         return Ok(());
     }
 
     let mut tx = pool.begin().await?;
-    sqlx::query(queries::CLEAR_MEDIA)
-        .bind(folder_name)
-        .execute(&mut *tx)
-        .await?;
-    sqlx::query(queries::CLEAR_TAGS)
-        .bind(folder_name)
-        .execute(&mut *tx)
-        .await?;
+    if scan.full() {
+        sqlx::query(queries::CLEAR_MEDIA)
+            .bind(folder_name)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(queries::CLEAR_TAGS)
+            .bind(folder_name)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(queries::CLEAR_COLLECTIONS)
+            .bind(folder_name)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(queries::CLEAR_FINGERPRINTS)
+            .bind(folder_name)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        delete_media_in_dirs(&mut tx, folder_name, scan.dirty_dirs()).await?;
+        delete_fingerprints(&mut tx, folder_name, scan.removed_files()).await?;
+    }
+
+    insert_media_batch(&mut tx, folder_name, scan.items()).await?;
+    upsert_collections(&mut tx, folder_name, scan.items()).await?;
+    upsert_fingerprints(&mut tx, folder_name, scan.fingerprints()).await?;
+    // probes outlive full scans, a rescan only has to probe the videos that changed
+    upsert_probes(&mut tx, folder_name, scan.probes()).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn delete_media_in_dirs(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    folder_name: &str,
+    dirs: &[String],
+) -> Result<(), sqlx::Error> {
+    for chunk in dirs.chunks(100) {
+        // tags reference media by path, so they have to go before the media rows
+        let mut query_builder =
+            QueryBuilder::<Sqlite>::new("DELETE FROM tags WHERE folder_name = ");
+        query_builder
+            .push_bind(folder_name)
+            .push(" AND path IN (SELECT path FROM media WHERE folder = ")
+            .push_bind(folder_name)
+            .push(" AND dir IN (");
+        let mut separated = query_builder.separated(", ");
+        for dir in chunk {
+            separated.push_bind(dir);
+        }
+        query_builder.push("))");
+        query_builder.build().execute(&mut **tx).await?;
+
+        let mut query_builder = QueryBuilder::<Sqlite>::new("DELETE FROM media WHERE folder = ");
+        query_builder.push_bind(folder_name).push(" AND dir IN (");
+        let mut separated = query_builder.separated(", ");
+        for dir in chunk {
+            separated.push_bind(dir);
+        }
+        query_builder.push(")");
+        query_builder.build().execute(&mut **tx).await?;
+    }
+    Ok(())
+}
+
+async fn delete_fingerprints(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    folder_name: &str,
+    paths: &[String],
+) -> Result<(), sqlx::Error> {
+    for chunk in paths.chunks(100) {
+        let mut query_builder =
+            QueryBuilder::<Sqlite>::new("DELETE FROM fingerprints WHERE folder_name = ");
+        query_builder.push_bind(folder_name).push(" AND path IN (");
+        let mut separated = query_builder.separated(", ");
+        for path in chunk {
+            separated.push_bind(path);
+        }
+        query_builder.push(")");
+        query_builder.build().execute(&mut **tx).await?;
+    }
+    Ok(())
+}
+
+async fn upsert_fingerprints(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    folder_name: &str,
+    fingerprints: &[FileFingerprint],
+) -> Result<(), sqlx::Error> {
+    for chunk in fingerprints.chunks(100) {
+        let mut query_builder = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO fingerprints (folder_name, path, size, mtime) ",
+        );
+        query_builder.push_values(chunk, |mut row, fingerprint| {
+            row.push_bind(folder_name)
+                .push_bind(fingerprint.path())
+                .push_bind(fingerprint.size())
+                .push_bind(fingerprint.mtime());
+        });
+        query_builder.push(
+            " ON CONFLICT (folder_name, path) DO UPDATE SET size = excluded.size, mtime = excluded.mtime",
+        );
+        query_builder.build().execute(&mut **tx).await?;
+    }
+    Ok(())
+}
 
+async fn insert_media_batch(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    folder_name: &str,
+    data: &[MediaItem],
+) -> Result<(), sqlx::Error> {
     // Batch insert media items in chunks to stay within SQLite bind parameter limits
     for chunk in data.chunks(100) {
         let mut query_builder = QueryBuilder::<Sqlite>::new(
-            "INSERT INTO media (type, path, title, posters, year, file, seasons, folder) ",
+            "INSERT INTO media (type, path, title, posters, year, file, seasons, folder, dir, inferred, versions, details, collection, streams) ",
         );
         query_builder.push_values(chunk, |mut row, media| {
             row.push_bind(media.media_type())
@@ -176,26 +368,100 @@ pub async fn insert_new_media(
                 .push_bind(media.year())
                 .push_bind(media.file())
                 .push_bind(media.seasons())
-                .push_bind(folder_name);
+                .push_bind(folder_name)
+                .push_bind(media.dir())
+                .push_bind(media.inferred())
+                .push_bind(media.versions())
+                .push_bind(media.details())
+                .push_bind(media.collection().map(|o| o.name()).unwrap_or_default())
+                .push_bind(media.streams());
         });
-        query_builder.build().execute(&mut *tx).await?;
+        query_builder.build().execute(&mut **tx).await?;
     }
 
     // Batch insert tags for each media item
     for media in data {
-        insert_tags_batch(&mut tx, folder_name, media.path(), media.tags(), "tags").await?;
-        insert_tags_batch(&mut tx, folder_name, media.path(), media.genres(), "genres").await?;
-        insert_tags_batch(&mut tx, folder_name, media.path(), media.actors(), "actors").await?;
+        insert_tags_batch(tx, folder_name, media.path(), media.tags(), "tags").await?;
+        insert_tags_batch(tx, folder_name, media.path(), media.genres(), "genres").await?;
+        insert_tags_batch(tx, folder_name, media.path(), media.actors(), "actors").await?;
+        insert_tags_batch(tx, folder_name, media.path(), media.studios(), "studios").await?;
+        insert_tags_batch(
+            tx,
+            folder_name,
+            media.path(),
+            media.directors(),
+            "directors",
+        )
+        .await?;
+        insert_tags_batch(
+            tx,
+            folder_name,
+            media.path(),
+            media.countries(),
+            "countries",
+        )
+        .await?;
         insert_tags_batch(
-            &mut tx,
+            tx,
             folder_name,
             media.path(),
-            media.studios(),
-            "studios",
+            media.resolutions(),
+            "resolutions",
+        )
+        .await?;
+        insert_tags_batch(tx, folder_name, media.path(), media.codecs(), "codecs").await?;
+        insert_tags_batch(
+            tx,
+            folder_name,
+            media.path(),
+            media.languages(),
+            "languages",
         )
         .await?;
     }
-    tx.commit().await?;
+    Ok(())
+}
+
+async fn upsert_collections(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    folder_name: &str,
+    data: &[MediaItem],
+) -> Result<(), sqlx::Error> {
+    for collection in data.iter().filter_map(|o| o.collection()) {
+        sqlx::query(queries::UPSERT_COLLECTION)
+            .bind(folder_name)
+            .bind(collection.name())
+            .bind(collection.overview())
+            .bind(collection.poster())
+            .execute(&mut **tx)
+            .await?;
+    }
+    sqlx::query(queries::DELETE_EMPTY_COLLECTIONS)
+        .bind(folder_name)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+async fn upsert_probes(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    folder_name: &str,
+    probes: &[MediaProbe],
+) -> Result<(), sqlx::Error> {
+    for probe in probes {
+        sqlx::query(queries::UPSERT_PROBE)
+            .bind(folder_name)
+            .bind(probe.path())
+            .bind(probe.size())
+            .bind(probe.mtime())
+            .bind(probe.output())
+            .execute(&mut **tx)
+            .await?;
+    }
+    sqlx::query(queries::DELETE_STALE_PROBES)
+        .bind(folder_name)
+        .execute(&mut **tx)
+        .await?;
     Ok(())
 }
 
@@ -230,22 +496,68 @@ pub async fn get_folder_media(
     position: &i32,
     server_port: &u16,
     filter_type: u8,
+    read_filter: u8,
     tags: &[Tag],
 ) -> Result<Vec<Media>, sqlx::Error> {
     let tags_json = serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string());
 
+    // issues of a series are listed in its seasons, their reading state is attached there
+    let mut reading_states = HashMap::new();
+    for row in sqlx::query(queries::GET_FOLDER_READING_STATES)
+        .bind(position)
+        .fetch_all(pool)
+        .await?
+    {
+        let path: String = row.try_get("path")?;
+        reading_states.insert(path, ReadingState::from_row(&row)?);
+    }
+
     let media_list = sqlx::query(queries::GET_FOLDER_CONTENT)
         .bind(&tags_json)
         .bind(position)
         .bind(filter_type)
+        .bind(read_filter)
+        .bind(MediaType::Comic as i32)
         .fetch_all(pool)
         .await?
         .iter()
-        .map(|r| Media::from_row(r, server_port))
+        .map(|r| Media::from_row(r, server_port, &reading_states))
         .collect::<Result<Vec<_>, _>>()?;
     Ok(media_list)
 }
 
+/// Movie sets of the folder with their movies in release order.
+pub async fn get_folder_collections(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    server_port: &u16,
+) -> Result<Vec<Collection>, sqlx::Error> {
+    let mut movies: HashMap<String, Vec<Value>> = HashMap::new();
+    for row in sqlx::query(queries::GET_COLLECTION_MOVIES)
+        .bind(position)
+        .fetch_all(pool)
+        .await?
+    {
+        let collection: String = row.try_get("collection")?;
+        movies
+            .entry(collection)
+            .or_default()
+            .push(Collection::movie_from_row(&row, server_port)?);
+    }
+
+    sqlx::query(queries::GET_FOLDER_COLLECTIONS)
+        .bind(position)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|r| {
+            let name: String = r.try_get("name")?;
+            let members = movies.remove(&name).unwrap_or_default();
+            Collection::from_row(r, server_port, members)
+        })
+        .collect()
+}
+
 pub async fn get_folder_media_tags(
     pool: &Pool<Sqlite>,
     position: &i32,
@@ -289,11 +601,18 @@ pub async fn update_folder_path(
     position: &i32,
     path: &str,
 ) -> Result<(), sqlx::Error> {
-    let _ = sqlx::query(queries::UPDATE_FOLDER_PATH)
+    let mut tx = pool.begin().await?;
+    sqlx::query(queries::UPDATE_FOLDER_PATH)
         .bind(path)
         .bind(position)
-        .execute(pool)
+        .execute(&mut *tx)
+        .await?;
+    // fingerprints are relative to the old root, so force a full rescan
+    sqlx::query(queries::CLEAR_FOLDER_FINGERPRINTS)
+        .bind(position)
+        .execute(&mut *tx)
         .await?;
+    tx.commit().await?;
     Ok(())
 }
 
@@ -352,6 +671,276 @@ pub async fn update_folder_filter_type(
     Ok(())
 }
 
+pub async fn update_folder_watch(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    watch: bool,
+) -> Result<(), sqlx::Error> {
+    let _ = sqlx::query(queries::UPDATE_FOLDER_WATCH)
+        .bind(watch as i32)
+        .bind(position)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_folder_image_comics(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+) -> Result<bool, sqlx::Error> {
+    let image_comics: i32 = sqlx::query_scalar(queries::GET_FOLDER_IMAGE_COMICS)
+        .bind(folder_name)
+        .fetch_one(pool)
+        .await?;
+    Ok(image_comics != 0)
+}
+
+pub async fn update_folder_image_comics(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    image_comics: bool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(queries::UPDATE_FOLDER_IMAGE_COMICS)
+        .bind(image_comics as i32)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    // loose images are only fingerprinted in this mode, so force a full rescan
+    sqlx::query(queries::CLEAR_FOLDER_FINGERPRINTS)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_folder_probe_media(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+) -> Result<bool, sqlx::Error> {
+    let probe_media: i32 = sqlx::query_scalar(queries::GET_FOLDER_PROBE_MEDIA)
+        .bind(folder_name)
+        .fetch_one(pool)
+        .await?;
+    Ok(probe_media != 0)
+}
+
+pub async fn update_folder_probe_media(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    probe_media: bool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(queries::UPDATE_FOLDER_PROBE_MEDIA)
+        .bind(probe_media as i32)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    // videos are only probed while their directory is parsed, so force a full rescan
+    sqlx::query(queries::CLEAR_FOLDER_FINGERPRINTS)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Root path of the library containing the indexed media `path` of the given type, or None when
+/// the folder has no such media.
+pub async fn get_media_library_path(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+    path: &str,
+    media_type: MediaType,
+) -> Result<Option<String>, sqlx::Error> {
+    let row = sqlx::query(queries::GET_MEDIA_LIBRARY_PATH)
+        .bind(folder_name)
+        .bind(path)
+        .bind(media_type as i32)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|o| o.get(0)))
+}
+
+/// Library root and the videos, relative to it, of the movie or the show holding `path`. A movie
+/// lists its file and the parts of all its versions, a show the files of its episodes.
+pub async fn get_media_videos(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+    path: &str,
+) -> Result<Option<(String, Vec<PathBuf>)>, sqlx::Error> {
+    let row = sqlx::query(queries::GET_MEDIA_VIDEOS)
+        .bind(folder_name)
+        .bind(path)
+        .bind(MediaType::Movie as i32)
+        .bind(MediaType::TvShow as i32)
+        .fetch_optional(pool)
+        .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let video_path = |path: &str, file: &str| match file {
+        // loose movies are keyed by their video
+        "" => PathBuf::from(path),
+        file => Path::new(path).join(file),
+    };
+    let text = |value: &Value, name: &str| {
+        value
+            .get(name)
+            .and_then(|o| o.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let parse = |column: &str| -> Result<Value, sqlx::Error> {
+        let value = row.try_get::<String, _>(column)?;
+        Ok(serde_json::from_str(&value).unwrap_or(Value::Null))
+    };
+
+    let mut videos = Vec::new();
+    if row.try_get::<i32, _>("t")? == MediaType::Movie as i32 {
+        videos.push(video_path(
+            &row.try_get::<String, _>("path")?,
+            &row.try_get::<String, _>("file")?,
+        ));
+    }
+    for version in parse("versions")?.as_array().into_iter().flatten() {
+        let parts = version.get("parts").and_then(|o| o.as_array());
+        videos.extend(
+            parts
+                .into_iter()
+                .flatten()
+                .filter_map(|o| o.as_str())
+                .map(PathBuf::from),
+        );
+    }
+    for episodes in parse("seasons")?
+        .as_object()
+        .into_iter()
+        .flat_map(|o| o.values())
+    {
+        for episode in episodes.as_array().into_iter().flatten() {
+            videos.push(video_path(&text(episode, "path"), &text(episode, "file")));
+        }
+    }
+    Ok(Some((row.try_get("root")?, videos)))
+}
+
+/// Record the page reached in a comic. Reaching the last page marks it as read.
+pub async fn update_reading_progress(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    path: &str,
+    current_page: u32,
+    total_pages: u32,
+) -> Result<(), sqlx::Error> {
+    let read = total_pages > 0 && current_page + 1 >= total_pages;
+    let last_read = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|o| o.as_millis() as i64)
+        .unwrap_or_default();
+    let _ = sqlx::query(queries::UPSERT_READING_PROGRESS)
+        .bind(position)
+        .bind(path)
+        .bind(current_page)
+        .bind(total_pages)
+        .bind(read as i32)
+        .bind(last_read)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn update_read_status(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    path: &str,
+    read: bool,
+) -> Result<(), sqlx::Error> {
+    let _ = sqlx::query(queries::UPSERT_READ_STATUS)
+        .bind(position)
+        .bind(path)
+        .bind(read as i32)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Title, year, NFO details and tag groups of a media for its detail page.
+pub async fn get_media_details(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    path: &str,
+) -> Result<Option<Value>, sqlx::Error> {
+    let Some(row) = sqlx::query(queries::GET_MEDIA_DETAILS)
+        .bind(position)
+        .bind(path)
+        .fetch_optional(pool)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let details = row.get::<String, _>("details");
+    let mut result = match serde_json::from_str::<Value>(&details) {
+        Ok(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    result.insert("title".to_string(), json!(row.get::<String, _>("title")));
+    result.insert(
+        "year".to_string(),
+        json!(row.get::<Option<String>, _>("year").unwrap_or_default()),
+    );
+    let streams = row.get::<String, _>("streams");
+    result.insert(
+        "streams".to_string(),
+        serde_json::from_str(&streams).unwrap_or(Value::Null),
+    );
+
+    let tags = sqlx::query(queries::GET_MEDIA_TAGS)
+        .bind(position)
+        .bind(path)
+        .fetch_all(pool)
+        .await?;
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for tag in tags {
+        groups
+            .entry(tag.get("t"))
+            .or_default()
+            .push(tag.get("name"));
+    }
+    for group in [
+        "genres",
+        "tags",
+        "actors",
+        "studios",
+        "directors",
+        "countries",
+        "resolutions",
+        "codecs",
+        "languages",
+    ] {
+        result.insert(
+            group.to_string(),
+            json!(groups.remove(group).unwrap_or_default()),
+        );
+    }
+    Ok(Some(Value::Object(result)))
+}
+
+pub async fn get_reading_state(
+    pool: &Pool<Sqlite>,
+    position: &i32,
+    path: &str,
+) -> Result<Option<ReadingState>, sqlx::Error> {
+    let reading_state = sqlx::query_as::<_, ReadingState>(queries::GET_READING_STATE)
+        .bind(position)
+        .bind(path)
+        .fetch_optional(pool)
+        .await?;
+    Ok(reading_state)
+}
+
 pub async fn get_folder_position(
     pool: &Pool<Sqlite>,
     name: &str,
@@ -378,7 +967,9 @@ mod tests {
         serde_json::from_value(json!({"group": group, "label": label})).unwrap()
     }
 
-    use crate::model::parser::{Media as MediaBuilder, MediaType};
+    use crate::model::parser::Media as MediaBuilder;
+    use crate::model::parser::{MovieSet, MovieVersion};
+    use std::collections::BTreeMap;
     use std::ffi::OsString;
 
     async fn setup_pool() -> Pool<Sqlite> {
@@ -387,78 +978,567 @@ mod tests {
         pool
     }
 
-    fn movie(
-        title: &str,
-        year: &str,
-        file: &str,
-        genres: &[&str],
-        actors: &[&str],
-        studios: &[&str],
-    ) -> MediaItem {
-        let mut m = MediaBuilder::default();
-        m.set_media_type(MediaType::Movie);
-        m.set_relative_path(OsString::from(title));
-        m.set_title(title.to_string());
-        m.set_year(year.to_string());
-        m.set_file(file.to_string());
-        m.add_poster("poster.jpg".to_string());
-        for g in genres {
-            m.add_genre(g.to_string());
-        }
-        m.set_actors(actors.iter().map(|a| a.to_string()).collect());
-        for s in studios {
-            m.add_studio(s.to_string());
+    fn movie(
+        title: &str,
+        year: &str,
+        file: &str,
+        genres: &[&str],
+        actors: &[&str],
+        studios: &[&str],
+    ) -> MediaItem {
+        let mut m = MediaBuilder::default();
+        m.set_media_type(MediaType::Movie);
+        m.set_relative_path(OsString::from(title));
+        m.set_title(title.to_string());
+        m.set_year(year.to_string());
+        m.set_file(file.to_string());
+        m.add_poster("poster.jpg".to_string());
+        for g in genres {
+            m.add_genre(g.to_string());
+        }
+        m.set_actors(actors.iter().map(|a| a.to_string()).collect());
+        for s in studios {
+            m.add_studio(s.to_string());
+        }
+        m.movie().unwrap()
+    }
+
+    async fn seed_data(pool: &Pool<Sqlite>) {
+        insert_folder_data(pool, "Movie", "/movies").await.unwrap();
+
+        let media = vec![
+            movie(
+                "John Wick",
+                "2014",
+                "John Wick.mkv",
+                &["Action", "Thriller"],
+                &["Keanu Reeves"],
+                &["Summit Entertainment"],
+            ),
+            movie(
+                "The Dark Knight",
+                "2008",
+                "The Dark Knight.mkv",
+                &["Action", "Crime", "Drama"],
+                &["Christian Bale"],
+                &["Warner Bros"],
+            ),
+            movie(
+                "Blade Runner",
+                "1982",
+                "Blade Runner.mkv",
+                &["Thriller", "Sci-Fi"],
+                &["Harrison Ford"],
+                &[],
+            ),
+            movie(
+                "Dune",
+                "2021",
+                "Dune.mkv",
+                &["Action", "Sci-Fi", "Drama"],
+                &[],
+                &["Warner Bros"],
+            ),
+            movie(
+                "Love Letter",
+                "1995",
+                "Love Letter.mp4",
+                &["Drama", "Romance"],
+                &[],
+                &[],
+            ),
+        ];
+
+        let scan = ScanResult::new(media, vec![], vec![], vec![], true);
+        update_media(pool, "Movie", &scan).await.unwrap();
+    }
+
+    fn movie_in_dir(title: &str, genres: &[&str]) -> MediaItem {
+        let mut m = MediaBuilder::default();
+        m.set_media_type(MediaType::Movie);
+        m.set_relative_path(OsString::from(title));
+        m.set_source_dir(OsString::from(title));
+        m.set_title(title.to_string());
+        for g in genres {
+            m.add_genre(g.to_string());
+        }
+        m.movie().unwrap()
+    }
+
+    async fn media_titles(pool: &Pool<Sqlite>) -> Vec<String> {
+        sqlx::query_scalar("SELECT title FROM media ORDER BY title")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    // -- migrations --
+
+    #[tokio::test]
+    async fn create_tables_applies_all_migrations_once() {
+        let pool = setup_pool().await;
+        // running again on an up-to-date database must be a no-op
+        create_tables(&pool).await.unwrap();
+
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(version, queries::MIGRATIONS.len() as i64);
+    }
+
+    // -- update_media --
+
+    #[tokio::test]
+    async fn update_media_replaces_only_dirty_directories() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+
+        let initial = ScanResult::new(
+            vec![
+                movie_in_dir("Heat", &["Crime"]),
+                movie_in_dir("Ronin", &["Action"]),
+            ],
+            vec![
+                FileFingerprint::new("Heat/movie.nfo".to_string(), 1, 1),
+                FileFingerprint::new("Ronin/movie.nfo".to_string(), 1, 1),
+            ],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "Movie", &initial).await.unwrap();
+
+        let mut heat = MediaBuilder::default();
+        heat.set_media_type(MediaType::Movie);
+        heat.set_relative_path(OsString::from("Heat"));
+        heat.set_source_dir(OsString::from("Heat"));
+        heat.set_title("Heat (Director's Cut)".to_string());
+        let incremental = ScanResult::new(
+            vec![heat.movie().unwrap()],
+            vec![FileFingerprint::new("Heat/movie.nfo".to_string(), 2, 2)],
+            vec![],
+            vec!["Heat".to_string()],
+            false,
+        );
+        update_media(&pool, "Movie", &incremental).await.unwrap();
+
+        assert_eq!(
+            media_titles(&pool).await,
+            vec!["Heat (Director's Cut)", "Ronin"]
+        );
+        // tags of the replaced movie are gone, untouched movies keep theirs
+        let tags: Vec<String> = sqlx::query_scalar("SELECT name FROM tags ORDER BY name")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(tags, vec!["Action"]);
+
+        let fingerprints = get_fingerprints(&pool, "Movie").await.unwrap();
+        assert_eq!(fingerprints["Heat/movie.nfo"].size(), 2);
+        assert_eq!(fingerprints["Ronin/movie.nfo"].size(), 1);
+    }
+
+    #[tokio::test]
+    async fn update_media_removes_deleted_directories() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+
+        let initial = ScanResult::new(
+            vec![movie_in_dir("Heat", &[]), movie_in_dir("Ronin", &[])],
+            vec![
+                FileFingerprint::new("Heat/movie.nfo".to_string(), 1, 1),
+                FileFingerprint::new("Ronin/movie.nfo".to_string(), 1, 1),
+            ],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "Movie", &initial).await.unwrap();
+
+        let removal = ScanResult::new(
+            vec![],
+            vec![],
+            vec!["Ronin/movie.nfo".to_string()],
+            vec!["Ronin".to_string()],
+            false,
+        );
+        update_media(&pool, "Movie", &removal).await.unwrap();
+
+        assert_eq!(media_titles(&pool).await, vec!["Heat"]);
+        let fingerprints = get_fingerprints(&pool, "Movie").await.unwrap();
+        assert!(!fingerprints.contains_key("Ronin/movie.nfo"));
+    }
+
+    #[tokio::test]
+    async fn folder_collections_list_movies_in_release_order() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+
+        let in_set = |title: &str, year: &str, overview: &str, poster: &str| {
+            let mut m = MediaBuilder::default();
+            m.set_media_type(MediaType::Movie);
+            m.set_relative_path(OsString::from(title));
+            m.set_source_dir(OsString::from(title));
+            m.set_title(title.to_string());
+            m.set_year(year.to_string());
+            let mut set = MovieSet::new("Alien Collection".to_string(), overview.to_string());
+            set.set_poster(poster.to_string());
+            m.set_collection(set);
+            m.movie().unwrap()
+        };
+        let initial = ScanResult::new(
+            vec![
+                in_set("Aliens", "1986", "", ""),
+                in_set(
+                    "Alien",
+                    "1979",
+                    "Xenomorphs.",
+                    "Alien Collection/poster.jpg",
+                ),
+                movie_in_dir("Heat", &[]),
+            ],
+            vec![],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "Movie", &initial).await.unwrap();
+
+        let collections = get_folder_collections(&pool, &0, &8080).await.unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].name(), "Alien Collection");
+        assert_eq!(
+            collections[0].poster(),
+            Some("http://127.0.0.1:8080/covers/Movie/Alien%20Collection/poster")
+        );
+        let titles = collections[0]
+            .movies()
+            .iter()
+            .map(|o| o["title"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(titles, vec!["Alien", "Aliens"]);
+        let json = serde_json::to_value(&collections[0]).unwrap();
+        assert_eq!(json["overview"], "Xenomorphs.");
+
+        let media = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
+        let json = serde_json::to_value(&media).unwrap();
+        assert_eq!(json[0]["collection"], "Alien Collection");
+        assert_eq!(json[2]["collection"], "");
+
+        // a set is gone together with its last movie
+        let removal = ScanResult::new(
+            vec![],
+            vec![],
+            vec![],
+            vec!["Alien".to_string(), "Aliens".to_string()],
+            false,
+        );
+        update_media(&pool, "Movie", &removal).await.unwrap();
+        assert!(get_folder_collections(&pool, &0, &8080)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn folder_media_keeps_remote_artwork_urls() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+
+        let mut heat = MediaBuilder::default();
+        heat.set_media_type(MediaType::Movie);
+        heat.set_relative_path(OsString::from("Heat"));
+        heat.set_title("Heat".to_string());
+        heat.add_poster("Heat-fanart.jpg".to_string());
+        heat.add_artwork("", "banner", "https://example.com/banner.jpg".to_string());
+        let scan = ScanResult::new(vec![heat.movie().unwrap()], vec![], vec![], vec![], true);
+        update_media(&pool, "Movie", &scan).await.unwrap();
+
+        let media = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
+        let json = serde_json::to_value(&media[0]).unwrap();
+        assert_eq!(
+            json["posters"],
+            json!({
+                "fanart": "http://127.0.0.1:8080/covers/Movie/Heat/Heat-fanart",
+                "banner": "https://example.com/banner.jpg"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn folder_media_points_episode_stills_to_the_cover_server() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "TV", "/tv").await.unwrap();
+
+        let mut show = MediaBuilder::default();
+        show.set_media_type(MediaType::TvShow);
+        show.set_relative_path(OsString::from("Show"));
+        show.set_title("Show".to_string());
+        let episode = |number: &str, thumb: &str| {
+            let mut m = MediaBuilder::default();
+            m.set_media_type(MediaType::Episode);
+            m.set_relative_path(OsString::from("Show/Season 01"));
+            m.set_season("1".to_string());
+            m.set_episode(number.to_string());
+            m.add_artwork("", "thumb", thumb.to_string());
+            m
+        };
+        let pilot = episode("1", "S01E01-thumb.jpg");
+        let second = episode("2", "https://example.com/still.jpg");
+        let seasons = HashMap::from([("01".to_string(), vec![&pilot, &second])]);
+        let scan = ScanResult::new(
+            vec![show.tv_show(Some(&seasons)).unwrap()],
+            vec![],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "TV", &scan).await.unwrap();
+
+        let media = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
+        let json = serde_json::to_value(&media[0]).unwrap();
+        assert_eq!(
+            json["seasons"]["01"][0]["thumb"],
+            "http://127.0.0.1:8080/covers/TV/Show/Season%2001/S01E01-thumb"
+        );
+        assert_eq!(
+            json["seasons"]["01"][1]["thumb"],
+            "https://example.com/still.jpg"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_tags_are_filterable_and_listed_in_details() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+
+        let in_resolution = |title: &str, width: u32, height: u32| {
+            let mut m = MediaBuilder::default();
+            m.set_media_type(MediaType::Movie);
+            m.set_relative_path(OsString::from(title));
+            m.set_title(title.to_string());
+            m.streams_mut().set_video_codec("h264".to_string());
+            m.streams_mut().set_size(width, height);
+            m.streams_mut()
+                .add_audio("ac3".to_string(), "eng".to_string(), 6);
+            m.movie().unwrap()
+        };
+        let scan = ScanResult::new(
+            vec![
+                in_resolution("Heat", 1920, 800),
+                in_resolution("Ronin", 720, 576),
+            ],
+            vec![],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "Movie", &scan).await.unwrap();
+
+        let tags = vec![tag("resolutions", "1080p")];
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &tags)
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].title(), "Heat");
+
+        let details = get_media_details(&pool, &0, "Ronin")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(details["resolutions"], json!(["SD"]));
+        assert_eq!(details["codecs"], json!(["H.264"]));
+        assert_eq!(details["languages"], json!(["eng"]));
+        assert_eq!(details["streams"]["width"], 720);
+        assert_eq!(details["streams"]["audio"][0]["channels"], 6);
+    }
+
+    #[tokio::test]
+    async fn update_folder_path_clears_fingerprints() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+        let scan = ScanResult::new(
+            vec![movie_in_dir("Heat", &[])],
+            vec![FileFingerprint::new("Heat/movie.nfo".to_string(), 1, 1)],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "Movie", &scan).await.unwrap();
+
+        update_folder_path(&pool, &0, "/new/movies").await.unwrap();
+
+        assert!(get_fingerprints(&pool, "Movie").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_folder_image_comics_clears_fingerprints() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Comics", "/comics")
+            .await
+            .unwrap();
+        assert!(!get_folder_image_comics(&pool, "Comics").await.unwrap());
+
+        let scan = ScanResult::new(
+            vec![],
+            vec![FileFingerprint::new("Saga.cbz".to_string(), 1, 1)],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "Comics", &scan).await.unwrap();
+
+        update_folder_image_comics(&pool, &0, true).await.unwrap();
+
+        assert!(get_folder_image_comics(&pool, "Comics").await.unwrap());
+        assert!(get_fingerprints(&pool, "Comics").await.unwrap().is_empty());
+        let data = get_folder_data(&pool, &0).await.unwrap();
+        assert_eq!(serde_json::to_value(&data).unwrap()["imageComics"], true);
+    }
+
+    #[tokio::test]
+    async fn probes_outlive_full_scans_until_their_video_is_removed() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movies", "/movies")
+            .await
+            .unwrap();
+        assert!(!get_folder_probe_media(&pool, "Movies").await.unwrap());
+
+        let heat = FileFingerprint::new("Heat.mkv".to_string(), 1, 1);
+        let ronin = FileFingerprint::new("Ronin.mkv".to_string(), 2, 2);
+        let mut scan = ScanResult::new(
+            vec![],
+            vec![heat.clone(), ronin.clone()],
+            vec![],
+            vec![],
+            true,
+        );
+        scan.set_probes(vec![
+            MediaProbe::new(&heat, "{}".to_string()),
+            MediaProbe::new(&ronin, "{}".to_string()),
+        ]);
+        update_media(&pool, "Movies", &scan).await.unwrap();
+
+        // a full rescan probing nothing keeps the cache of the videos still there
+        let scan = ScanResult::new(vec![], vec![heat.clone()], vec![], vec![], true);
+        update_media(&pool, "Movies", &scan).await.unwrap();
+        let probes = get_probes(&pool, "Movies").await.unwrap();
+        assert_eq!(
+            probes.into_values().collect::<Vec<MediaProbe>>(),
+            vec![MediaProbe::new(&heat, "{}".to_string())]
+        );
+
+        update_folder_probe_media(&pool, &0, true).await.unwrap();
+
+        assert!(get_folder_probe_media(&pool, "Movies").await.unwrap());
+        assert!(get_fingerprints(&pool, "Movies").await.unwrap().is_empty());
+        let data = get_folder_data(&pool, &0).await.unwrap();
+        assert_eq!(serde_json::to_value(&data).unwrap()["probeMedia"], true);
+    }
+
+    #[tokio::test]
+    async fn get_media_library_path_finds_issues_of_a_series() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Comics", "/comics")
+            .await
+            .unwrap();
+
+        let issue = |path: &str| {
+            let mut m = MediaBuilder::default();
+            m.set_media_type(MediaType::Comic);
+            m.set_relative_path(OsString::from(path));
+            m
+        };
+        let (first, second) = (issue("Saga/1.cbz"), issue("Saga/2.cbz"));
+        let mut series = issue("Saga");
+        series.set_title("Saga".to_string());
+        let volumes = BTreeMap::from([("01".to_string(), vec![&first, &second])]);
+        let items = vec![
+            series.comic_series(&volumes).unwrap(),
+            issue("One.cbz").comic().unwrap(),
+        ];
+        update_media(
+            &pool,
+            "Comics",
+            &ScanResult::new(items, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
+
+        for path in ["Saga/2.cbz", "One.cbz"] {
+            let root = get_media_library_path(&pool, "Comics", path, MediaType::Comic)
+                .await
+                .unwrap();
+            assert_eq!(root.as_deref(), Some("/comics"));
         }
-        m.movie().unwrap()
+        let missing = get_media_library_path(&pool, "Comics", "Saga/3.cbz", MediaType::Comic)
+            .await
+            .unwrap();
+        assert_eq!(missing, None);
     }
 
-    async fn seed_data(pool: &Pool<Sqlite>) {
-        insert_folder_data(pool, "Movie", "/movies").await.unwrap();
-
-        let media = vec![
-            movie(
-                "John Wick",
-                "2014",
-                "John Wick.mkv",
-                &["Action", "Thriller"],
-                &["Keanu Reeves"],
-                &["Summit Entertainment"],
-            ),
-            movie(
-                "The Dark Knight",
-                "2008",
-                "The Dark Knight.mkv",
-                &["Action", "Crime", "Drama"],
-                &["Christian Bale"],
-                &["Warner Bros"],
-            ),
-            movie(
-                "Blade Runner",
-                "1982",
-                "Blade Runner.mkv",
-                &["Thriller", "Sci-Fi"],
-                &["Harrison Ford"],
-                &[],
-            ),
-            movie(
-                "Dune",
-                "2021",
-                "Dune.mkv",
-                &["Action", "Sci-Fi", "Drama"],
-                &[],
-                &["Warner Bros"],
+    #[tokio::test]
+    async fn get_media_videos_lists_versions_and_episodes() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Video", "/video").await.unwrap();
+
+        let mut movie = MediaBuilder::default();
+        movie.set_media_type(MediaType::Movie);
+        movie.set_relative_path(OsString::from("Heat"));
+        movie.set_file("Heat - 2160p.mkv".to_string());
+        movie.set_versions(vec![
+            MovieVersion::new(
+                "2160p".to_string(),
+                vec!["Heat/Heat - 2160p.mkv".to_string()],
             ),
-            movie(
-                "Love Letter",
-                "1995",
-                "Love Letter.mp4",
-                &["Drama", "Romance"],
-                &[],
-                &[],
+            MovieVersion::new(
+                "1080p".to_string(),
+                vec!["Heat/Heat - 1080p.mkv".to_string()],
             ),
+        ]);
+        let mut show = MediaBuilder::default();
+        show.set_media_type(MediaType::TvShow);
+        show.set_relative_path(OsString::from("Show"));
+        let mut pilot = MediaBuilder::default();
+        pilot.set_media_type(MediaType::Episode);
+        pilot.set_relative_path(OsString::from("Show/Season 01"));
+        pilot.set_file("S01E01.mkv".to_string());
+        let seasons = HashMap::from([("01".to_string(), vec![&pilot])]);
+        let items = vec![
+            movie.movie().unwrap(),
+            show.tv_show(Some(&seasons)).unwrap(),
         ];
+        update_media(
+            &pool,
+            "Video",
+            &ScanResult::new(items, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
 
-        insert_new_media(pool, "Movie", &media).await.unwrap();
+        let (root, videos) = get_media_videos(&pool, "Video", "Heat")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(root, "/video");
+        assert_eq!(
+            videos,
+            vec![
+                Path::new("Heat").join("Heat - 2160p.mkv"),
+                PathBuf::from("Heat/Heat - 2160p.mkv"),
+                PathBuf::from("Heat/Heat - 1080p.mkv"),
+            ]
+        );
+        let (_, videos) = get_media_videos(&pool, "Video", "Show/Season 01")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(videos, vec![Path::new("Show/Season 01").join("S01E01.mkv")]);
+        assert_eq!(
+            get_media_videos(&pool, "Video", "Ronin").await.unwrap(),
+            None
+        );
     }
 
     // -- get_folder_media_tags --
@@ -512,7 +1592,7 @@ mod tests {
         let pool = setup_pool().await;
         seed_data(&pool).await;
 
-        let result = get_folder_media(&pool, &0, &8080, 0, &[]).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
         assert_eq!(result.len(), 5);
     }
 
@@ -523,7 +1603,9 @@ mod tests {
 
         // OR filter with Action genre -> John Wick, The Dark Knight, Dune
         let tags = vec![tag("genres", "Action")];
-        let result = get_folder_media(&pool, &0, &8080, 0, &tags).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &tags)
+            .await
+            .unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         assert_eq!(titles.len(), 3);
         assert!(titles.contains(&"John Wick"));
@@ -539,7 +1621,9 @@ mod tests {
         // OR filter: Action OR Thriller -> any media with at least one
         // John Wick (Action+Thriller), Dark Knight (Action), Blade Runner (Thriller), Dune (Action)
         let tags = vec![tag("genres", "Action"), tag("genres", "Thriller")];
-        let result = get_folder_media(&pool, &0, &8080, 0, &tags).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &tags)
+            .await
+            .unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         assert_eq!(titles.len(), 4);
         assert!(titles.contains(&"John Wick"));
@@ -558,7 +1642,9 @@ mod tests {
         // AND filter: Action AND Thriller -> must have both
         // Only John Wick has both Action + Thriller
         let tags = vec![tag("genres", "Action"), tag("genres", "Thriller")];
-        let result = get_folder_media(&pool, &0, &8080, 1, &tags).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 1, 0, &tags)
+            .await
+            .unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         assert_eq!(titles, vec!["John Wick"]);
     }
@@ -574,7 +1660,9 @@ mod tests {
         // Warner Bros: The Dark Knight, Dune
         // No overlap -> empty (each group must pass independently)
         let tags = vec![tag("genres", "Romance"), tag("studios", "Warner Bros")];
-        let result = get_folder_media(&pool, &0, &8080, 0, &tags).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &tags)
+            .await
+            .unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         // Must satisfy both groups: Romance genre AND Warner Bros studio
         // No movie has both -> empty
@@ -591,7 +1679,9 @@ mod tests {
         // Warner Bros: Dark Knight, Dune
         // Intersection (must pass both groups): Dark Knight, Dune
         let tags = vec![tag("genres", "Drama"), tag("studios", "Warner Bros")];
-        let result = get_folder_media(&pool, &0, &8080, 0, &tags).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &tags)
+            .await
+            .unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         assert_eq!(titles.len(), 2);
         assert!(titles.contains(&"The Dark Knight"));
@@ -604,7 +1694,9 @@ mod tests {
         seed_data(&pool).await;
 
         let tags = vec![tag("genres", "Horror")];
-        let result = get_folder_media(&pool, &0, &8080, 0, &tags).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &tags)
+            .await
+            .unwrap();
         assert!(result.is_empty());
     }
 
@@ -619,6 +1711,7 @@ mod tests {
         // hide_panel=0 serializes as showSidePanel=true
         assert_eq!(json["showSidePanel"], true);
         assert_eq!(json["skipFolders"], json!([]));
+        assert_eq!(json["coverFormat"], "jpeg");
     }
 
     #[tokio::test]
@@ -630,6 +1723,30 @@ mod tests {
         assert_eq!(result, vec!["_Todo", "_Bonus"]);
     }
 
+    #[tokio::test]
+    async fn update_cover_format_clears_fingerprints_of_every_folder() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movies", "/movies")
+            .await
+            .unwrap();
+        let scan = ScanResult::new(
+            vec![],
+            vec![FileFingerprint::new("Heat/poster.jpg".to_string(), 1, 1)],
+            vec![],
+            vec![],
+            true,
+        );
+        update_media(&pool, "Movies", &scan).await.unwrap();
+        assert_eq!(get_cover_format(&pool).await.unwrap(), CoverFormat::Jpeg);
+
+        update_cover_format(&pool, CoverFormat::WebP).await.unwrap();
+
+        assert_eq!(get_cover_format(&pool).await.unwrap(), CoverFormat::WebP);
+        assert!(get_fingerprints(&pool, "Movies").await.unwrap().is_empty());
+        let json = serde_json::to_value(get_settings(&pool).await.unwrap()).unwrap();
+        assert_eq!(json["coverFormat"], "webp");
+    }
+
     #[tokio::test]
     async fn update_hide_side_panel_toggles() {
         let pool = setup_pool().await;
@@ -795,6 +1912,25 @@ mod tests {
         assert_eq!(tag_count, 0);
     }
 
+    #[tokio::test]
+    async fn update_folder_watch_toggles_watched_folders() {
+        let pool = setup_pool().await;
+
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+        insert_folder_data(&pool, "TV", "/tv").await.unwrap();
+        assert!(get_watched_folders(&pool).await.unwrap().is_empty());
+
+        update_folder_watch(&pool, &1, true).await.unwrap();
+        let folders = get_watched_folders(&pool).await.unwrap();
+        let names: Vec<&str> = folders.iter().map(|f| f.folder_name()).collect();
+        assert_eq!(names, vec!["TV"]);
+        let data = get_folder_data(&pool, &1).await.unwrap();
+        assert_eq!(serde_json::to_value(&data).unwrap()["watch"], true);
+
+        update_folder_watch(&pool, &1, false).await.unwrap();
+        assert!(get_watched_folders(&pool).await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn get_folder_position_returns_correct_position() {
         let pool = setup_pool().await;
@@ -831,7 +1967,7 @@ mod tests {
         let pool = setup_pool().await;
         seed_data(&pool).await;
 
-        let result = get_folder_media(&pool, &0, &8080, 0, &[]).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         // Default sort (sort_type=0) is by path ascending
         assert_eq!(
@@ -852,7 +1988,7 @@ mod tests {
         seed_data(&pool).await;
 
         update_sort_type(&pool, &0, &1).await.unwrap();
-        let result = get_folder_media(&pool, &0, &8080, 0, &[]).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         assert_eq!(
             titles,
@@ -872,7 +2008,7 @@ mod tests {
         seed_data(&pool).await;
 
         update_sort_type(&pool, &0, &2).await.unwrap();
-        let result = get_folder_media(&pool, &0, &8080, 0, &[]).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         assert_eq!(
             titles,
@@ -892,7 +2028,7 @@ mod tests {
         seed_data(&pool).await;
 
         update_sort_type(&pool, &0, &3).await.unwrap();
-        let result = get_folder_media(&pool, &0, &8080, 0, &[]).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         // 1982, 1995, 2008, 2014, 2021
         assert_eq!(
@@ -913,7 +2049,7 @@ mod tests {
         seed_data(&pool).await;
 
         update_sort_type(&pool, &0, &4).await.unwrap();
-        let result = get_folder_media(&pool, &0, &8080, 0, &[]).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
         let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
         // 2021, 2014, 2008, 1995, 1982
         assert_eq!(
@@ -933,7 +2069,7 @@ mod tests {
         let pool = setup_pool().await;
         seed_data(&pool).await;
 
-        let result = get_folder_media(&pool, &0, &8080, 0, &[]).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
         let first = &result[0];
         let json = serde_json::to_value(first).unwrap();
         let poster_url = json["posters"]["main"].as_str().unwrap();
@@ -943,4 +2079,310 @@ mod tests {
             poster_url
         );
     }
+
+    #[tokio::test]
+    async fn folder_media_marks_inferred_movies() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+
+        let mut inferred = MediaBuilder::default();
+        inferred.set_media_type(MediaType::Movie);
+        inferred.set_relative_path(OsString::from("Heat.1995.mkv"));
+        inferred.set_title("Heat".to_string());
+        inferred.set_inferred(true);
+        let items = vec![
+            movie("Alien", "1979", "Alien.mkv", &[], &[], &[]),
+            inferred.movie().unwrap(),
+        ];
+        update_media(
+            &pool,
+            "Movie",
+            &ScanResult::new(items, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
+
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
+        let flags = result
+            .iter()
+            .map(|o| serde_json::to_value(o).unwrap())
+            .map(|o| (o["title"].clone(), o["inferred"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            vec![(json!("Alien"), json!(false)), (json!("Heat"), json!(true))]
+        );
+    }
+
+    #[tokio::test]
+    async fn media_details_include_nfo_details_and_tags() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Movie", "/movies").await.unwrap();
+
+        let mut heat = MediaBuilder::default();
+        heat.set_media_type(MediaType::Movie);
+        heat.set_relative_path(OsString::from("Heat"));
+        heat.set_title("Heat".to_string());
+        heat.set_year("1995".to_string());
+        heat.add_genre("Crime".to_string());
+        heat.details_mut().set_plot("Bank robbers.".to_string());
+        heat.details_mut().add_director("Michael Mann".to_string());
+        heat.details_mut().add_country("USA".to_string());
+        let items = vec![
+            heat.movie().unwrap(),
+            movie("Alien", "1979", "Alien.mkv", &[], &[], &[]),
+        ];
+        update_media(
+            &pool,
+            "Movie",
+            &ScanResult::new(items, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
+
+        let details = get_media_details(&pool, &0, "Heat").await.unwrap().unwrap();
+        assert_eq!(details["title"], "Heat");
+        assert_eq!(details["year"], "1995");
+        assert_eq!(details["plot"], "Bank robbers.");
+        assert_eq!(details["genres"], json!(["Crime"]));
+        assert_eq!(details["directors"], json!(["Michael Mann"]));
+        assert!(get_media_details(&pool, &0, "Ronin")
+            .await
+            .unwrap()
+            .is_none());
+
+        // directors and countries filter like any other tag group
+        let tags = vec![tag("directors", "Michael Mann")];
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &tags)
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].title(), "Heat");
+        let tags = get_folder_media_tags(&pool, &0).await.unwrap();
+        let labels = tags.iter().map(|o| o["label"].clone()).collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![json!("countries"), json!("directors"), json!("genres")]
+        );
+    }
+
+    #[tokio::test]
+    async fn reading_progress_round_trips() {
+        let pool = setup_pool().await;
+        seed_data(&pool).await;
+
+        assert_eq!(get_reading_state(&pool, &0, "Dune").await.unwrap(), None);
+
+        update_reading_progress(&pool, &0, "Dune", 3, 10)
+            .await
+            .unwrap();
+        let state = get_reading_state(&pool, &0, "Dune").await.unwrap().unwrap();
+        assert_eq!(state.current_page(), 3);
+        assert!(!state.is_read());
+        assert!(state.last_read().is_some());
+
+        // reaching the last page marks the comic as read
+        update_reading_progress(&pool, &0, "Dune", 9, 10)
+            .await
+            .unwrap();
+        let state = get_reading_state(&pool, &0, "Dune").await.unwrap().unwrap();
+        assert_eq!(state.current_page(), 9);
+        assert!(state.is_read());
+    }
+
+    #[tokio::test]
+    async fn update_read_status_keeps_progress() {
+        let pool = setup_pool().await;
+        seed_data(&pool).await;
+
+        update_read_status(&pool, &0, "Dune", true).await.unwrap();
+        let state = get_reading_state(&pool, &0, "Dune").await.unwrap().unwrap();
+        assert!(state.is_read());
+        assert_eq!(state.current_page(), 0);
+        assert_eq!(state.last_read(), None);
+
+        update_reading_progress(&pool, &0, "Dune", 4, 10)
+            .await
+            .unwrap();
+        update_read_status(&pool, &0, "Dune", true).await.unwrap();
+        let state = get_reading_state(&pool, &0, "Dune").await.unwrap().unwrap();
+        assert!(state.is_read());
+        assert_eq!(state.current_page(), 4);
+    }
+
+    #[tokio::test]
+    async fn folder_media_filters_by_read_state() {
+        let pool = setup_pool().await;
+        seed_data(&pool).await;
+
+        update_reading_progress(&pool, &0, "John Wick", 2, 10)
+            .await
+            .unwrap();
+        update_read_status(&pool, &0, "Dune", true).await.unwrap();
+
+        let titles = |media: Vec<Media>| {
+            media
+                .iter()
+                .map(|m| m.title().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        let unread = get_folder_media(&pool, &0, &8080, 0, 1, &[]).await.unwrap();
+        assert_eq!(
+            titles(unread),
+            vec![
+                "Blade Runner",
+                "John Wick",
+                "Love Letter",
+                "The Dark Knight"
+            ]
+        );
+
+        let in_progress = get_folder_media(&pool, &0, &8080, 0, 2, &[]).await.unwrap();
+        assert_eq!(in_progress[0].reading_state().unwrap().current_page(), 2);
+        assert_eq!(titles(in_progress), vec!["John Wick"]);
+
+        let read = get_folder_media(&pool, &0, &8080, 0, 3, &[]).await.unwrap();
+        assert_eq!(titles(read), vec!["Dune"]);
+    }
+
+    #[tokio::test]
+    async fn folder_media_sort_by_last_read() {
+        let pool = setup_pool().await;
+        seed_data(&pool).await;
+
+        update_reading_progress(&pool, &0, "Blade Runner", 1, 10)
+            .await
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        update_reading_progress(&pool, &0, "Love Letter", 1, 10)
+            .await
+            .unwrap();
+
+        update_sort_type(&pool, &0, &5).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
+        let titles: Vec<&str> = result.iter().map(|m| m.title()).collect();
+        // most recently read first, never opened media by path
+        assert_eq!(
+            titles,
+            vec![
+                "Love Letter",
+                "Blade Runner",
+                "Dune",
+                "John Wick",
+                "The Dark Knight"
+            ]
+        );
+
+        let json = serde_json::to_value(&result[0]).unwrap();
+        assert_eq!(json["readingState"]["currentPage"], 1);
+        assert_eq!(json["readingState"]["read"], false);
+        assert_eq!(
+            serde_json::to_value(&result[2]).unwrap()["readingState"],
+            Value::Null
+        );
+    }
+
+    #[tokio::test]
+    async fn folder_media_folds_reading_state_of_series_issues() {
+        let pool = setup_pool().await;
+        insert_folder_data(&pool, "Comics", "/comics")
+            .await
+            .unwrap();
+
+        let issue = |path: &str| {
+            let mut m = MediaBuilder::default();
+            m.set_media_type(MediaType::Comic);
+            m.set_relative_path(OsString::from(path));
+            m.set_title(path.to_string());
+            m
+        };
+        let (first, second) = (issue("Saga/1.cbz"), issue("Saga/2.cbz"));
+        let series = issue("Saga");
+        let volumes = BTreeMap::from([("01".to_string(), vec![&first, &second])]);
+        let items = vec![
+            series.comic_series(&volumes).unwrap(),
+            issue("One.cbz").comic().unwrap(),
+        ];
+        update_media(
+            &pool,
+            "Comics",
+            &ScanResult::new(items, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
+
+        let titles = |media: Vec<Media>| {
+            media
+                .iter()
+                .map(|m| m.title().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        // one finished issue leaves the series in progress
+        update_reading_progress(&pool, &0, "Saga/1.cbz", 9, 10)
+            .await
+            .unwrap();
+        let in_progress = get_folder_media(&pool, &0, &8080, 0, 2, &[]).await.unwrap();
+        let issues = &serde_json::to_value(&in_progress[0]).unwrap()["seasons"]["01"];
+        assert_eq!(issues[0]["readingState"]["currentPage"], 9);
+        assert_eq!(issues[0]["readingState"]["read"], true);
+        assert_eq!(issues[1]["readingState"], Value::Null);
+        assert_eq!(titles(in_progress), vec!["Saga"]);
+        let unread = get_folder_media(&pool, &0, &8080, 0, 1, &[]).await.unwrap();
+        assert_eq!(titles(unread), vec!["One.cbz", "Saga"]);
+        assert!(get_folder_media(&pool, &0, &8080, 0, 3, &[])
+            .await
+            .unwrap()
+            .is_empty());
+
+        // the series resumes at the issue read last and is read once all issues are
+        std::thread::sleep(Duration::from_millis(5));
+        update_reading_progress(&pool, &0, "Saga/2.cbz", 2, 20)
+            .await
+            .unwrap();
+        update_read_status(&pool, &0, "Saga/2.cbz", true)
+            .await
+            .unwrap();
+        let read = get_folder_media(&pool, &0, &8080, 0, 3, &[]).await.unwrap();
+        assert_eq!(read[0].reading_state().unwrap().current_page(), 2);
+        assert!(read[0].reading_state().unwrap().is_read());
+        assert_eq!(titles(read), vec!["Saga"]);
+
+        std::thread::sleep(Duration::from_millis(5));
+        update_reading_progress(&pool, &0, "One.cbz", 1, 10)
+            .await
+            .unwrap();
+        update_sort_type(&pool, &0, &5).await.unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
+        assert_eq!(titles(result), vec!["One.cbz", "Saga"]);
+        std::thread::sleep(Duration::from_millis(5));
+        update_reading_progress(&pool, &0, "Saga/1.cbz", 9, 10)
+            .await
+            .unwrap();
+        let result = get_folder_media(&pool, &0, &8080, 0, 0, &[]).await.unwrap();
+        assert_eq!(titles(result), vec!["Saga", "One.cbz"]);
+    }
+
+    #[tokio::test]
+    async fn reading_state_survives_full_rescan() {
+        let pool = setup_pool().await;
+        seed_data(&pool).await;
+
+        update_reading_progress(&pool, &0, "Dune", 3, 10)
+            .await
+            .unwrap();
+        let media = vec![movie("Dune", "2021", "Dune.mkv", &[], &[], &[])];
+        update_media(
+            &pool,
+            "Movie",
+            &ScanResult::new(media, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
+
+        let result = get_folder_media(&pool, &0, &8080, 0, 2, &[]).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].reading_state().unwrap().current_page(), 3);
+    }
 }
@@ -1,8 +1,27 @@
 pub const CREATE_TABLE_QUERY: &str = include_str!("sql/create_tables.sql");
 
+// Schema changes applied on top of CREATE_TABLE_QUERY, in order. The number of applied
+// migrations is tracked with `PRAGMA user_version`, so entries must never be reordered or edited.
+pub const MIGRATIONS: &[&str] = &[
+    include_str!("sql/migrations/001_file_fingerprints.sql"),
+    include_str!("sql/migrations/002_folder_watch.sql"),
+    include_str!("sql/migrations/003_reading_state.sql"),
+    include_str!("sql/migrations/004_image_folder_comics.sql"),
+    include_str!("sql/migrations/005_inferred_media.sql"),
+    include_str!("sql/migrations/006_movie_versions.sql"),
+    include_str!("sql/migrations/007_disc_structures.sql"),
+    include_str!("sql/migrations/008_media_details.sql"),
+    include_str!("sql/migrations/009_movie_collections.sql"),
+    include_str!("sql/migrations/010_stream_details.sql"),
+    include_str!("sql/migrations/011_media_probes.sql"),
+    include_str!("sql/migrations/012_cover_format.sql"),
+];
+
 // Binds: ?1 = tags JSON array (e.g. '[{"group":"genres","label":"Action"}]' or '[]')
 //        ?2 = folder position
 //        ?3 = filter_type (0 = OR, 1 = AND)
+//        ?4 = read_filter (0 = all, 1 = unread, 2 = in progress, 3 = read)
+//        ?5 = comic type
 //
 // - filter_tags CTE unpacks the JSON array into rows via json_each.
 // - filter_groups CTE counts how many tags were selected per group.
@@ -10,6 +29,10 @@ pub const CREATE_TABLE_QUERY: &str = include_str!("sql/create_tables.sql");
 //     >= 1 for OR mode, or = tag_count for AND mode.
 // - When no tags are passed, filter_groups is empty, NOT EXISTS is
 //   vacuously true, and all media in the folder are returned.
+// - reading_state is left joined, media never opened count as unread.
+// - reading_paths CTE maps a comic series to the paths of its issues, any other media to its own
+//   path. reading CTE folds their states: a series is read once all issues are, in progress once
+//   any issue is opened, and carries the pages and last_read of the issue read last.
 pub const GET_FOLDER_CONTENT: &str = include_str!("sql/get_folder_content.sql");
 
 //language=sqlite
@@ -32,6 +55,21 @@ pub const UPDATE_SKIP_FOLDERS: &str = "
     UPDATE settings SET skip_folders = ? WHERE settings_id=0
     ";
 
+//language=sqlite
+pub const GET_COVER_FORMAT: &str = "
+    SELECT cover_format FROM settings
+    ";
+
+//language=sqlite
+pub const UPDATE_COVER_FORMAT: &str = "
+    UPDATE settings SET cover_format = ? WHERE settings_id=0
+    ";
+
+//language=sqlite
+pub const CLEAR_ALL_FINGERPRINTS: &str = "
+    DELETE FROM fingerprints
+    ";
+
 //language=sqlite
 pub const INSERT_NEW_FOLDER_DATA: &str = "
     INSERT INTO folders (folder_name, position, path)
@@ -43,6 +81,11 @@ pub const GET_FOLDER_LIST: &str = "
     SELECT folder_name, path, position from folders ORDER BY position
     ";
 
+//language=sqlite
+pub const GET_WATCHED_FOLDERS: &str = "
+    SELECT folder_name, path, position from folders WHERE watch = 1 ORDER BY position
+    ";
+
 //language=sqlite
 pub const GET_FOLDER_DATA: &str = "
     SELECT * from folders WHERE position=?
@@ -58,6 +101,27 @@ pub const CLEAR_TAGS: &str = "
     DELETE FROM tags WHERE folder_name = ?
     ";
 
+//language=sqlite
+pub const CLEAR_COLLECTIONS: &str = "
+    DELETE FROM collections WHERE folder_name = ?
+    ";
+
+//language=sqlite
+pub const CLEAR_FINGERPRINTS: &str = "
+    DELETE FROM fingerprints WHERE folder_name = ?
+    ";
+
+//language=sqlite
+pub const CLEAR_FOLDER_FINGERPRINTS: &str = "
+    DELETE FROM fingerprints
+    WHERE folder_name = (SELECT folder_name FROM folders WHERE position = ?)
+    ";
+
+//language=sqlite
+pub const GET_FINGERPRINTS: &str = "
+    SELECT path, size, mtime FROM fingerprints WHERE folder_name = ?
+    ";
+
 //language=sqlite
 pub const UPDATE_SORT_TYPE: &str = "
      UPDATE folders SET sort_type = ? WHERE position=?
@@ -88,6 +152,34 @@ pub const UPDATE_FOLDER_STATUS: &str = "
      UPDATE folders SET status = ? WHERE position = ?
     ";
 
+// Binds: ?1 = folder name, ?2 = media path, ?3 = media type
+// Comic issues grouped into a series are only listed in the seasons of the series item.
+//language=sqlite
+pub const GET_MEDIA_LIBRARY_PATH: &str = "
+     SELECT f.path FROM media m JOIN folders f ON m.folder = f.folder_name
+     WHERE m.folder = ?1 AND m.type = ?3
+       AND (m.path = ?2
+         OR EXISTS (SELECT 1
+                    FROM json_each(CASE WHEN json_valid(m.seasons) THEN m.seasons ELSE '{}' END) v,
+                         json_each(v.value) i
+                    WHERE json_extract(i.value, '$.path') = ?2))
+     LIMIT 1
+    ";
+
+// Binds: ?1 = folder name, ?2 = movie path or episode path, ?3 = movie type, ?4 = tv show type
+//language=sqlite
+pub const GET_MEDIA_VIDEOS: &str = "
+     SELECT f.path AS root, m.type AS t, m.path, m.file, m.versions, m.seasons
+     FROM media m JOIN folders f ON m.folder = f.folder_name
+     WHERE m.folder = ?1 AND m.type IN (?3, ?4)
+       AND (m.path = ?2
+         OR EXISTS (SELECT 1
+                    FROM json_each(CASE WHEN json_valid(m.seasons) THEN m.seasons ELSE '{}' END) v,
+                         json_each(v.value) i
+                    WHERE json_extract(i.value, '$.path') = ?2))
+     LIMIT 1
+    ";
+
 //language=sqlite
 pub const GET_FOLDER_POSITION: &str = "
      SELECT position from folders WHERE folder_name=? AND path=?
@@ -106,3 +198,138 @@ pub const UPDATE_FOLDER_FILTER_TYPE: &str = "
     SET filter_type = 1 - filter_type
     WHERE position = ?
 ";
+
+//language=sqlite
+pub const UPDATE_FOLDER_WATCH: &str = "
+    UPDATE folders SET watch = ? WHERE position = ?
+";
+
+//language=sqlite
+pub const GET_FOLDER_IMAGE_COMICS: &str = "
+    SELECT image_comics FROM folders WHERE folder_name = ?
+";
+
+//language=sqlite
+pub const UPDATE_FOLDER_IMAGE_COMICS: &str = "
+    UPDATE folders SET image_comics = ? WHERE position = ?
+";
+
+//language=sqlite
+pub const GET_FOLDER_PROBE_MEDIA: &str = "
+    SELECT probe_media FROM folders WHERE folder_name = ?
+";
+
+//language=sqlite
+pub const UPDATE_FOLDER_PROBE_MEDIA: &str = "
+    UPDATE folders SET probe_media = ? WHERE position = ?
+";
+
+//language=sqlite
+pub const GET_PROBES: &str = "
+    SELECT path, size, mtime, output FROM probes WHERE folder_name = ?
+";
+
+// Binds: ?1 = folder name, ?2 = path, ?3 = size, ?4 = mtime, ?5 = ffprobe output
+//language=sqlite
+pub const UPSERT_PROBE: &str = "
+    INSERT INTO probes (folder_name, path, size, mtime, output)
+    VALUES (?1, ?2, ?3, ?4, ?5)
+    ON CONFLICT (folder_name, path) DO UPDATE SET size   = excluded.size,
+                                                  mtime  = excluded.mtime,
+                                                  output = excluded.output
+";
+
+// Videos removed from the library lose their fingerprint and their cached output with it
+//language=sqlite
+pub const DELETE_STALE_PROBES: &str = "
+    DELETE FROM probes
+    WHERE folder_name = ?1
+      AND path NOT IN (SELECT path FROM fingerprints WHERE folder_name = ?1)
+";
+
+// Binds: ?1 = folder position, ?2 = path, ?3 = current page, ?4 = total pages, ?5 = read flag,
+//        ?6 = last read timestamp in milliseconds
+//language=sqlite
+pub const UPSERT_READING_PROGRESS: &str = "
+    INSERT INTO reading_state (folder_name, path, current_page, total_pages, read, last_read)
+    SELECT folder_name, ?2, ?3, ?4, ?5, ?6 FROM folders WHERE position = ?1
+    ON CONFLICT (folder_name, path) DO UPDATE SET current_page = excluded.current_page,
+                                                  total_pages  = excluded.total_pages,
+                                                  read         = excluded.read,
+                                                  last_read    = excluded.last_read
+";
+
+//language=sqlite
+pub const UPSERT_READ_STATUS: &str = "
+    INSERT INTO reading_state (folder_name, path, read)
+    SELECT folder_name, ?2, ?3 FROM folders WHERE position = ?1
+    ON CONFLICT (folder_name, path) DO UPDATE SET read = excluded.read
+";
+
+//language=sqlite
+pub const GET_MEDIA_DETAILS: &str = "
+    SELECT m.title, m.year, m.details, m.streams
+    FROM media m JOIN folders f ON m.folder = f.folder_name
+    WHERE f.position = ? AND m.path = ?
+    ORDER BY m.type
+    LIMIT 1
+";
+
+//language=sqlite
+pub const GET_MEDIA_TAGS: &str = "
+    SELECT t.t, t.name
+    FROM tags t JOIN folders f ON t.folder_name = f.folder_name
+    WHERE f.position = ? AND t.path = ?
+    ORDER BY t.rowid
+";
+
+// Binds: ?1 = folder name, ?2 = set name, ?3 = overview, ?4 = poster
+// Every movie of a set carries it, values a movie comes without are kept.
+//language=sqlite
+pub const UPSERT_COLLECTION: &str = "
+    INSERT INTO collections (folder_name, name, overview, poster)
+    VALUES (?1, ?2, ?3, ?4)
+    ON CONFLICT (folder_name, name) DO UPDATE SET
+        overview = CASE WHEN excluded.overview <> '' THEN excluded.overview ELSE overview END,
+        poster   = CASE WHEN excluded.poster <> '' THEN excluded.poster ELSE poster END
+";
+
+// Sets lose their last movie when those are removed or rescanned without the set
+//language=sqlite
+pub const DELETE_EMPTY_COLLECTIONS: &str = "
+    DELETE FROM collections
+    WHERE folder_name = ?1
+      AND name NOT IN (SELECT collection FROM media WHERE folder = ?1)
+";
+
+//language=sqlite
+pub const GET_FOLDER_COLLECTIONS: &str = "
+    SELECT c.name, c.overview, c.poster, c.folder_name
+    FROM collections c JOIN folders f ON c.folder_name = f.folder_name
+    WHERE f.position = ?
+    ORDER BY c.name
+";
+
+// Movies of every set in the folder, in release order
+//language=sqlite
+pub const GET_COLLECTION_MOVIES: &str = "
+    SELECT m.collection, m.type AS t, m.path, m.title, m.year, m.posters, m.file, f.folder_name
+    FROM media m JOIN folders f ON m.folder = f.folder_name
+    WHERE f.position = ? AND m.collection <> ''
+    ORDER BY m.collection, m.year, m.title
+";
+
+// Reading state of every media and issue in the folder, keyed by path
+//language=sqlite
+pub const GET_FOLDER_READING_STATES: &str = "
+    SELECT r.path, r.current_page, r.total_pages, r.read, r.last_read
+    FROM reading_state r JOIN folders f ON r.folder_name = f.folder_name
+    WHERE f.position = ?
+";
+
+//language=sqlite
+pub const GET_READING_STATE: &str = "
+    SELECT r.current_page, r.total_pages, r.read, r.last_read
+    FROM reading_state r JOIN folders f ON r.folder_name = f.folder_name
+    WHERE f.position = ? AND r.path = ?
+";
@@ -3,7 +3,34 @@ WITH filter_tags AS (SELECT json_extract(value, '$.group') AS t,
                      FROM json_each(?1)),
      filter_groups AS (SELECT t, COUNT(*) AS tag_count
                        FROM filter_tags
-                       GROUP BY t)
+                       GROUP BY t),
+     reading_paths AS (SELECT media.folder AS folder_name, media.path, media.path AS state_path
+                       FROM media
+                                JOIN folders ON media.folder = folders.folder_name
+                       WHERE folders.position = ?2
+                         AND (media.type != ?5 OR media.seasons = '')
+                       UNION ALL
+                       SELECT media.folder, media.path, json_extract(issue.value, '$.path')
+                       FROM media
+                                JOIN folders ON media.folder = folders.folder_name,
+                            json_each(CASE WHEN json_valid(media.seasons) THEN media.seasons ELSE '{}' END) volume,
+                            json_each(volume.value) issue
+                       WHERE folders.position = ?2
+                         AND media.type = ?5),
+     reading AS (SELECT reading_paths.folder_name,
+                        reading_paths.path,
+                        reading_state.current_page,
+                        reading_state.total_pages,
+                        MIN(COALESCE(reading_state.read, 0)) OVER paths                           AS read,
+                        MAX(reading_state.current_page > 0 OR reading_state.read = 1) OVER paths AS started,
+                        MAX(reading_state.last_read) OVER paths                                   AS last_read,
+                        ROW_NUMBER() OVER (paths ORDER BY reading_state.path IS NULL,
+                            reading_state.last_read DESC)                                         AS recency
+                 FROM reading_paths
+                          LEFT JOIN reading_state
+                                    ON reading_state.folder_name = reading_paths.folder_name AND
+                                       reading_state.path = reading_paths.state_path
+                 WINDOW paths AS (PARTITION BY reading_paths.folder_name, reading_paths.path))
 SELECT media.type as t,
        media.path,
        media.title,
@@ -11,10 +38,25 @@ SELECT media.type as t,
        media.year,
        media.file,
        media.seasons,
-       folders.folder_name
+       media.inferred,
+       media.versions,
+       media.collection,
+       folders.folder_name,
+       reading.current_page,
+       reading.total_pages,
+       reading.read,
+       reading.last_read
 FROM media
          JOIN folders ON media.folder = folders.folder_name
+         LEFT JOIN reading
+                   ON reading.folder_name = folders.folder_name AND reading.path = media.path AND reading.recency = 1
 WHERE folders.position = ?2
+  AND CASE ?4
+          WHEN 1 THEN COALESCE(reading.read, 0) = 0
+          WHEN 2 THEN COALESCE(reading.read, 0) = 0 AND reading.started = 1
+          WHEN 3 THEN reading.read = 1
+          ELSE 1
+    END
   AND NOT EXISTS (SELECT 1
                   FROM filter_groups fg
                   WHERE (SELECT COUNT(DISTINCT tags.name)
@@ -26,6 +68,7 @@ WHERE folders.position = ?2
 ORDER BY CASE
              WHEN folders.sort_type = 2 THEN media.title
              WHEN folders.sort_type = 4 THEN media.year
+             WHEN folders.sort_type = 5 THEN reading.last_read
              END DESC,
          CASE
              WHEN folders.sort_type = 1 THEN media.title
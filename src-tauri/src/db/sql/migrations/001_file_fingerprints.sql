@@ -0,0 +1,16 @@
+alter table media
+    add column dir TEXT default '' not null;
+create index if not exists media_folder_dir_index
+    on media (folder, dir);
+create table if not exists fingerprints
+(
+    folder_name TEXT    not null
+        constraint fingerprints_folders_folder_name_fk
+            references folders
+            on update cascade on delete cascade,
+    path        TEXT    not null,
+    size        INTEGER not null,
+    mtime       INTEGER not null,
+    constraint fingerprints_pk
+        primary key (folder_name, path)
+);
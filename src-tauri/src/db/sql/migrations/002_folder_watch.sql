@@ -0,0 +1,2 @@
+alter table folders
+    add column watch INTEGER default 0 not null;
@@ -0,0 +1,14 @@
+create table if not exists reading_state
+(
+    folder_name  TEXT              not null
+        constraint reading_state_folders_folder_name_fk
+            references folders
+            on update cascade on delete cascade,
+    path         TEXT              not null,
+    current_page INTEGER default 0 not null,
+    total_pages  INTEGER default 0 not null,
+    read         INTEGER default 0 not null,
+    last_read    INTEGER,
+    constraint reading_state_pk
+        primary key (folder_name, path)
+);
@@ -0,0 +1,5 @@
+alter table folders
+    add column image_comics INTEGER default 0 not null;
+-- comic covers moved to covers/<folder name>, a full rescan regenerates them
+delete
+from fingerprints;
@@ -0,0 +1,5 @@
+alter table media
+    add column inferred INTEGER default 0 not null;
+-- directories without NFO files were skipped so far, a full rescan picks up their movies
+delete
+from fingerprints;
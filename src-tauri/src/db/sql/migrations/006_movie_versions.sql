@@ -0,0 +1,5 @@
+alter table media
+    add column versions TEXT default '' not null;
+-- movies only kept their first file so far, a full rescan collects their parts and versions
+delete
+from fingerprints;
@@ -0,0 +1,3 @@
+-- disc structures were dropped so far, a full rescan picks up their movies
+delete
+from fingerprints;
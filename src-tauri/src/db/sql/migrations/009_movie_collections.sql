@@ -0,0 +1,17 @@
+alter table media
+    add column collection TEXT default '' not null;
+create table if not exists collections
+(
+    folder_name TEXT not null
+        constraint collections_folders_folder_name_fk
+            references folders
+            on update cascade on delete cascade,
+    name        TEXT not null,
+    overview    TEXT default '' not null,
+    poster      TEXT default '' not null,
+    constraint collections_pk
+        primary key (folder_name, name)
+);
+-- movie sets were not read so far, a full rescan collects them
+delete
+from fingerprints;
@@ -0,0 +1,29 @@
+alter table media
+    add column streams TEXT default '' not null;
+-- resolutions, codecs and audio languages from the stream details are filterable as well
+create table tags_new
+(
+    folder_name TEXT not null
+        constraint tags_folders_folder_name_fk
+            references folders
+            on update cascade on delete cascade,
+    path        TEXT not null,
+    t           TEXT not null,
+    name        TEXT not null,
+    constraint tags_pk
+        unique (folder_name, path, name, t),
+    constraint tags_t_check
+        check (t IN ('genres', 'tags', 'actors', 'studios', 'directors', 'countries', 'resolutions',
+                     'codecs', 'languages'))
+);
+insert into tags_new (folder_name, path, t, name)
+select folder_name, path, t, name
+from tags;
+drop table tags;
+alter table tags_new
+    rename to tags;
+create index if not exists tags_folder_name_index
+    on tags (folder_name);
+-- stream details were not read so far, a full rescan collects them
+delete
+from fingerprints;
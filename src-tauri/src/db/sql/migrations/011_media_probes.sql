@@ -0,0 +1,15 @@
+alter table folders
+    add column probe_media INTEGER default 0 not null;
+create table if not exists probes
+(
+    folder_name TEXT    not null
+        constraint probes_folders_folder_name_fk
+            references folders
+            on update cascade on delete cascade,
+    path        TEXT    not null,
+    size        INTEGER not null,
+    mtime       INTEGER not null,
+    output      TEXT    not null,
+    constraint probes_pk
+        primary key (folder_name, path)
+);
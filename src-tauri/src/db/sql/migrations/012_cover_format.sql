@@ -0,0 +1,5 @@
+alter table settings
+    add column cover_format TEXT default 'jpeg' not null;
+-- covers were converted to avif by ffmpeg so far, a full rescan converts them in-process
+delete
+from fingerprints;
@@ -1,7 +1,28 @@
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
 
 const IMAGE_EXTENSIONS: &[&str] = &[".jpg", ".png", ".jpeg", ".bmp", ".gif", ".webp"];
 const COMIC_EXTENSIONS: &[&str] = &[".cbz", ".cbr", ".cbt", ".cb7"];
+// Kodi artwork types, named by the suffix of an image file or the aspect of an NFO <thumb>
+const ART_TYPES: &[&str] = &[
+    "poster",
+    "fanart",
+    "banner",
+    "clearlogo",
+    "clearart",
+    "landscape",
+    "discart",
+    "thumb",
+];
+// other names Kodi accepts for some of the artwork types
+const ART_ALIASES: &[(&str, &str)] = &[
+    ("folder", "poster"),
+    ("backdrop", "fanart"),
+    ("logo", "clearlogo"),
+    ("disc", "discart"),
+];
+// Blu-ray and DVD folder structures with the file a player opens them by
+const DISC_TARGETS: &[(&str, &str)] = &[("BDMV", "index.bdmv"), ("VIDEO_TS", "VIDEO_TS.IFO")];
 
 fn strip_extensions(path: &str, extensions: &[&str]) -> String {
     let result = path.replace('\\', "/");
@@ -21,6 +42,120 @@ pub fn strip_comic_extensions(path: &str) -> String {
     strip_extensions(path, COMIC_EXTENSIONS)
 }
 
+pub fn is_image_file(path: &str) -> bool {
+    let lowercase = path.to_lowercase();
+    IMAGE_EXTENSIONS.iter().any(|ext| lowercase.ends_with(ext))
+}
+
+/// Kodi artwork type of an image named like `poster.jpg`, `Heat-fanart.jpg` or
+/// `season01-banner.png`.
+pub fn get_art_type(file_name: &str) -> Option<&'static str> {
+    let stem = Path::new(file_name).file_stem()?.to_str()?.to_lowercase();
+    let suffix = stem.rsplit('-').next().unwrap_or_default();
+    ART_TYPES
+        .iter()
+        .find(|o| **o == suffix)
+        .copied()
+        .or_else(|| {
+            ART_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == suffix)
+                .map(|(_, art_type)| *art_type)
+        })
+        // posters used to be matched by name only, e.g. `poster1.jpg`
+        .or_else(|| stem.contains("poster").then_some("poster"))
+}
+
+/// Artwork referenced by an NFO file may be a URL instead of a file next to it.
+pub fn is_remote_image(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// The file a player opens for a disc folder structure named `dir_name`, if it is one.
+pub fn get_disc_target(dir_name: &str) -> Option<&'static str> {
+    DISC_TARGETS
+        .iter()
+        .find(|(dir, _)| dir_name.eq_ignore_ascii_case(dir))
+        .map(|(_, target)| *target)
+}
+
+/// The directory holding the disc folder structure `path` lies in, e.g. `Heat` for
+/// `Heat/BDMV/STREAM/00001.m2ts`.
+pub fn get_disc_parent(path: &Path) -> Option<PathBuf> {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        if get_disc_target(&component.as_os_str().to_string_lossy()).is_some() {
+            return Some(result);
+        }
+        result.push(component);
+    }
+    None
+}
+
+/// Name with the characters file systems reject replaced, the way Kodi names the folder of a
+/// movie set, e.g. `Mission_ Impossible Collection` for `Mission: Impossible Collection`.
+pub fn get_legal_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Hidden entries and the resource forks macOS adds to archives are never part of a comic.
+pub fn is_hidden_entry(path: &str) -> bool {
+    Path::new(&path.replace('\\', "/")).components().any(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name.starts_with('.') && name != "." && name != ".."
+            || name == "__MACOSX"
+            || name.eq_ignore_ascii_case("Thumbs.db")
+    })
+}
+
+/// Compare names the way a person would, so that "page2" sorts before "page10". Runs of digits
+/// are compared by value, everything else case-insensitively.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_digits = take_digits(&mut a_chars);
+                let y_digits = take_digits(&mut b_chars);
+                let x_value = x_digits.trim_start_matches('0');
+                let y_value = y_digits.trim_start_matches('0');
+                let ordering = x_value
+                    .len()
+                    .cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|o| o.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits
+}
+
 pub fn get_cached_image_path(server_port: &u16, folder_name: &str, src: &str) -> String {
     let cleanup_image_path = strip_image_extensions(src);
 
@@ -36,7 +171,7 @@ pub fn get_cached_image_path(server_port: &u16, folder_name: &str, src: &str) ->
         })
         .collect::<Vec<_>>()
         .join("/");
-    format!("http://127.0.0.1:{}/{}", server_port, encoded_path)
+    format!("http://127.0.0.1:{}/covers/{}", server_port, encoded_path)
 }
 
 #[cfg(test)]
@@ -87,15 +222,89 @@ mod tests {
         assert_eq!(strip_comic_extensions("folder\\comic.cbz"), "folder/comic");
     }
 
+    #[test]
+    fn is_image_file_ignores_case() {
+        assert!(is_image_file("Pages/001.JPG"));
+        assert!(is_image_file("cover.webp"));
+        assert!(!is_image_file("ComicInfo.xml"));
+    }
+
+    #[test]
+    fn get_disc_parent_finds_disc_structures() {
+        let stream = Path::new("Heat")
+            .join("BDMV")
+            .join("STREAM")
+            .join("00001.m2ts");
+        assert_eq!(get_disc_parent(&stream), Some(PathBuf::from("Heat")));
+        let dvd = Path::new("Movies").join("Ronin").join("video_ts");
+        assert_eq!(
+            get_disc_parent(&dvd),
+            Some(Path::new("Movies").join("Ronin"))
+        );
+        assert_eq!(get_disc_parent(Path::new("Heat/Heat.mkv")), None);
+        assert_eq!(get_disc_target("Video_TS"), Some("VIDEO_TS.IFO"));
+    }
+
+    #[test]
+    fn get_art_type_detects_kodi_artwork() {
+        assert_eq!(get_art_type("poster.jpg"), Some("poster"));
+        assert_eq!(get_art_type("Heat (1995)-fanart.jpg"), Some("fanart"));
+        assert_eq!(get_art_type("season01-banner.png"), Some("banner"));
+        assert_eq!(get_art_type("season-specials-poster.jpg"), Some("poster"));
+        assert_eq!(get_art_type("S01E01-thumb.jpg"), Some("thumb"));
+        assert_eq!(get_art_type("folder.jpg"), Some("poster"));
+        assert_eq!(get_art_type("logo.png"), Some("clearlogo"));
+        assert_eq!(get_art_type("poster1.jpg"), Some("poster"));
+        assert_eq!(get_art_type("Blade-Runner.jpg"), None);
+        assert_eq!(get_art_type("page01.jpg"), None);
+    }
+
+    #[test]
+    fn get_legal_file_name_replaces_reserved_characters() {
+        assert_eq!(
+            get_legal_file_name("Mission: Impossible Collection"),
+            "Mission_ Impossible Collection"
+        );
+        assert_eq!(get_legal_file_name("AC/DC"), "AC_DC");
+        assert_eq!(get_legal_file_name("Alien Collection"), "Alien Collection");
+    }
+
+    #[test]
+    fn is_hidden_entry_detects_system_files() {
+        assert!(is_hidden_entry("__MACOSX/._001.jpg"));
+        assert!(is_hidden_entry("pages/.DS_Store"));
+        assert!(is_hidden_entry("pages\\Thumbs.db"));
+        assert!(!is_hidden_entry("pages/001.jpg"));
+    }
+
+    #[test]
+    fn natural_cmp_orders_numbers_by_value() {
+        let mut names = vec!["page10.jpg", "Page2.jpg", "page1.jpg", "page02b.jpg"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["page1.jpg", "Page2.jpg", "page02b.jpg", "page10.jpg"]
+        );
+    }
+
+    #[test]
+    fn natural_cmp_is_total_for_equal_values() {
+        assert_eq!(natural_cmp("01.jpg", "1.jpg"), "01.jpg".cmp("1.jpg"));
+        assert_eq!(natural_cmp("a.jpg", "a.jpg"), Ordering::Equal);
+    }
+
     #[test]
     fn get_cached_image_path_constructs_url() {
         let result = get_cached_image_path(&8080, "Movie", "poster.jpg");
-        assert_eq!(result, "http://127.0.0.1:8080/Movie/poster");
+        assert_eq!(result, "http://127.0.0.1:8080/covers/Movie/poster");
     }
 
     #[test]
     fn get_cached_image_path_encodes_special_chars() {
         let result = get_cached_image_path(&8080, "Movie", "John Wick/poster.jpg");
-        assert_eq!(result, "http://127.0.0.1:8080/Movie/John%20Wick/poster");
+        assert_eq!(
+            result,
+            "http://127.0.0.1:8080/covers/Movie/John%20Wick/poster"
+        );
     }
 }
@@ -4,20 +4,23 @@
 )]
 
 use crate::db::main::{create_pool, get_database_path};
-use crate::model::database::{Folder, FolderData, Media, Setting, Tag};
+use crate::model::database::{Collection, Folder, FolderData, Media, ReadingState, Setting, Tag};
+use crate::model::parser::{CoverFormat, ScanSettings};
 use log::{error, info, LevelFilter};
 use serde_json::Value;
 use sqlx::{Pool, Sqlite};
-use std::{collections::HashSet, fs, process::Command};
+use std::{collections::HashSet, fs, path::PathBuf, process::Command, sync::Arc};
 use tauri::{Emitter, Manager, Runtime, State};
 use tauri_plugin_log::{Target, TargetKind};
 use tauri_plugin_notification::NotificationExt;
+use watcher::{LibraryWatcher, ScanQueue};
 
 mod db;
 mod helper;
 mod model;
 mod parser;
 mod server;
+mod watcher;
 
 #[derive(Clone, serde::Serialize)]
 struct Payload {
@@ -35,10 +38,15 @@ struct DatabaseConnectionState(Pool<Sqlite>);
 
 struct ServerPort(u16);
 
+struct FolderWatcherState(LibraryWatcher);
+
+struct ScanQueueState(Arc<ScanQueue>);
+
 #[tauri::command]
 async fn parser<R: Runtime>(
     app_handle: tauri::AppHandle<R>,
     database_state: State<'_, DatabaseConnectionState>,
+    scan_queue_state: State<'_, ScanQueueState>,
     position: i32,
     name: &str,
     path: &str,
@@ -47,6 +55,7 @@ async fn parser<R: Runtime>(
     let name = String::from(name);
     let path = String::from(path);
     let pool = database_state.0.clone();
+    let scans = scan_queue_state.0.clone();
 
     let handle = tauri::async_runtime::spawn(async move {
         let path = path.as_str();
@@ -78,6 +87,7 @@ async fn parser<R: Runtime>(
             }
         }
 
+        let _guard = scans.lock(name).await;
         process_parsing(&app_handle, &pool, name, path, folder_position).await;
     });
 
@@ -94,16 +104,25 @@ async fn parser<R: Runtime>(
 async fn update_folder_path<R: Runtime>(
     app_handle: tauri::AppHandle<R>,
     database_state: State<'_, DatabaseConnectionState>,
+    watcher_state: State<'_, FolderWatcherState>,
+    scan_queue_state: State<'_, ScanQueueState>,
     name: String,
     position: i32,
     path: String,
 ) -> Result<(), String> {
     let pool = database_state.0.clone();
+    let scans = scan_queue_state.0.clone();
+
+    let watcher = &watcher_state.0;
+    if watcher.is_watching(&name) {
+        watch_folder(&app_handle, watcher, &scans, &pool, &name, &path)?;
+    }
 
     let handle = tauri::async_runtime::spawn(async move {
         let path = path.as_str();
         let name = name.as_str();
 
+        let _guard = scans.lock(name).await;
         if let Err(e) = db::main::update_folder_path(&pool, &position, path).await {
             error!("Fail to update folder path. Raising Error: {:?}", e);
             return;
@@ -186,27 +205,188 @@ async fn handle_parsing<R: Runtime>(
         .emit("parsing", invalidation_payload)
         .expect("Fail to send message to refresh status on loading.");
 
+    scan_folder(app_handle, pool, name, path, None).await?;
+    Ok(())
+}
+
+/// Scan the folder, or only the `scope` directories of it, and apply the changes to the database.
+/// Returns whether anything in the library changed.
+async fn scan_folder<R: Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+    pool: &Pool<Sqlite>,
+    name: &str,
+    path: &str,
+    scope: Option<Vec<PathBuf>>,
+) -> Result<bool, String> {
     let skip_folders: HashSet<String> = db::main::get_skip_folders(pool)
         .await
         .map_err(|e| format!("Fail to get skip folders. Raising Error: {:?}", e))?
         .into_iter()
         .collect();
 
-    let value = tauri::async_runtime::spawn_blocking({
+    let fingerprints = db::main::get_fingerprints(pool, name)
+        .await
+        .map_err(|e| format!("Fail to get file fingerprints. Raising Error: {:?}", e))?;
+
+    let image_comics = db::main::get_folder_image_comics(pool, name)
+        .await
+        .map_err(|e| format!("Fail to get folder settings. Raising Error: {:?}", e))?;
+    let probe_media = db::main::get_folder_probe_media(pool, name)
+        .await
+        .map_err(|e| format!("Fail to get folder settings. Raising Error: {:?}", e))?;
+    let probes = if probe_media {
+        Some(
+            db::main::get_probes(pool, name)
+                .await
+                .map_err(|e| format!("Fail to get media probes. Raising Error: {:?}", e))?,
+        )
+    } else {
+        None
+    };
+    let cover_format = db::main::get_cover_format(pool)
+        .await
+        .map_err(|e| format!("Fail to get cover format. Raising Error: {:?}", e))?;
+    let settings = ScanSettings::new(image_comics, cover_format, probes);
+
+    let scan = tauri::async_runtime::spawn_blocking({
         let app_handle = app_handle.clone();
         let name = name.to_string();
         let path = path.to_string();
-        move || parser::main::parse(&app_handle, &name, &path, &skip_folders)
+        move || {
+            parser::main::parse(
+                &app_handle,
+                &name,
+                &path,
+                &skip_folders,
+                &fingerprints,
+                scope.as_deref(),
+                &settings,
+            )
+        }
     })
     .await
-    .map_err(|e| format!("Parser thread panicked: {}", e))?;
+    .map_err(|e| format!("Parser thread panicked: {}", e))??;
 
-    if let Err(e) = db::main::insert_new_media(pool, name, &value).await {
+    if let Err(e) = db::main::update_media(pool, name, &scan).await {
         return Err(format!(
             "Fail to update folder data. Raising Error: {:?}",
             e
         ));
     }
+    Ok(!scan.is_unchanged())
+}
+
+fn watch_folder<R: Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+    watcher: &LibraryWatcher,
+    scans: &Arc<ScanQueue>,
+    pool: &Pool<Sqlite>,
+    name: &str,
+    path: &str,
+) -> Result<(), String> {
+    let app_handle = app_handle.clone();
+    let scans = scans.clone();
+    let pool = pool.clone();
+    let folder_name = name.to_string();
+    let folder_path = path.to_string();
+
+    watcher.watch(name, path, move |dirs| {
+        // changes are merged into the rescan still waiting for the folder, if there is one
+        if !scans.push(&folder_name, dirs) {
+            return;
+        }
+        let app_handle = app_handle.clone();
+        let scans = scans.clone();
+        let pool = pool.clone();
+        let name = folder_name.clone();
+        let path = folder_path.clone();
+        tauri::async_runtime::spawn(async move {
+            // full scans and earlier rescans of the folder run first
+            let (_guard, dirs) = scans.next(&name).await;
+            if let Err(e) = process_watch_scan(&app_handle, &pool, &name, &path, dirs).await {
+                error!("Error on updating watched folder {}: {}", name, e);
+            }
+        });
+    })
+}
+
+async fn process_watch_scan<R: Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+    pool: &Pool<Sqlite>,
+    name: &str,
+    path: &str,
+    dirs: Vec<PathBuf>,
+) -> Result<(), String> {
+    // folders can be reordered while watched, so look up the current position
+    let position = db::main::get_folder_position(pool, name, path)
+        .await
+        .map_err(|e| {
+            format!(
+                "Fail to get watched folder position. Raising Error: {:?}",
+                e
+            )
+        })?;
+    let invalidation_payload: InvalidationPayload = InvalidationPayload {
+        t: 1u8,
+        id: position,
+    };
+
+    if let Err(e) = db::main::update_folder_status(pool, &1, &position).await {
+        return Err(format!(
+            "Fail to change folder status to loading. Raising Error: {:?}",
+            e
+        ));
+    }
+    app_handle
+        .emit("parsing", invalidation_payload.clone())
+        .expect("Fail to send message to refresh status on loading.");
+
+    let result = scan_folder(app_handle, pool, name, path, Some(dirs)).await;
+    let status = if result.is_ok() { 0 } else { 2 };
+    if let Err(e) = db::main::update_folder_status(pool, &status, &position).await {
+        error!("Fail to change folder status. Raising Error: {:?}", e);
+    }
+    app_handle
+        .emit("parsing", invalidation_payload)
+        .expect("Fail to send message to refresh folder on change.");
+    result.map(|_| ())
+}
+
+#[tauri::command]
+async fn update_folder_watch<R: Runtime>(
+    app_handle: tauri::AppHandle<R>,
+    database_state: State<'_, DatabaseConnectionState>,
+    watcher_state: State<'_, FolderWatcherState>,
+    scan_queue_state: State<'_, ScanQueueState>,
+    position: i32,
+    watch: bool,
+) -> Result<(), String> {
+    let pool = &database_state.0;
+    let watcher = &watcher_state.0;
+
+    let folder_data = db::main::get_folder_data(pool, &position)
+        .await
+        .map_err(|e| format!("Fail to get folder data. Raising Error: {:?}", e))?;
+
+    if watch {
+        watch_folder(
+            &app_handle,
+            watcher,
+            &scan_queue_state.0,
+            pool,
+            folder_data.folder_name(),
+            folder_data.path(),
+        )?;
+    } else {
+        watcher.unwatch(folder_data.folder_name());
+    }
+
+    if let Err(e) = db::main::update_folder_watch(pool, &position, watch).await {
+        return Err(format!(
+            "Fail to update folder watch. Raising Error: {:?}",
+            e
+        ));
+    }
     Ok(())
 }
 
@@ -251,6 +431,22 @@ async fn update_skip_folders(
     Ok(())
 }
 
+#[tauri::command]
+async fn update_cover_format(
+    database_state: State<'_, DatabaseConnectionState>,
+    cover_format: &str,
+) -> Result<(), String> {
+    let pool = &database_state.0;
+    let cover_format = cover_format.parse::<CoverFormat>()?;
+    if let Err(e) = db::main::update_cover_format(pool, cover_format).await {
+        return Err(format!(
+            "Fail to update cover format. Raising Error: {:?}",
+            e
+        ));
+    }
+    Ok(())
+}
+
 #[tauri::command]
 async fn get_folder_list(
     database_state: State<'_, DatabaseConnectionState>,
@@ -280,17 +476,139 @@ async fn get_folder_media(
     server_port_state: State<'_, ServerPort>,
     position: i32,
     filter_type: u8,
+    read_filter: Option<u8>,
     tags: Vec<Tag>,
 ) -> Result<Vec<Media>, String> {
     let pool = &database_state.0;
     let server_port = server_port_state.0;
 
-    match db::main::get_folder_media(pool, &position, &server_port, filter_type, &tags).await {
+    match db::main::get_folder_media(
+        pool,
+        &position,
+        &server_port,
+        filter_type,
+        read_filter.unwrap_or_default(),
+        &tags,
+    )
+    .await
+    {
         Ok(media) => Ok(media),
         Err(e) => Err(format!("Fail to get folder media. Raising Error: {:?}", e)),
     }
 }
 
+#[tauri::command]
+async fn update_folder_image_comics(
+    database_state: State<'_, DatabaseConnectionState>,
+    position: i32,
+    image_comics: bool,
+) -> Result<(), String> {
+    let pool = &database_state.0;
+    if let Err(e) = db::main::update_folder_image_comics(pool, &position, image_comics).await {
+        return Err(format!(
+            "Fail to update folder image comics. Raising Error: {:?}",
+            e
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn update_folder_probe_media(
+    database_state: State<'_, DatabaseConnectionState>,
+    position: i32,
+    probe_media: bool,
+) -> Result<(), String> {
+    let pool = &database_state.0;
+    if let Err(e) = db::main::update_folder_probe_media(pool, &position, probe_media).await {
+        return Err(format!(
+            "Fail to update folder media probing. Raising Error: {:?}",
+            e
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_folder_collections(
+    database_state: State<'_, DatabaseConnectionState>,
+    server_port_state: State<'_, ServerPort>,
+    position: i32,
+) -> Result<Vec<Collection>, String> {
+    let pool = &database_state.0;
+    let server_port = server_port_state.0;
+    match db::main::get_folder_collections(pool, &position, &server_port).await {
+        Ok(collections) => Ok(collections),
+        Err(e) => Err(format!(
+            "Fail to get folder collections. Raising Error: {:?}",
+            e
+        )),
+    }
+}
+
+#[tauri::command]
+async fn get_media_details(
+    database_state: State<'_, DatabaseConnectionState>,
+    position: i32,
+    path: String,
+) -> Result<Option<Value>, String> {
+    let pool = &database_state.0;
+    match db::main::get_media_details(pool, &position, &path).await {
+        Ok(details) => Ok(details),
+        Err(e) => Err(format!("Fail to get media details. Raising Error: {:?}", e)),
+    }
+}
+
+#[tauri::command]
+async fn get_reading_state(
+    database_state: State<'_, DatabaseConnectionState>,
+    position: i32,
+    path: String,
+) -> Result<Option<ReadingState>, String> {
+    let pool = &database_state.0;
+    match db::main::get_reading_state(pool, &position, &path).await {
+        Ok(reading_state) => Ok(reading_state),
+        Err(e) => Err(format!("Fail to get reading state. Raising Error: {:?}", e)),
+    }
+}
+
+#[tauri::command]
+async fn update_reading_progress(
+    database_state: State<'_, DatabaseConnectionState>,
+    position: i32,
+    path: String,
+    current_page: u32,
+    total_pages: u32,
+) -> Result<(), String> {
+    let pool = &database_state.0;
+    if let Err(e) =
+        db::main::update_reading_progress(pool, &position, &path, current_page, total_pages).await
+    {
+        return Err(format!(
+            "Fail to update reading progress. Raising Error: {:?}",
+            e
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn update_read_status(
+    database_state: State<'_, DatabaseConnectionState>,
+    position: i32,
+    path: String,
+    read: bool,
+) -> Result<(), String> {
+    let pool = &database_state.0;
+    if let Err(e) = db::main::update_read_status(pool, &position, &path, read).await {
+        return Err(format!(
+            "Fail to update read status. Raising Error: {:?}",
+            e
+        ));
+    }
+    Ok(())
+}
+
 #[tauri::command]
 async fn get_folder_media_tags(
     database_state: State<'_, DatabaseConnectionState>,
@@ -335,6 +653,7 @@ async fn reorder_folder(
 async fn delete_folder<R: Runtime>(
     app_handle: tauri::AppHandle<R>,
     database_state: State<'_, DatabaseConnectionState>,
+    watcher_state: State<'_, FolderWatcherState>,
     name: &str,
     position: i32,
 ) -> Result<(), String> {
@@ -342,6 +661,7 @@ async fn delete_folder<R: Runtime>(
     if let Err(e) = db::main::delete_folder(pool, name, &position).await {
         return Err(format!("Fail to delete folders. Raising Error: {:?}", e));
     }
+    watcher_state.0.unwatch(name);
 
     let app_dir = app_handle.path().app_data_dir().unwrap();
     let covers_folder = app_dir.join("covers").join(name);
@@ -352,6 +672,16 @@ async fn delete_folder<R: Runtime>(
             e
         ));
     }
+    let trickplay_folder = app_dir.join("trickplay").join(name);
+    if trickplay_folder.exists() {
+        if let Err(e) = fs::remove_dir_all(&trickplay_folder) {
+            return Err(format!(
+                "Fail to delete trickplay at directory: {}. Raising Error: {}",
+                trickplay_folder.display(),
+                e
+            ));
+        }
+    }
     Ok(())
 }
 
@@ -433,11 +763,20 @@ fn main() {
 			get_setting,
 			hide_side_panel,
 			update_skip_folders,
+			update_cover_format,
 			get_folder_list,
 			get_folder_data,
 			get_folder_media,
 			get_folder_media_tags,
+			get_folder_collections,
+			get_media_details,
+			get_reading_state,
+			update_reading_progress,
+			update_read_status,
 			update_folder_filter_type,
+			update_folder_watch,
+			update_folder_image_comics,
+			update_folder_probe_media,
 			update_sort_type,
 			update_folder_path,
 			reorder_folder,
@@ -447,15 +786,16 @@ fn main() {
 		.setup(move |app| {
 			let app_handle = app.app_handle().clone();
 
+			// JPEG, PNG and WebP covers are converted in-process, AVIF covers, video frames, probing
+			// and seek previews need ffmpeg and fail one by one without it
 			if !check_ffmpeg_exists() {
-				app_handle
+				error!("Missing ffmpeg, AVIF covers, video frames, media probing and seek previews are unavailable.");
+				let _ = app_handle
 					.notification()
 					.builder()
 					.title("MediaDB")
-					.body("Missing core dependency ffmpeg, please download and install from https://www.ffmpeg.org/download.html.")
-					.show()
-					.unwrap();
-				panic!("Missing ffmpeg, please download and install from https://www.ffmpeg.org/download.html.")
+					.body("Missing ffmpeg, AVIF covers, video frames, media probing and seek previews are unavailable. Download and install it from https://www.ffmpeg.org/download.html.")
+					.show();
 			}
 
 			if let Err(e) = db::main::initialize(&app_handle) {
@@ -473,10 +813,22 @@ fn main() {
 				pool
 			});
 
-			app.manage(DatabaseConnectionState(pool));
+			let watcher = LibraryWatcher::default();
+			let scans = Arc::new(ScanQueue::default());
+			let watched_folders = tauri::async_runtime::block_on(db::main::get_watched_folders(&pool))
+				.expect("Fail to get watched folders");
+			for folder in watched_folders {
+				if let Err(e) = watch_folder(&app_handle, &watcher, &scans, &pool, folder.folder_name(), folder.path()) {
+					error!("{}", e);
+				}
+			}
+
+			app.manage(DatabaseConnectionState(pool.clone()));
+			app.manage(FolderWatcherState(watcher));
+			app.manage(ScanQueueState(scans));
 
 			let app_data_dir = app.handle().path().app_data_dir().unwrap();
-			server::start(&app_data_dir, port);
+			server::start(&app_data_dir, port, pool);
 
 			Ok(())
 		})
@@ -1,4 +1,4 @@
-use crate::helper::main::get_cached_image_path;
+use crate::helper::main::{get_cached_image_path, is_remote_image};
 use crate::model::parser::MediaType;
 use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{json, Value};
@@ -15,6 +15,8 @@ pub struct Setting {
     #[serde(rename = "skipFolders")]
     #[serde(serialize_with = "serialize_string_list")]
     skip_folders: String,
+    #[serde(rename = "coverFormat")]
+    cover_format: String,
 }
 
 fn serialize_bool_number<S>(v: &i32, serializer: S) -> Result<S::Ok, S::Error>
@@ -49,10 +51,13 @@ pub struct Folder {
 }
 
 impl Folder {
-    #[cfg(test)]
     pub fn folder_name(&self) -> &str {
         &self.folder_name
     }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
 }
 
 #[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
@@ -66,6 +71,32 @@ pub struct FolderData {
     path: String,
     position: i32,
     status: u8,
+    #[serde(serialize_with = "serialize_number_flag")]
+    watch: i32,
+    #[serde(rename = "imageComics")]
+    #[serde(serialize_with = "serialize_number_flag")]
+    image_comics: i32,
+    #[serde(rename = "probeMedia")]
+    #[serde(serialize_with = "serialize_number_flag")]
+    probe_media: i32,
+}
+
+impl FolderData {
+    pub fn folder_name(&self) -> &str {
+        &self.folder_name
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+fn serialize_number_flag<S>(v: &i32, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let ret = v != &0i32;
+    ret.serialize(serializer)
 }
 
 #[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
@@ -79,6 +110,14 @@ pub struct Media {
     file: String,
     #[serde(serialize_with = "serialize_json_string")]
     seasons: String,
+    #[serde(serialize_with = "serialize_number_flag")]
+    inferred: i32,
+    #[serde(serialize_with = "serialize_json_list")]
+    versions: String,
+    collection: String, // movie set, empty when the movie is not part of one
+    #[serde(rename = "readingState")]
+    #[sqlx(skip)]
+    reading_state: Option<ReadingState>,
 }
 
 impl Media {
@@ -87,7 +126,11 @@ impl Media {
         &self.title
     }
 
-    pub fn from_row(row: &SqliteRow, server_port: &u16) -> Result<Self, sqlx::Error> {
+    pub fn from_row(
+        row: &SqliteRow,
+        server_port: &u16,
+        reading_states: &HashMap<String, ReadingState>,
+    ) -> Result<Self, sqlx::Error> {
         let media_type = row.try_get::<u8, _>("t")?;
         let path = row.try_get::<String, _>("path")?;
         let folder_name = row.try_get::<String, _>("folder_name")?;
@@ -105,9 +148,128 @@ impl Media {
             posters,
             year: row.try_get("year")?,
             file: row.try_get("file")?,
-            seasons: row.try_get("seasons")?,
+            seasons: construct_season_images(
+                &media_type,
+                server_port,
+                &folder_name,
+                row.try_get("seasons")?,
+                reading_states,
+            ),
+            inferred: row.try_get("inferred")?,
+            versions: row.try_get("versions")?,
+            collection: row.try_get("collection")?,
+            reading_state: ReadingState::from_joined_row(row)?,
         })
     }
+
+    #[cfg(test)]
+    pub fn reading_state(&self) -> Option<&ReadingState> {
+        self.reading_state.as_ref()
+    }
+}
+
+/// Movie set with the movies of the folder that belong to it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Collection {
+    name: String,
+    overview: String,
+    poster: Option<String>,
+    movies: Vec<Value>,
+}
+
+impl Collection {
+    pub fn from_row(
+        row: &SqliteRow,
+        server_port: &u16,
+        movies: Vec<Value>,
+    ) -> Result<Self, sqlx::Error> {
+        let folder_name = row.try_get::<String, _>("folder_name")?;
+        let poster = row.try_get::<String, _>("poster")?;
+        Ok(Collection {
+            name: row.try_get("name")?,
+            overview: row.try_get("overview")?,
+            // set artwork is converted like any other cover, it lies relative to the library root
+            poster: (!poster.is_empty())
+                .then(|| get_cached_image_path(server_port, &folder_name, &poster)),
+            movies,
+        })
+    }
+
+    pub fn movie_from_row(row: &SqliteRow, server_port: &u16) -> Result<Value, sqlx::Error> {
+        let path = row.try_get::<String, _>("path")?;
+        let posters = construct_posters_map(
+            &row.try_get::<u8, _>("t")?,
+            server_port,
+            &row.try_get::<String, _>("folder_name")?,
+            &path,
+            &row.try_get::<String, _>("posters")?,
+        );
+        Ok(json!({
+            "path": path,
+            "title": row.try_get::<String, _>("title")?,
+            "year": row.try_get::<String, _>("year")?,
+            "file": row.try_get::<String, _>("file")?,
+            "posters": posters,
+        }))
+    }
+
+    #[cfg(test)]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[cfg(test)]
+    pub fn poster(&self) -> Option<&str> {
+        self.poster.as_deref()
+    }
+
+    #[cfg(test)]
+    pub fn movies(&self) -> &[Value] {
+        &self.movies
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadingState {
+    #[serde(rename = "currentPage")]
+    current_page: u32,
+    #[serde(rename = "totalPages")]
+    total_pages: u32,
+    #[serde(serialize_with = "serialize_number_flag")]
+    read: i32,
+    // milliseconds since the epoch, none until a page has been opened
+    #[serde(rename = "lastRead")]
+    last_read: Option<i64>,
+}
+
+impl ReadingState {
+    /// Reading state of a media row left joined with reading_state, None when there is no entry.
+    fn from_joined_row(row: &SqliteRow) -> Result<Option<Self>, sqlx::Error> {
+        let Some(current_page) = row.try_get::<Option<u32>, _>("current_page")? else {
+            return Ok(None);
+        };
+        Ok(Some(ReadingState {
+            current_page,
+            total_pages: row.try_get("total_pages")?,
+            read: row.try_get("read")?,
+            last_read: row.try_get("last_read")?,
+        }))
+    }
+
+    #[cfg(test)]
+    pub fn current_page(&self) -> u32 {
+        self.current_page
+    }
+
+    #[cfg(test)]
+    pub fn is_read(&self) -> bool {
+        self.read != 0
+    }
+
+    #[cfg(test)]
+    pub fn last_read(&self) -> Option<i64> {
+        self.last_read
+    }
 }
 
 fn construct_posters_map(
@@ -129,6 +291,8 @@ fn construct_posters_map(
         .into_iter()
         .map(|(k, v)| {
             let value = match media_type_enum {
+                // artwork an NFO file only references by URL is not cached
+                _ if is_remote_image(&v) => v,
                 MediaType::Movie | MediaType::TvShow => get_cached_image_path(
                     server_port,
                     folder_name,
@@ -144,6 +308,50 @@ fn construct_posters_map(
     Value::Object(posters)
 }
 
+/// Point the covers of the issues in a comic series and the stills of the episodes in a show to
+/// the image server. Issues also get their reading state, null until they are opened.
+fn construct_season_images(
+    media_type: &u8,
+    server_port: &u16,
+    folder_name: &str,
+    seasons: String,
+    reading_states: &HashMap<String, ReadingState>,
+) -> String {
+    let media_type = MediaType::from(*media_type);
+    if !matches!(media_type, MediaType::Comic | MediaType::TvShow) || seasons.is_empty() {
+        return seasons;
+    }
+
+    let Ok(mut volumes) = serde_json::from_str::<HashMap<String, Vec<Value>>>(&seasons) else {
+        return seasons;
+    };
+    for item in volumes.values_mut().flatten() {
+        match media_type {
+            MediaType::Comic => {
+                if let Some(Value::String(poster)) = item.get_mut("poster") {
+                    *poster = get_cached_image_path(server_port, folder_name, poster);
+                }
+                let reading_state = item["path"].as_str().and_then(|o| reading_states.get(o));
+                item["readingState"] = json!(reading_state);
+            }
+            _ => {
+                // stills lie next to the episode, relative to its directory
+                let dir = PathBuf::from(item["path"].as_str().unwrap_or_default());
+                if let Some(Value::String(thumb)) = item.get_mut("thumb") {
+                    if !is_remote_image(thumb) {
+                        *thumb = get_cached_image_path(
+                            server_port,
+                            folder_name,
+                            &dir.join(&*thumb).to_string_lossy(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    json!(volumes).to_string()
+}
+
 fn serialize_json_string<S>(v: &str, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -157,6 +365,19 @@ where
     }
 }
 
+fn serialize_json_list<S>(v: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if v.is_empty() {
+        let v: Value = json!([]);
+        v.serialize(serializer)
+    } else {
+        let value: Value = serde_json::from_str(v).map_err(serde::ser::Error::custom)?;
+        value.serialize(serializer)
+    }
+}
+
 #[derive(sqlx::FromRow, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
 pub struct Tag {
     #[serde(rename = "group")]
@@ -1,7 +1,10 @@
+use crate::helper::main::{get_art_type, is_remote_image};
 use log::error;
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Debug, Default)]
 pub struct MediaSource {
@@ -32,6 +35,406 @@ impl MediaSource {
     }
 }
 
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Eq)]
+pub struct FileFingerprint {
+    path: String,
+    size: i64,
+    mtime: i64,
+}
+
+impl FileFingerprint {
+    pub fn new(path: String, size: i64, mtime: i64) -> Self {
+        FileFingerprint { path, size, mtime }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+    pub fn mtime(&self) -> i64 {
+        self.mtime
+    }
+
+    pub fn same_content(&self, other: &FileFingerprint) -> bool {
+        self.size == other.size && self.mtime == other.mtime
+    }
+}
+
+/// Output of ffprobe for a video, kept as long as the fingerprint of the file is unchanged.
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq, Eq)]
+pub struct MediaProbe {
+    path: String,
+    size: i64,
+    mtime: i64,
+    output: String, // ffprobe JSON
+}
+
+impl MediaProbe {
+    pub fn new(fingerprint: &FileFingerprint, output: String) -> Self {
+        MediaProbe {
+            path: fingerprint.path.clone(),
+            size: fingerprint.size,
+            mtime: fingerprint.mtime,
+            output,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+    pub fn mtime(&self) -> i64 {
+        self.mtime
+    }
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn is_current(&self, fingerprint: &FileFingerprint) -> bool {
+        self.size == fingerprint.size && self.mtime == fingerprint.mtime
+    }
+}
+
+/// One cut of a movie with its files in playing order, relative to the library root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovieVersion {
+    label: String,
+    parts: Vec<String>,
+}
+
+impl MovieVersion {
+    pub fn new(label: String, parts: Vec<String>) -> Self {
+        MovieVersion { label, parts }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+    pub fn parts(&self) -> &[String] {
+        &self.parts
+    }
+}
+
+/// Movie set (franchise) a movie belongs to, from the `<set>` tag of its NFO file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovieSet {
+    name: String,
+    overview: String,
+    poster: String, // relative to the library root, empty without set artwork
+}
+
+impl MovieSet {
+    pub fn new(name: String, overview: String) -> Self {
+        MovieSet {
+            name,
+            overview,
+            poster: String::from(""),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn overview(&self) -> &str {
+        &self.overview
+    }
+    pub fn poster(&self) -> &str {
+        &self.poster
+    }
+    pub fn set_poster(&mut self, poster: String) {
+        self.poster = poster;
+    }
+}
+
+/// Descriptive NFO metadata shown on the detail page of a media.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MediaDetails {
+    original_title: String,
+    sort_title: String,
+    plot: String,
+    outline: String,
+    tagline: String,
+    runtime: String, // minutes
+    mpaa: String,
+    premiered: String,
+    directors: Vec<String>,
+    credits: Vec<String>,
+    countries: Vec<String>,
+    unique_ids: Vec<Value>,
+    ratings: Vec<Value>,
+    edition: String, // read from the file name of an inferred movie
+}
+
+impl MediaDetails {
+    pub fn set_original_title(&mut self, original_title: String) {
+        self.original_title = original_title;
+    }
+    pub fn set_sort_title(&mut self, sort_title: String) {
+        self.sort_title = sort_title;
+    }
+    pub fn set_plot(&mut self, plot: String) {
+        self.plot = plot;
+    }
+    pub fn set_outline(&mut self, outline: String) {
+        self.outline = outline;
+    }
+    pub fn set_tagline(&mut self, tagline: String) {
+        self.tagline = tagline;
+    }
+    pub fn set_runtime(&mut self, runtime: String) {
+        self.runtime = runtime;
+    }
+    pub fn set_mpaa(&mut self, mpaa: String) {
+        self.mpaa = mpaa;
+    }
+    pub fn set_premiered(&mut self, premiered: String) {
+        self.premiered = premiered;
+    }
+    pub fn set_edition(&mut self, edition: String) {
+        self.edition = edition;
+    }
+    pub fn add_director(&mut self, director: String) {
+        if !director.trim().is_empty() && !self.directors.contains(&director) {
+            self.directors.push(director)
+        }
+    }
+    pub fn add_credit(&mut self, credit: String) {
+        if !credit.trim().is_empty() && !self.credits.contains(&credit) {
+            self.credits.push(credit)
+        }
+    }
+    pub fn add_country(&mut self, country: String) {
+        if !country.trim().is_empty() && !self.countries.contains(&country) {
+            self.countries.push(country)
+        }
+    }
+    /// Add an id of an online database such as imdb, tmdb or tvdb.
+    pub fn add_unique_id(&mut self, id_type: &str, value: String, default: bool) {
+        if !value.trim().is_empty() {
+            self.unique_ids
+                .push(json!({"type": id_type, "value": value, "default": default}));
+        }
+    }
+    pub fn add_rating(
+        &mut self,
+        name: &str,
+        value: f64,
+        votes: Option<u64>,
+        max: f64,
+        default: bool,
+    ) {
+        self.ratings.push(json!({
+            "name": name,
+            "value": value,
+            "votes": votes,
+            "max": max,
+            "default": default,
+        }));
+    }
+
+    /// Value of the default rating, or of the first one when none is marked as default.
+    fn default_rating(&self) -> Option<f64> {
+        self.ratings
+            .iter()
+            .find(|o| o["default"] == true)
+            .or_else(|| self.ratings.first())
+            .and_then(|o| o["value"].as_f64())
+    }
+
+    fn is_empty(&self) -> bool {
+        self == &MediaDetails::default()
+    }
+
+    fn to_json(&self) -> String {
+        if self.is_empty() {
+            return String::from("");
+        }
+        format!(
+            "{}",
+            json!({
+                "originalTitle": self.original_title,
+                "sortTitle": self.sort_title,
+                "plot": self.plot,
+                "outline": self.outline,
+                "tagline": self.tagline,
+                "runtime": self.runtime,
+                "mpaa": self.mpaa,
+                "premiered": self.premiered,
+                "directors": self.directors,
+                "credits": self.credits,
+                "countries": self.countries,
+                "uniqueIds": self.unique_ids,
+                "ratings": self.ratings,
+                "edition": self.edition,
+            })
+        )
+    }
+}
+
+/// Technical metadata of a video, from the `<fileinfo><streamdetails>` tag of an NFO file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StreamDetails {
+    container: String,
+    bit_rate: u64, // bits per second
+    video_codec: String,
+    width: u32,
+    height: u32,
+    aspect: String,
+    hdr_type: String,
+    duration: u32, // seconds
+    audio: Vec<AudioStream>,
+    subtitles: Vec<String>, // languages
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AudioStream {
+    codec: String,
+    language: String,
+    channels: u32,
+}
+
+impl StreamDetails {
+    pub fn set_container(&mut self, container: String) {
+        self.container = container;
+    }
+    pub fn set_bit_rate(&mut self, bit_rate: u64) {
+        self.bit_rate = bit_rate;
+    }
+    pub fn set_video_codec(&mut self, codec: String) {
+        self.video_codec = codec;
+    }
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+    pub fn set_aspect(&mut self, aspect: String) {
+        self.aspect = aspect;
+    }
+    pub fn set_hdr_type(&mut self, hdr_type: String) {
+        self.hdr_type = hdr_type;
+    }
+    pub fn set_duration(&mut self, duration: u32) {
+        self.duration = duration;
+    }
+    pub fn add_audio(&mut self, codec: String, language: String, channels: u32) {
+        self.audio.push(AudioStream {
+            codec,
+            language,
+            channels,
+        });
+    }
+    pub fn add_subtitle(&mut self, language: String) {
+        if !language.is_empty() && !self.subtitles.contains(&language) {
+            self.subtitles.push(language);
+        }
+    }
+
+    pub fn duration(&self) -> u32 {
+        self.duration
+    }
+
+    /// Resolution class of the video the way players label it, none without a known size.
+    pub fn resolution(&self) -> Option<&'static str> {
+        match (self.width, self.height) {
+            (0, 0) => None,
+            // the height of a wide movie is cropped, so the width decides as well
+            (w, h) if w >= 3200 || h >= 2000 => Some("4K"),
+            (w, h) if w >= 1800 || h >= 1000 => Some("1080p"),
+            (w, h) if w >= 1200 || h >= 700 => Some("720p"),
+            _ => Some("SD"),
+        }
+    }
+
+    /// Video codec under its common name, NFO files use the names of the decoders.
+    pub fn codec(&self) -> Option<String> {
+        let codec = self.video_codec.to_lowercase();
+        let name = match codec.as_str() {
+            "" => return None,
+            "h264" | "avc" | "avc1" | "x264" => "H.264",
+            "hevc" | "h265" | "x265" | "hvc1" => "HEVC",
+            "av1" => "AV1",
+            "vp9" => "VP9",
+            "vc1" | "wvc1" => "VC-1",
+            "mpeg2video" | "mpeg2" => "MPEG-2",
+            "mpeg4" | "xvid" | "divx" | "dx50" => "MPEG-4",
+            _ => return Some(codec.to_uppercase()),
+        };
+        Some(name.to_string())
+    }
+
+    pub fn audio_languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = vec![];
+        for language in self.audio.iter().map(|o| o.language.to_lowercase()) {
+            if !language.is_empty() && !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+        languages
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &StreamDetails::default()
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "container": self.container,
+            "bitRate": self.bit_rate,
+            "videoCodec": self.video_codec,
+            "codec": self.codec(),
+            "resolution": self.resolution(),
+            "width": self.width,
+            "height": self.height,
+            "aspect": self.aspect,
+            "hdrType": self.hdr_type,
+            "duration": self.duration,
+            "audio": self.audio.iter().map(|o| json!({
+                "codec": o.codec,
+                "language": o.language,
+                "channels": o.channels,
+            })).collect::<Vec<Value>>(),
+            "subtitles": self.subtitles,
+        })
+    }
+
+    fn to_json(&self) -> String {
+        if self.is_empty() {
+            return String::from("");
+        }
+        format!("{}", self.json())
+    }
+}
+
+/// Values of the filterable stream tag groups of a media, episodes add to the ones of their show.
+#[derive(Debug, Default)]
+struct StreamTags {
+    resolutions: Vec<String>,
+    codecs: Vec<String>,
+    languages: Vec<String>,
+}
+
+impl StreamTags {
+    fn add(&mut self, streams: &StreamDetails) {
+        fn merge(target: &mut Vec<String>, values: impl IntoIterator<Item = String>) {
+            for v in values {
+                if !target.contains(&v) {
+                    target.push(v);
+                }
+            }
+        }
+        merge(
+            &mut self.resolutions,
+            streams.resolution().map(String::from),
+        );
+        merge(&mut self.codecs, streams.codec());
+        merge(&mut self.languages, streams.audio_languages());
+    }
+}
+
 #[repr(u8)]
 #[derive(Debug, Copy, Clone)]
 pub enum MediaType {
@@ -64,6 +467,7 @@ impl From<u8> for MediaType {
 pub struct Media {
     media_type: MediaType,
     relative_path: OsString,
+    source_dir: OsString, // directory the media was discovered in
     file: String,
     title: String,
     posters: Vec<String>,
@@ -73,8 +477,15 @@ pub struct Media {
     actors: Vec<String>,
     studios: Vec<String>,
 
-    season: String,  // season number
-    episode: String, // episode number
+    season: String,              // season number
+    episode: String,             // episode number
+    series: String,              // series a comic issue belongs to
+    inferred: bool,              // identified from file and folder names instead of an NFO file
+    versions: Vec<MovieVersion>, // alternate cuts of a movie, the first one is the default
+    details: MediaDetails,
+    collection: Option<MovieSet>,
+    artwork: Vec<(String, String)>, // posters map key and image referenced by the NFO file
+    streams: StreamDetails,
 }
 
 impl Default for Media {
@@ -82,6 +493,7 @@ impl Default for Media {
         Media {
             media_type: MediaType::Unknown,
             relative_path: Default::default(),
+            source_dir: Default::default(),
             file: "".to_string(),
             title: "".to_string(),
             posters: vec![],
@@ -92,6 +504,13 @@ impl Default for Media {
             studios: vec![],
             season: "".to_string(),
             episode: "".to_string(),
+            series: "".to_string(),
+            inferred: false,
+            versions: vec![],
+            details: Default::default(),
+            collection: None,
+            artwork: vec![],
+            streams: Default::default(),
         }
     }
 }
@@ -121,12 +540,36 @@ impl Media {
         }
     }
 
+    /// Add an image an NFO file references, the first one of a kind is kept. `season` is empty for
+    /// the artwork of the media itself.
+    pub fn add_artwork(&mut self, season: &str, art_type: &str, image: String) {
+        let key = get_art_key(season, art_type);
+        if !image.trim().is_empty() && !self.artwork.iter().any(|(k, _)| k == &key) {
+            self.artwork.push((key, image));
+        }
+    }
+
     pub fn add_studio(&mut self, studio: String) {
         if !studio.trim().is_empty() {
             self.studios.push(studio)
         }
     }
 
+    /// Add the tags, genres, actors and studios of `other` that are not present yet.
+    pub fn merge_tags(&mut self, other: &Media) {
+        fn merge(target: &mut Vec<String>, values: &[String]) {
+            for v in values {
+                if !target.contains(v) {
+                    target.push(v.clone());
+                }
+            }
+        }
+        merge(&mut self.tags, &other.tags);
+        merge(&mut self.genres, &other.genres);
+        merge(&mut self.actors, &other.actors);
+        merge(&mut self.studios, &other.studios);
+    }
+
     // Getter
     pub fn media_type(&self) -> &MediaType {
         &self.media_type
@@ -134,18 +577,77 @@ impl Media {
     pub fn relative_path(&self) -> &OsString {
         &self.relative_path
     }
+    pub fn source_dir(&self) -> &OsString {
+        &self.source_dir
+    }
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    pub fn year(&self) -> &str {
+        &self.year
+    }
+    pub fn series(&self) -> &str {
+        &self.series
+    }
     pub fn file(&self) -> &str {
         &self.file
     }
     pub fn posters(&self) -> &[String] {
         &self.posters
     }
+    /// Video of a movie or an episode relative to the library root, a loose movie is keyed by it.
+    pub fn video_path(&self) -> PathBuf {
+        if self.file.is_empty() {
+            PathBuf::from(&self.relative_path)
+        } else {
+            Path::new(&self.relative_path).join(&self.file)
+        }
+    }
+    pub fn streams(&self) -> &StreamDetails {
+        &self.streams
+    }
+    /// Images next to the media or referenced by its NFO file, relative to the media directory.
+    /// Remote artwork is left out, it is never converted into a cover.
+    pub fn local_artwork(&self) -> impl Iterator<Item = &String> {
+        self.posters
+            .iter()
+            .chain(self.artwork.iter().map(|(_, image)| image))
+            .filter(|o| !is_remote_image(o))
+    }
+    /// Whether there is a poster to show for the media, or a still for an episode.
+    pub fn has_artwork(&self) -> bool {
+        match self.media_type {
+            MediaType::Episode => self.episode_thumb().is_some(),
+            _ => {
+                self.posters.iter().any(|o| get_file_art_key(o) == "main")
+                    || self.artwork.iter().any(|(key, _)| key == "main")
+            }
+        }
+    }
     pub fn season(&self) -> &str {
         &self.season
     }
     pub fn episode(&self) -> &str {
         &self.episode
     }
+    pub fn inferred(&self) -> bool {
+        self.inferred
+    }
+    pub fn versions(&self) -> &[MovieVersion] {
+        &self.versions
+    }
+    pub fn details_mut(&mut self) -> &mut MediaDetails {
+        &mut self.details
+    }
+    pub fn streams_mut(&mut self) -> &mut StreamDetails {
+        &mut self.streams
+    }
+    pub fn collection(&self) -> Option<&MovieSet> {
+        self.collection.as_ref()
+    }
+    pub fn collection_mut(&mut self) -> Option<&mut MovieSet> {
+        self.collection.as_mut()
+    }
 
     // Setter
     pub fn set_media_type(&mut self, media_type: MediaType) {
@@ -154,6 +656,9 @@ impl Media {
     pub fn set_relative_path(&mut self, relative_path: OsString) {
         self.relative_path = relative_path;
     }
+    pub fn set_source_dir(&mut self, source_dir: OsString) {
+        self.source_dir = source_dir;
+    }
     pub fn set_file(&mut self, file: String) {
         self.file = file;
     }
@@ -180,6 +685,18 @@ impl Media {
     pub fn set_episode(&mut self, episode: String) {
         self.episode = format!("{:0>2}", episode);
     }
+    pub fn set_series(&mut self, series: String) {
+        self.series = series;
+    }
+    pub fn set_inferred(&mut self, inferred: bool) {
+        self.inferred = inferred;
+    }
+    pub fn set_versions(&mut self, versions: Vec<MovieVersion>) {
+        self.versions = versions;
+    }
+    pub fn set_collection(&mut self, collection: MovieSet) {
+        self.collection = Some(collection);
+    }
 
     // json
     pub fn movie(&self) -> Option<MediaItem> {
@@ -187,6 +704,7 @@ impl Media {
             return Some(MediaItem {
                 media_type: MediaType::Movie.as_u8(),
                 path: self.relative_path().to_string_lossy().into_owned(),
+                dir: self.source_dir.to_string_lossy().into_owned(),
                 title: self.title.clone(),
                 posters: format!("{}", self.construct_poster_map()),
                 tags: self.tags.clone(),
@@ -196,6 +714,14 @@ impl Media {
                 year: self.year.clone(),
                 file: self.file.clone(),
                 seasons: String::from(""),
+                inferred: self.inferred,
+                versions: self.versions_json(),
+                details: self.details.to_json(),
+                directors: self.details.directors.clone(),
+                countries: self.details.countries.clone(),
+                collection: self.collection.clone(),
+                streams: self.streams.to_json(),
+                stream_tags: self.stream_tags(),
             });
         }
         error!("Expected a movie, but got {:?}", self.media_type);
@@ -226,9 +752,14 @@ impl Media {
                     )
                 })
                 .collect::<HashMap<&String, Vec<Value>>>();
+            let mut stream_tags = self.stream_tags();
+            for episode in season_map.unwrap().values().flatten() {
+                stream_tags.add(&episode.streams);
+            }
             return Some(MediaItem {
                 media_type: MediaType::TvShow.as_u8(),
                 path: self.relative_path().to_string_lossy().into_owned(),
+                dir: self.source_dir.to_string_lossy().into_owned(),
                 title: self.title.clone(),
                 posters: format!("{}", self.construct_poster_map()),
                 tags: self.tags.clone(),
@@ -238,12 +769,39 @@ impl Media {
                 year: String::from(""),
                 file: String::from(""),
                 seasons: format!("{}", json!(seasons)),
+                inferred: false,
+                versions: String::from(""),
+                details: self.details.to_json(),
+                directors: self.details.directors.clone(),
+                countries: self.details.countries.clone(),
+                collection: None,
+                streams: String::from(""),
+                stream_tags,
             });
         }
         error!("Expected a tv show, but got {:?}", self.media_type);
         None
     }
 
+    fn stream_tags(&self) -> StreamTags {
+        let mut stream_tags = StreamTags::default();
+        stream_tags.add(&self.streams);
+        stream_tags
+    }
+
+    fn versions_json(&self) -> String {
+        // a movie made of a single file has nothing to choose from
+        if self.versions.iter().map(|o| o.parts.len()).sum::<usize>() < 2 {
+            return String::from("");
+        }
+        let versions = self
+            .versions
+            .iter()
+            .map(|o| json!({"label": o.label, "parts": o.parts}))
+            .collect::<Vec<Value>>();
+        format!("{}", json!(versions))
+    }
+
     fn episode_json(&self) -> Option<Value> {
         if let MediaType::Episode = self.media_type {
             return Some(json!({
@@ -252,26 +810,42 @@ impl Media {
                 "season": self.season,
                 "episode": self.episode,
                 "path": self.relative_path.to_string_lossy(),
+                "plot": self.details.plot,
+                "runtime": self.details.runtime,
+                "aired": self.details.premiered,
+                "rating": self.details.default_rating(),
+                "thumb": self.episode_thumb(),
+                "streams": (!self.streams.is_empty()).then(|| self.streams.json()),
             }));
         }
         error!("Expected an episode, but got {:?}", self.media_type);
         None
     }
 
+    /// Still of an episode, an image next to its video goes before the one its NFO file references.
+    fn episode_thumb(&self) -> Option<&String> {
+        self.posters.first().or_else(|| {
+            self.artwork
+                .iter()
+                .find(|(key, _)| key == "thumb")
+                .map(|(_, image)| image)
+        })
+    }
+
     fn construct_poster_map(&self) -> Value {
         let mut poster_map = serde_json::Map::new();
         for p in self.posters() {
-            if p.starts_with("season-specials") {
-                poster_map.insert(String::from("00"), Value::String(p.clone()));
-            } else if p.starts_with("season") {
-                if let Some(first) = p.split('-').next() {
-                    if let Some(num) = first.strip_prefix("season") {
-                        poster_map.insert(num.to_string(), Value::String(p.clone()));
-                    }
-                }
-            } else {
-                poster_map.insert(String::from("main"), Value::String(p.clone()));
-            }
+            let key = match self.media_type {
+                MediaType::Comic => String::from("main"),
+                _ => get_file_art_key(p),
+            };
+            poster_map.insert(key, Value::String(p.clone()));
+        }
+        // artwork listed in the NFO file only fills in what has no image next to the media
+        for (key, value) in &self.artwork {
+            poster_map
+                .entry(key.clone())
+                .or_insert_with(|| Value::String(value.clone()));
         }
         Value::Object(poster_map)
     }
@@ -281,26 +855,123 @@ impl Media {
             return Some(MediaItem {
                 media_type: MediaType::Comic.as_u8(),
                 path: self.relative_path().to_string_lossy().into_owned(),
+                dir: self.source_dir.to_string_lossy().into_owned(),
                 title: self.title.clone(),
                 posters: format!("{}", self.construct_poster_map()),
-                tags: vec![],
-                genres: vec![],
-                actors: vec![],
-                studios: vec![],
-                year: String::from(""),
+                tags: self.tags.clone(),
+                genres: self.genres.clone(),
+                actors: self.actors.clone(),
+                studios: self.studios.clone(),
+                year: self.year.clone(),
                 file: self.file.clone(),
                 seasons: String::from(""),
+                inferred: false,
+                versions: String::from(""),
+                details: String::from(""),
+                directors: vec![],
+                countries: vec![],
+                collection: None,
+                streams: String::from(""),
+                stream_tags: Default::default(),
             });
         }
         error!("Expected a comic, but got {:?}", self.media_type);
         None
     }
+
+    /// A comic series with its issues grouped by volume, each volume already in reading order.
+    pub fn comic_series(&self, volumes: &BTreeMap<String, Vec<&Media>>) -> Option<MediaItem> {
+        if let MediaType::Comic = self.media_type {
+            let volumes = volumes
+                .iter()
+                .map(|(volume, issues)| {
+                    (
+                        volume,
+                        issues
+                            .iter()
+                            .filter_map(|o| o.issue_json())
+                            .collect::<Vec<Value>>(),
+                    )
+                })
+                .collect::<BTreeMap<&String, Vec<Value>>>();
+            return Some(MediaItem {
+                media_type: MediaType::Comic.as_u8(),
+                path: self.relative_path().to_string_lossy().into_owned(),
+                dir: self.source_dir.to_string_lossy().into_owned(),
+                title: self.title.clone(),
+                posters: format!("{}", self.construct_poster_map()),
+                tags: self.tags.clone(),
+                genres: self.genres.clone(),
+                actors: self.actors.clone(),
+                studios: self.studios.clone(),
+                year: self.year.clone(),
+                file: String::from(""),
+                seasons: format!("{}", json!(volumes)),
+                inferred: false,
+                versions: String::from(""),
+                details: String::from(""),
+                directors: vec![],
+                countries: vec![],
+                collection: None,
+                streams: String::from(""),
+                stream_tags: Default::default(),
+            });
+        }
+        error!("Expected a comic series, but got {:?}", self.media_type);
+        None
+    }
+
+    fn issue_json(&self) -> Option<Value> {
+        if let MediaType::Comic = self.media_type {
+            // the reading state changes between scans, it is joined by path when the series is read
+            return Some(json!({
+                "title": self.title,
+                "file": self.file,
+                "volume": self.season,
+                "issue": self.episode,
+                "path": self.relative_path.to_string_lossy(),
+                "poster": self.posters.first(),
+            }));
+        }
+        error!("Expected a comic issue, but got {:?}", self.media_type);
+        None
+    }
+}
+
+/// Key of an artwork in the posters map: `main` for the poster of the media and the season number
+/// for a season poster, other artwork is keyed by its type and prefixed by the season if any.
+fn get_art_key(season: &str, art_type: &str) -> String {
+    match (season, art_type) {
+        ("", "poster") => String::from("main"),
+        ("", art_type) => art_type.to_string(),
+        (season, "poster") => season.to_string(),
+        (season, art_type) => format!("{}-{}", season, art_type),
+    }
+}
+
+/// Key of an image named the Kodi way, e.g. `fanart` for `Heat-fanart.jpg` or `01` for
+/// `season01-poster.jpg`. Images without a known artwork type are taken as posters.
+fn get_file_art_key(file_name: &str) -> String {
+    let art_type = get_art_type(file_name).unwrap_or("poster");
+    let name = file_name.to_lowercase();
+    let season = if name.starts_with("season-specials") {
+        "00"
+    } else if name.starts_with("season-all") {
+        "all"
+    } else {
+        name.strip_prefix("season")
+            .and_then(|o| o.split('-').next())
+            .filter(|o| !o.is_empty() && o.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or_default()
+    };
+    get_art_key(season, art_type)
 }
 
 #[derive(Debug)]
 pub struct MediaItem {
     media_type: u8,
     path: String,
+    dir: String,
     title: String,
     posters: String,
     tags: Vec<String>,
@@ -312,6 +983,14 @@ pub struct MediaItem {
     year: String,
     file: String,
     seasons: String,
+    inferred: bool,
+    versions: String,
+    details: String,
+    directors: Vec<String>,
+    countries: Vec<String>,
+    collection: Option<MovieSet>,
+    streams: String,
+    stream_tags: StreamTags,
 }
 
 impl MediaItem {
@@ -323,6 +1002,10 @@ impl MediaItem {
         &self.path
     }
 
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+
     pub fn title(&self) -> &str {
         &self.title
     }
@@ -358,4 +1041,178 @@ impl MediaItem {
     pub fn seasons(&self) -> &str {
         &self.seasons
     }
+
+    pub fn inferred(&self) -> bool {
+        self.inferred
+    }
+
+    pub fn versions(&self) -> &str {
+        &self.versions
+    }
+
+    pub fn details(&self) -> &str {
+        &self.details
+    }
+
+    pub fn directors(&self) -> &[String] {
+        &self.directors
+    }
+
+    pub fn countries(&self) -> &[String] {
+        &self.countries
+    }
+
+    pub fn collection(&self) -> Option<&MovieSet> {
+        self.collection.as_ref()
+    }
+
+    pub fn streams(&self) -> &str {
+        &self.streams
+    }
+
+    pub fn resolutions(&self) -> &[String] {
+        &self.stream_tags.resolutions
+    }
+
+    pub fn codecs(&self) -> &[String] {
+        &self.stream_tags.codecs
+    }
+
+    pub fn languages(&self) -> &[String] {
+        &self.stream_tags.languages
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    items: Vec<MediaItem>,
+    fingerprints: Vec<FileFingerprint>, // new or modified files
+    removed_files: Vec<String>,
+    dirty_dirs: Vec<String>, // directories whose media rows are replaced by `items`
+    full: bool,
+    probes: Vec<MediaProbe>, // videos probed by this scan
+}
+
+impl ScanResult {
+    pub fn new(
+        items: Vec<MediaItem>,
+        fingerprints: Vec<FileFingerprint>,
+        removed_files: Vec<String>,
+        dirty_dirs: Vec<String>,
+        full: bool,
+    ) -> Self {
+        ScanResult {
+            items,
+            fingerprints,
+            removed_files,
+            dirty_dirs,
+            full,
+            probes: vec![],
+        }
+    }
+
+    pub fn set_probes(&mut self, probes: Vec<MediaProbe>) {
+        self.probes = probes;
+    }
+
+    pub fn items(&self) -> &[MediaItem] {
+        &self.items
+    }
+    pub fn fingerprints(&self) -> &[FileFingerprint] {
+        &self.fingerprints
+    }
+    pub fn removed_files(&self) -> &[String] {
+        &self.removed_files
+    }
+    pub fn dirty_dirs(&self) -> &[String] {
+        &self.dirty_dirs
+    }
+    pub fn full(&self) -> bool {
+        self.full
+    }
+    pub fn probes(&self) -> &[MediaProbe] {
+        &self.probes
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        !self.full && self.dirty_dirs.is_empty() && self.removed_files.is_empty()
+    }
+}
+
+/// Image format covers are saved in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CoverFormat {
+    #[default]
+    Jpeg,
+    Png,
+    WebP, // lossless when encoded in-process
+    Avif, // only ffmpeg encodes it
+}
+
+impl CoverFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoverFormat::Jpeg => "jpeg",
+            CoverFormat::Png => "png",
+            CoverFormat::WebP => "webp",
+            CoverFormat::Avif => "avif",
+        }
+    }
+
+    pub fn needs_ffmpeg(&self) -> bool {
+        matches!(self, CoverFormat::Avif)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CoverFormat::Jpeg => "jpg",
+            _ => self.as_str(),
+        }
+    }
+}
+
+impl FromStr for CoverFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "jpeg" | "jpg" => Ok(CoverFormat::Jpeg),
+            "png" => Ok(CoverFormat::Png),
+            "webp" => Ok(CoverFormat::WebP),
+            "avif" => Ok(CoverFormat::Avif),
+            _ => Err(format!("Unsupported cover format {}", value)),
+        }
+    }
+}
+
+/// Settings a scan depends on.
+#[derive(Debug, Default)]
+pub struct ScanSettings {
+    image_comics: bool,
+    cover_format: CoverFormat,
+    probes: Option<HashMap<String, MediaProbe>>, // cached ffprobe output, none when probing is off
+}
+
+impl ScanSettings {
+    pub fn new(
+        image_comics: bool,
+        cover_format: CoverFormat,
+        probes: Option<HashMap<String, MediaProbe>>,
+    ) -> Self {
+        ScanSettings {
+            image_comics,
+            cover_format,
+            probes,
+        }
+    }
+
+    pub fn image_comics(&self) -> bool {
+        self.image_comics
+    }
+    pub fn cover_format(&self) -> CoverFormat {
+        self.cover_format
+    }
+    pub fn probes(&self) -> Option<&HashMap<String, MediaProbe>> {
+        self.probes.as_ref()
+    }
 }
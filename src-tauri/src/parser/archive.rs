@@ -0,0 +1,440 @@
+use std::{
+    fs,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use sevenz_rust2::{ArchiveReader, Password};
+use zip::ZipArchive;
+
+const ZIP_MAGIC: &[&[u8]] = &[b"PK\x03\x04", b"PK\x05\x06", b"PK\x07\x08"];
+// RAR 1.5 - 4.x and RAR 5.0 signatures share the first six bytes
+const RAR_MAGIC: &[u8] = b"Rar!\x1a\x07";
+const SEVEN_ZIP_MAGIC: &[u8] = b"7z\xbc\xaf\x27\x1c";
+// POSIX and GNU tar headers carry "ustar" at offset 257
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ArchiveFormat {
+    Zip,
+    Rar,
+    Tar,
+    SevenZip,
+}
+
+impl ArchiveFormat {
+    /// Detect the archive format from the file header. Comic extensions are unreliable, plenty of
+    /// `.cbr` files are zip archives and the other way around.
+    pub fn detect(path: &Path) -> Result<ArchiveFormat, String> {
+        let mut header = Vec::with_capacity(TAR_MAGIC_OFFSET + TAR_MAGIC.len());
+        File::open(path)
+            .and_then(|f| {
+                f.take((TAR_MAGIC_OFFSET + TAR_MAGIC.len()) as u64)
+                    .read_to_end(&mut header)
+            })
+            .map_err(|e| format!("Fail to read header of {}. Error: {}", path.display(), e))?;
+
+        Self::from_magic(&header)
+            .ok_or_else(|| format!("Unsupported archive format for {}", path.display()))
+    }
+
+    fn from_magic(header: &[u8]) -> Option<ArchiveFormat> {
+        if ZIP_MAGIC.iter().any(|o| header.starts_with(o)) {
+            return Some(ArchiveFormat::Zip);
+        }
+        if header.starts_with(RAR_MAGIC) {
+            return Some(ArchiveFormat::Rar);
+        }
+        if header.starts_with(SEVEN_ZIP_MAGIC) {
+            return Some(ArchiveFormat::SevenZip);
+        }
+        if header
+            .get(TAR_MAGIC_OFFSET..)
+            .is_some_and(|o| o.starts_with(TAR_MAGIC))
+        {
+            return Some(ArchiveFormat::Tar);
+        }
+        None
+    }
+}
+
+/// Read access to the files of a comic archive, independent of the container format.
+pub(crate) trait ComicArchive {
+    /// Names of the file entries in archive order, directories excluded.
+    fn file_names(&mut self) -> Result<Vec<String>, String>;
+
+    /// Read the full content of the file entry `name`.
+    fn read_file(&mut self, name: &str) -> Result<Vec<u8>, String>;
+}
+
+pub(crate) fn open_archive(path: &Path) -> Result<Box<dyn ComicArchive>, String> {
+    // image folder comics are read like an archive of their files
+    if path.is_dir() {
+        return Ok(Box::new(DirectoryComicArchive(path.to_path_buf())));
+    }
+
+    let archive: Box<dyn ComicArchive> = match ArchiveFormat::detect(path)? {
+        ArchiveFormat::Zip => {
+            let file = File::open(path)
+                .map_err(|e| format!("Fail to open comic file {:?}. Error: {}", path, e))?;
+            let archive = ZipArchive::new(file)
+                .map_err(|e| format!("Fail to read zip file {:?}. Error: {}", path, e))?;
+            Box::new(ZipComicArchive(archive))
+        }
+        ArchiveFormat::Rar => Box::new(RarComicArchive(path.to_path_buf())),
+        ArchiveFormat::Tar => Box::new(TarComicArchive(path.to_path_buf())),
+        ArchiveFormat::SevenZip => {
+            let reader = ArchiveReader::open(path, Password::empty())
+                .map_err(|e| format!("Fail to read 7z file {:?}. Error: {}", path, e))?;
+            Box::new(SevenZipComicArchive(reader))
+        }
+    };
+    Ok(archive)
+}
+
+struct ZipComicArchive(ZipArchive<File>);
+
+impl ComicArchive for ZipComicArchive {
+    fn file_names(&mut self) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        for i in 0..self.0.len() {
+            let zip_file = self
+                .0
+                .by_index_raw(i)
+                .map_err(|e| format!("Fail to get file at index {}. Error: {}", i, e))?;
+            if zip_file.is_file() {
+                names.push(zip_file.name().to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    fn read_file(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let mut zip_file = self
+            .0
+            .by_name(name)
+            .map_err(|e| format!("Fail to get file {}. Error: {}", name, e))?;
+        let mut content = Vec::new();
+        zip_file
+            .read_to_end(&mut content)
+            .map_err(|e| format!("Fail to read zip file content. Error: {}", e))?;
+        Ok(content)
+    }
+}
+
+// unrar only offers sequential access, so every call walks the archive from the start
+struct RarComicArchive(PathBuf);
+
+impl ComicArchive for RarComicArchive {
+    fn file_names(&mut self) -> Result<Vec<String>, String> {
+        let archive = unrar::Archive::new(&self.0)
+            .open_for_listing()
+            .map_err(|e| format!("Fail to read rar file {:?}. Error: {}", self.0, e))?;
+
+        let mut names = Vec::new();
+        for entry in archive {
+            let entry = entry.map_err(|e| format!("Fail to read rar entry. Error: {}", e))?;
+            if entry.is_file() {
+                names.push(entry.filename.to_string_lossy().into_owned());
+            }
+        }
+        Ok(names)
+    }
+
+    fn read_file(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let mut archive = unrar::Archive::new(&self.0)
+            .open_for_processing()
+            .map_err(|e| format!("Fail to read rar file {:?}. Error: {}", self.0, e))?;
+
+        while let Some(header) = archive
+            .read_header()
+            .map_err(|e| format!("Fail to read rar entry. Error: {}", e))?
+        {
+            if header.entry().filename.to_string_lossy() != name {
+                archive = header
+                    .skip()
+                    .map_err(|e| format!("Fail to skip rar entry. Error: {}", e))?;
+                continue;
+            }
+
+            let (content, _) = header
+                .read()
+                .map_err(|e| format!("Fail to read rar file content. Error: {}", e))?;
+            return Ok(content);
+        }
+        Err(format!("Fail to find {} in rar file {:?}", name, self.0))
+    }
+}
+
+// tar has no index, so every call walks the archive from the start
+struct TarComicArchive(PathBuf);
+
+impl TarComicArchive {
+    fn open(&self) -> Result<tar::Archive<File>, String> {
+        File::open(&self.0)
+            .map(tar::Archive::new)
+            .map_err(|e| format!("Fail to open comic file {:?}. Error: {}", self.0, e))
+    }
+}
+
+impl ComicArchive for TarComicArchive {
+    fn file_names(&mut self) -> Result<Vec<String>, String> {
+        let mut archive = self.open()?;
+        let entries = archive
+            .entries()
+            .map_err(|e| format!("Fail to read tar file {:?}. Error: {}", self.0, e))?;
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Fail to read tar entry. Error: {}", e))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            if let Ok(path) = entry.path() {
+                names.push(path.to_string_lossy().into_owned());
+            }
+        }
+        Ok(names)
+    }
+
+    fn read_file(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let mut archive = self.open()?;
+        let entries = archive
+            .entries()
+            .map_err(|e| format!("Fail to read tar file {:?}. Error: {}", self.0, e))?;
+
+        for entry in entries {
+            let mut entry = entry.map_err(|e| format!("Fail to read tar entry. Error: {}", e))?;
+            if !entry.path().is_ok_and(|o| o.to_string_lossy() == name) {
+                continue;
+            }
+
+            let mut content = Vec::new();
+            entry
+                .read_to_end(&mut content)
+                .map_err(|e| format!("Fail to read tar file content. Error: {}", e))?;
+            return Ok(content);
+        }
+        Err(format!("Fail to find {} in tar file {:?}", name, self.0))
+    }
+}
+
+struct SevenZipComicArchive(ArchiveReader<File>);
+
+impl ComicArchive for SevenZipComicArchive {
+    fn file_names(&mut self) -> Result<Vec<String>, String> {
+        Ok(self
+            .0
+            .archive()
+            .files
+            .iter()
+            .filter(|o| !o.is_directory && o.has_stream)
+            .map(|o| o.name.clone())
+            .collect())
+    }
+
+    fn read_file(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        self.0
+            .read_file(name)
+            .map_err(|e| format!("Fail to read 7z file content {}. Error: {}", name, e))
+    }
+}
+
+// a leaf directory of images, entries are the names of the files directly inside it
+struct DirectoryComicArchive(PathBuf);
+
+impl ComicArchive for DirectoryComicArchive {
+    fn file_names(&mut self) -> Result<Vec<String>, String> {
+        let entries = fs::read_dir(&self.0)
+            .map_err(|e| format!("Fail to read comic directory {:?}. Error: {}", self.0, e))?;
+
+        Ok(entries
+            .flatten()
+            .filter(|o| o.file_type().is_ok_and(|t| t.is_file()))
+            .map(|o| o.file_name().to_string_lossy().into_owned())
+            .collect())
+    }
+
+    fn read_file(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        // only files directly inside the directory can be read
+        if Path::new(name).file_name().is_none_or(|o| o != name) {
+            return Err(format!(
+                "Fail to find {} in comic directory {:?}",
+                name, self.0
+            ));
+        }
+        fs::read(self.0.join(name))
+            .map_err(|e| format!("Fail to read comic file {}. Error: {}", name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            if name.ends_with('/') {
+                writer
+                    .add_directory(*name, SimpleFileOptions::default())
+                    .unwrap();
+            } else {
+                writer
+                    .start_file(*name, SimpleFileOptions::default())
+                    .unwrap();
+                writer.write_all(content).unwrap();
+            }
+        }
+        std::fs::write(path, writer.finish().unwrap().into_inner()).unwrap();
+    }
+
+    fn write_tar(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *content).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn write_7z(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut writer = sevenz_rust2::ArchiveWriter::create(path).unwrap();
+        for (name, content) in entries {
+            writer
+                .push_archive_entry(
+                    sevenz_rust2::ArchiveEntry::new_file(name),
+                    Some(Cursor::new(content.to_vec())),
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn assert_reads_entries(path: &Path, format: ArchiveFormat) {
+        assert_eq!(ArchiveFormat::detect(path), Ok(format));
+
+        let mut archive = open_archive(path).unwrap();
+        assert_eq!(
+            archive.file_names().unwrap(),
+            vec!["pages/001.jpg", "pages/002.jpg"]
+        );
+        assert_eq!(archive.read_file("pages/002.jpg").unwrap(), b"second");
+        assert!(archive.read_file("missing.jpg").is_err());
+    }
+
+    #[test]
+    fn from_magic_detects_supported_formats() {
+        assert_eq!(
+            ArchiveFormat::from_magic(b"PK\x03\x04rest"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(b"Rar!\x1a\x07\x00"),
+            Some(ArchiveFormat::Rar)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(b"Rar!\x1a\x07\x01\x00"),
+            Some(ArchiveFormat::Rar)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(b"7z\xbc\xaf\x27\x1c\x00\x04"),
+            Some(ArchiveFormat::SevenZip)
+        );
+        assert_eq!(ArchiveFormat::from_magic(b"\x89PNG"), None);
+    }
+
+    #[test]
+    fn detect_ignores_file_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("comic.cbr");
+        write_zip(&path, &[("001.jpg", b"image")]);
+
+        assert_eq!(ArchiveFormat::detect(&path), Ok(ArchiveFormat::Zip));
+    }
+
+    #[test]
+    fn detect_rejects_unknown_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("comic.cbz");
+        std::fs::write(&path, b"not an archive").unwrap();
+
+        assert!(ArchiveFormat::detect(&path).is_err());
+    }
+
+    #[test]
+    fn zip_archive_skips_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("comic.cbz");
+        write_zip(
+            &path,
+            &[
+                ("pages/", b""),
+                ("pages/001.jpg", b"first"),
+                ("pages/002.jpg", b"second"),
+            ],
+        );
+
+        assert_reads_entries(&path, ArchiveFormat::Zip);
+    }
+
+    #[test]
+    fn tar_archive_reads_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("comic.cbt");
+        write_tar(
+            &path,
+            &[("pages/001.jpg", b"first"), ("pages/002.jpg", b"second")],
+        );
+
+        assert_reads_entries(&path, ArchiveFormat::Tar);
+    }
+
+    #[test]
+    fn seven_zip_archive_reads_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("comic.cb7");
+        write_7z(
+            &path,
+            &[("pages/001.jpg", b"first"), ("pages/002.jpg", b"second")],
+        );
+
+        assert_reads_entries(&path, ArchiveFormat::SevenZip);
+    }
+
+    #[test]
+    fn rar_archive_reads_entries() {
+        // no crate writes rar archives, the checked-in fixture holds an `extras` directory and two stored pages
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("comic.cbr");
+        std::fs::write(&path, include_bytes!("../../tests/fixtures/comic.cbr")).unwrap();
+        assert_eq!(ArchiveFormat::detect(&path), Ok(ArchiveFormat::Rar));
+
+        let mut archive = open_archive(&path).unwrap();
+        assert_eq!(archive.file_names().unwrap(), vec!["001.jpg", "002.jpg"]);
+        assert_eq!(archive.read_file("002.jpg").unwrap(), b"second");
+        assert_eq!(archive.read_file("001.jpg").unwrap(), b"first");
+        assert!(archive.read_file("missing.jpg").is_err());
+    }
+
+    #[test]
+    fn directory_archive_reads_direct_children() {
+        let dir = tempfile::tempdir().unwrap();
+        let chapter = dir.path().join("Chapter 1");
+        std::fs::create_dir_all(chapter.join("extras")).unwrap();
+        std::fs::write(chapter.join("001.jpg"), b"first").unwrap();
+        std::fs::write(dir.path().join("secret.jpg"), b"secret").unwrap();
+
+        let mut archive = open_archive(&chapter).unwrap();
+        assert_eq!(archive.file_names().unwrap(), vec!["001.jpg"]);
+        assert_eq!(archive.read_file("001.jpg").unwrap(), b"first");
+        assert!(archive.read_file("../secret.jpg").is_err());
+        assert!(archive.read_file("extras").is_err());
+    }
+}
@@ -1,45 +1,50 @@
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
     ffi::OsString,
     fs,
     fs::File,
-    io::{Read, Write},
-    path::Path,
+    io::Write,
+    path::{Path, PathBuf},
 };
 
-use crate::helper::main::strip_comic_extensions;
-use crate::model::parser::{Media, MediaType};
+use crate::helper::main::{is_hidden_entry, is_image_file, natural_cmp, strip_comic_extensions};
+use crate::model::parser::{CoverFormat, Media, MediaItem, MediaType};
+use crate::parser::archive::open_archive;
 use crate::parser::utilities::convert_image;
 use log::error;
 use rayon::prelude::*;
 use tauri_plugin_notification::NotificationExt;
-use zip::{read::ZipFile, ZipArchive};
 
 pub(crate) fn parse_comics<R: tauri::Runtime>(
     app_handle: &tauri::AppHandle<R>,
     app_dir: &Path,
+    name: &str,
     root_path: &Path,
     comic_files: &[OsString],
-) -> Result<Vec<Media>, String> {
+    changed_files: &HashSet<OsString>,
+    cover_format: CoverFormat,
+) -> Result<(Vec<Media>, Vec<OsString>), String> {
     if comic_files.is_empty() {
-        return Ok(Vec::new());
+        return Ok((Vec::new(), Vec::new()));
     }
 
-    let cover_path = app_dir.join("covers");
+    let cover_folder_path = app_dir.join("covers").join(name);
 
-    let comic_folder_name = root_path.file_name().ok_or_else(|| {
-        format!(
-            "Cannot get folder name from root_path: {}",
-            root_path.display()
-        )
-    })?;
-
-    let cover_folder_path = cover_path.join(comic_folder_name);
-
-    let results: Vec<Media> = comic_files
+    let results: Vec<Result<Option<Media>, &OsString>> = comic_files
         .into_par_iter()
-        .filter_map(
-            |comic_file| match parse_comic(&cover_folder_path, root_path, comic_file) {
-                Ok(media) => Some(media),
+        .map(|comic_file| {
+            // an image folder is only parsed when one of its images changed
+            let refresh_cover =
+                changed_files.contains(comic_file) || root_path.join(comic_file).is_dir();
+            match parse_comic(
+                &cover_folder_path,
+                root_path,
+                comic_file,
+                refresh_cover,
+                cover_format,
+            ) {
+                Ok(media) => Ok(media),
                 Err(e) => {
                     let _ = app_handle
                         .notification()
@@ -47,20 +52,30 @@ pub(crate) fn parse_comics<R: tauri::Runtime>(
                         .title("MediaDB: Encounter Error when parsing comic file.")
                         .body(e)
                         .show();
-                    None
+                    Err(comic_file)
                 }
-            },
-        )
-        .flatten()
+            }
+        })
         .collect();
 
-    Ok(results)
+    // comics that failed to parse are returned, so they are tried again by the next scan
+    let mut media = Vec::new();
+    let mut failed = Vec::new();
+    for result in results {
+        match result {
+            Ok(m) => media.extend(m),
+            Err(comic_file) => failed.push(comic_file.clone()),
+        }
+    }
+    Ok((media, failed))
 }
 
 fn parse_comic(
     cover_folder_path: &Path,
     root_path: &Path,
     file_path: &OsString,
+    refresh_cover: bool,
+    cover_format: CoverFormat,
 ) -> Result<Option<Media>, String> {
     let comic_path = root_path.join(file_path);
     let relative_file_path = strip_comic_extensions(&file_path.to_string_lossy());
@@ -81,55 +96,278 @@ fn parse_comic(
         )
     })?;
 
-    let file = File::open(&comic_path)
-        .map_err(|e| format!("Fail to open comic file {:?}. Error: {}", file_path, e))?;
+    let mut archive = open_archive(&comic_path)?;
+    let file_names = archive.file_names()?;
+    let comic_info = match find_comic_info(&file_names) {
+        Some(name) => Some(String::from_utf8_lossy(&archive.read_file(name)?).into_owned()),
+        None => None,
+    };
 
-    let mut archive = ZipArchive::new(file)
-        .map_err(|e| format!("Fail to read zip file {:?}. Error: {}", file_path, e))?;
-
-    for i in 0..archive.len() {
-        let mut zip_file = archive
-            .by_index(i)
-            .map_err(|e| format!("Fail to get file at index {}. Error: {}", i, e))?;
-
-        if !zip_file.is_file() {
-            continue;
+    if refresh_cover || !cover_dest_path.exists() {
+        let front_cover = comic_info.as_deref().and_then(get_front_cover_page);
+        if let Some(cover) = select_cover(&file_names, front_cover) {
+            save_cover(&cover_dest_path, &archive.read_file(cover)?, cover_format)?;
         }
-
-        save_cover(&cover_dest_path, &mut zip_file)?;
-        break;
     }
 
-    let file_name = comic_path.file_stem().ok_or_else(|| {
-        format!(
-            "Cannot get file stem for comic path {}",
-            comic_path.display()
-        )
-    })?;
-
-    let file_name_str = comic_path.file_name().ok_or_else(|| {
+    let file_name = comic_path.file_name().ok_or_else(|| {
         format!(
             "Cannot get file name for comic path {}",
             comic_path.display()
         )
     })?;
 
+    // image folder comics are titled by the directory name, which may contain dots
+    let title = if comic_path.is_dir() {
+        file_name
+    } else {
+        comic_path.file_stem().unwrap_or(file_name)
+    };
+
     let mut media = Media::default();
     media.set_media_type(MediaType::Comic);
-    media.set_title(String::from(file_name.to_string_lossy()));
-    media.set_file(String::from(file_name_str.to_string_lossy()));
-    media.add_poster(String::from(file_name.to_string_lossy()));
+    media.set_title(String::from(title.to_string_lossy()));
+    media.set_file(String::from(file_name.to_string_lossy()));
+    media.add_poster(relative_file_path);
     media.set_relative_path(file_path.to_os_string());
+
+    if let Some(content) = comic_info {
+        // metadata is optional, a broken ComicInfo.xml must not drop the comic
+        if let Err(e) = parse_comic_info(&mut media, &content) {
+            error!("{} in {}", e, comic_path.display());
+        }
+    }
     Ok(Some(media))
 }
 
-fn save_cover(comic_dest_name: &Path, file: &mut ZipFile<File>) -> Result<(), String> {
-    let mut content = Vec::new();
-    file.read_to_end(&mut content)
-        .map_err(|e| format!("Fail to read zip file content. Error: {}", e))?;
+/// Fold comics into series, like episodes are folded into a tv show. Comics in the same directory
+/// form a series, split further by their ComicInfo `Series`. Image folder comics are grouped by
+/// their parent directory. A series needs at least two issues, and loose comics at the library root
+/// are only grouped by `Series`.
+pub(crate) fn aggregate_comics(comics: &[&Media]) -> Vec<MediaItem> {
+    let mut groups: BTreeMap<(PathBuf, String), Vec<&Media>> = BTreeMap::new();
+    for comic in comics {
+        groups.entry(get_series_key(comic)).or_default().push(comic);
+    }
+
+    groups
+        .into_iter()
+        .flat_map(|((dir, series), mut issues)| {
+            if issues.len() < 2 || (series.is_empty() && dir.as_os_str().is_empty()) {
+                return issues.iter().filter_map(|o| o.comic()).collect();
+            }
+
+            issues.sort_by(|a, b| compare_issues(a, b));
+            let mut volumes: BTreeMap<String, Vec<&Media>> = BTreeMap::new();
+            for issue in &issues {
+                // issues without a volume belong to the first one
+                let volume = match issue.season() {
+                    "" => String::from("01"),
+                    v => v.to_string(),
+                };
+                volumes.entry(volume).or_default().push(issue);
+            }
+
+            get_series(&dir, &series, &issues)
+                .comic_series(&volumes)
+                .into_iter()
+                .collect::<Vec<MediaItem>>()
+        })
+        .collect()
+}
+
+fn get_series_key(comic: &Media) -> (PathBuf, String) {
+    let source_dir = Path::new(comic.source_dir());
+    // an image folder comic is the directory itself
+    let dir = if comic.relative_path() == comic.source_dir() {
+        source_dir.parent().unwrap_or(Path::new(""))
+    } else {
+        source_dir
+    };
+    (dir.to_path_buf(), comic.series().to_string())
+}
+
+fn compare_issues(a: &Media, b: &Media) -> Ordering {
+    natural_cmp(a.season(), b.season())
+        .then_with(|| natural_cmp(a.episode(), b.episode()))
+        .then_with(|| {
+            natural_cmp(
+                &a.relative_path().to_string_lossy(),
+                &b.relative_path().to_string_lossy(),
+            )
+        })
+}
+
+/// The series item of `issues`, which must be in reading order. It takes the cover and year of the
+/// first issue and the tags of all of them. A series named by ComicInfo gets a path of its own, so
+/// it never clashes with a directory of the same name.
+fn get_series(dir: &Path, series: &str, issues: &[&Media]) -> Media {
+    let mut media = Media::default();
+    media.set_media_type(MediaType::Comic);
+    media.set_source_dir(dir.as_os_str().to_os_string());
+    if series.is_empty() {
+        let title = dir.file_name().unwrap_or(dir.as_os_str());
+        media.set_title(title.to_string_lossy().into_owned());
+        media.set_relative_path(dir.as_os_str().to_os_string());
+    } else {
+        media.set_title(series.to_string());
+        media.set_relative_path(dir.join(format!("{}#series", series)).into_os_string());
+    }
+
+    if let Some(first) = issues.first() {
+        media.set_posters(first.posters().to_vec());
+        media.set_year(first.year().to_string());
+    }
+    for issue in issues {
+        media.merge_tags(issue);
+    }
+    media
+}
+
+/// Image entries of an archive in reading order.
+fn get_pages(file_names: &[String]) -> Vec<&String> {
+    let mut pages = file_names
+        .iter()
+        .filter(|o| is_image_file(o) && !is_hidden_entry(o))
+        .collect::<Vec<&String>>();
+    pages.sort_by(|a, b| natural_cmp(a, b));
+    pages
+}
+
+/// List the pages of the comic at `comic_path` in reading order.
+pub(crate) fn get_comic_pages(comic_path: &Path) -> Result<Vec<String>, String> {
+    let file_names = open_archive(comic_path)?.file_names()?;
+    Ok(get_pages(&file_names).into_iter().cloned().collect())
+}
+
+/// Read page `index` of the comic at `comic_path`, returning the entry name with its content,
+/// or None when the comic has fewer pages.
+pub(crate) fn read_comic_page(
+    comic_path: &Path,
+    index: usize,
+) -> Result<Option<(String, Vec<u8>)>, String> {
+    let mut archive = open_archive(comic_path)?;
+    let file_names = archive.file_names()?;
+    let Some(page) = get_pages(&file_names).get(index).map(|o| o.to_string()) else {
+        return Ok(None);
+    };
+    let content = archive.read_file(&page)?;
+    Ok(Some((page, content)))
+}
+
+/// Pick the cover among the image entries of an archive, in reading order. The ComicInfo front
+/// cover wins, then an entry named `cover.*` or `000.*`, and otherwise the first page.
+fn select_cover(file_names: &[String], front_cover: Option<usize>) -> Option<&String> {
+    let pages = get_pages(file_names);
+
+    if let Some(page) = front_cover.and_then(|o| pages.get(o)) {
+        return Some(page);
+    }
+
+    let stem = |name: &str| {
+        Path::new(name)
+            .file_stem()
+            .map(|o| o.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    };
+    pages
+        .iter()
+        .find(|o| stem(o) == "cover")
+        .or_else(|| {
+            pages.iter().find(|o| {
+                let stem = stem(o);
+                !stem.is_empty() && stem.chars().all(|c| c == '0')
+            })
+        })
+        .or(pages.first())
+        .copied()
+}
+
+/// Index of the page marked as `FrontCover` in the `Pages` list of ComicInfo.xml.
+fn get_front_cover_page(content: &str) -> Option<usize> {
+    let doc = roxmltree::Document::parse(content).ok()?;
+    doc.descendants()
+        .filter(|o| o.has_tag_name("Page"))
+        .find(|o| o.attribute("Type") == Some("FrontCover"))
+        .and_then(|o| o.attribute("Image"))
+        .and_then(|o| o.trim().parse().ok())
+}
+
+fn find_comic_info(file_names: &[String]) -> Option<&String> {
+    file_names.iter().find(|o| {
+        Path::new(o)
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case("ComicInfo.xml"))
+    })
+}
+
+fn parse_comic_info(media: &mut Media, content: &str) -> Result<(), String> {
+    let doc = roxmltree::Document::parse(content)
+        .map_err(|e| format!("Error when parsing ComicInfo.xml. Raising error {}", e))?;
+
+    let root = doc.root_element();
+    if root.tag_name().name() != "ComicInfo" {
+        return Err(String::from("ComicInfo.xml does not have a ComicInfo root"));
+    }
+
+    let mut title = None;
+    let mut series = None;
+    let mut number = None;
+    for node in root.children().filter(|o| o.is_element()) {
+        let Some(text) = node.text().map(str::trim).filter(|o| !o.is_empty()) else {
+            continue;
+        };
+        match node.tag_name().name() {
+            "Title" => title = Some(text),
+            "Series" => {
+                series = Some(text);
+                media.set_series(text.to_string());
+            }
+            "Number" => {
+                number = Some(text);
+                media.set_episode(text.to_string());
+            }
+            "Volume" => media.set_season(text.to_string()),
+            "Year" => media.set_year(text.to_string()),
+            "Genre" => split_list(text).for_each(|o| media.add_genre(o)),
+            "Tags" => split_list(text).for_each(|o| media.add_tag(o)),
+            "Publisher" => media.add_studio(text.to_string()),
+            "Writer" | "Penciller" | "Inker" | "Colorist" | "Letterer" | "CoverArtist"
+            | "Editor" => media.extend_actors(split_list(text).collect()),
+            &_ => {}
+        }
+    }
 
+    let display_title = match (series, number, title) {
+        (Some(series), Some(number), Some(title)) => {
+            Some(format!("{} #{}: {}", series, number, title))
+        }
+        (Some(series), Some(number), None) => Some(format!("{} #{}", series, number)),
+        (Some(series), None, None) => Some(series.to_string()),
+        (_, _, Some(title)) => Some(title.to_string()),
+        _ => None,
+    };
+    if let Some(v) = display_title {
+        media.set_title(v);
+    }
+    Ok(())
+}
+
+// ComicInfo stores multiple values as a comma separated list
+fn split_list(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(',')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .map(str::to_string)
+}
+
+fn save_cover(
+    comic_dest_name: &Path,
+    content: &[u8],
+    cover_format: CoverFormat,
+) -> Result<(), String> {
     File::create(comic_dest_name)
-        .and_then(|mut f| f.write_all(content.as_slice()))
+        .and_then(|mut f| f.write_all(content))
         .map_err(|e| format!("Fail to write cover file. Error: {}", e))?;
 
     let comic_cover_path = comic_dest_name
@@ -137,9 +375,313 @@ fn save_cover(comic_dest_name: &Path, file: &mut ZipFile<File>) -> Result<(), St
         .to_str()
         .ok_or_else(|| format!("Invalid cover path: {}", comic_dest_name.display()))?;
 
-    if let Err(e) = convert_image(comic_cover_path, comic_cover_path) {
+    if let Err(e) = convert_image(comic_cover_path, comic_cover_path, cover_format) {
         error!("{}", e);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMIC_INFO: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+    <Title>The Judas Contract</Title>
+    <Series>The New Teen Titans</Series>
+    <Number>44</Number>
+    <Volume>1</Volume>
+    <Year>1984</Year>
+    <Writer>Marv Wolfman</Writer>
+    <Penciller>George Perez, Dick Giordano</Penciller>
+    <Publisher>DC Comics</Publisher>
+    <Genre>Superhero, Action</Genre>
+    <Tags>Deathstroke</Tags>
+</ComicInfo>"#;
+
+    #[test]
+    fn parse_comic_info_maps_metadata() {
+        let mut media = Media::default();
+        media.set_media_type(MediaType::Comic);
+        parse_comic_info(&mut media, COMIC_INFO).unwrap();
+
+        let item = media.comic().unwrap();
+        assert_eq!(item.title(), "The New Teen Titans #44: The Judas Contract");
+        assert_eq!(item.year(), "1984");
+        assert_eq!(item.genres(), &["Superhero", "Action"]);
+        assert_eq!(item.tags(), &["Deathstroke"]);
+        assert_eq!(item.studios(), &["DC Comics"]);
+        assert_eq!(
+            item.actors(),
+            &["Marv Wolfman", "George Perez", "Dick Giordano"]
+        );
+        assert_eq!(media.season(), "01");
+        assert_eq!(media.episode(), "44");
+    }
+
+    #[test]
+    fn parse_comic_info_keeps_title_without_series_or_title() {
+        let mut media = Media::default();
+        media.set_media_type(MediaType::Comic);
+        media.set_title("Issue 01".to_string());
+        parse_comic_info(&mut media, "<ComicInfo><Year>2001</Year></ComicInfo>").unwrap();
+
+        assert_eq!(media.comic().unwrap().title(), "Issue 01");
+    }
+
+    #[test]
+    fn parse_comic_info_rejects_other_documents() {
+        let mut media = Media::default();
+        assert!(parse_comic_info(&mut media, "<movie><title>Heat</title></movie>").is_err());
+        assert!(parse_comic_info(&mut media, "not xml").is_err());
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|o| o.to_string()).collect()
+    }
+
+    #[test]
+    fn select_cover_skips_non_images_and_system_entries() {
+        let file_names = names(&[
+            "ComicInfo.xml",
+            "__MACOSX/._page1.jpg",
+            ".hidden.jpg",
+            "page10.jpg",
+            "page2.jpg",
+        ]);
+        assert_eq!(
+            select_cover(&file_names, None),
+            Some(&"page2.jpg".to_string())
+        );
+    }
+
+    #[test]
+    fn select_cover_prefers_named_cover() {
+        let file_names = names(&["001.jpg", "000.jpg", "Cover.PNG"]);
+        assert_eq!(
+            select_cover(&file_names, None),
+            Some(&"Cover.PNG".to_string())
+        );
+
+        let file_names = names(&["credits.jpg", "scans/000.jpg"]);
+        assert_eq!(
+            select_cover(&file_names, None),
+            Some(&"scans/000.jpg".to_string())
+        );
+    }
+
+    #[test]
+    fn select_cover_uses_front_cover_page() {
+        let file_names = names(&["p3.jpg", "p1.jpg", "p2.jpg", "cover.jpg"]);
+        assert_eq!(
+            select_cover(&file_names, Some(2)),
+            Some(&"p2.jpg".to_string())
+        );
+        // an index past the last page falls back to the name based rules
+        assert_eq!(
+            select_cover(&file_names, Some(9)),
+            Some(&"cover.jpg".to_string())
+        );
+        assert_eq!(select_cover(&names(&["ComicInfo.xml"]), None), None);
+    }
+
+    #[test]
+    fn get_front_cover_page_reads_pages() {
+        let content = r#"<ComicInfo><Pages>
+            <Page Image="0" Type="Advertisement"/>
+            <Page Image="1" Type="FrontCover"/>
+        </Pages></ComicInfo>"#;
+        assert_eq!(get_front_cover_page(content), Some(1));
+        assert_eq!(get_front_cover_page(COMIC_INFO), None);
+    }
+
+    #[test]
+    fn parse_comic_reads_image_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let chapter = dir.path().join("library").join("Manga").join("Chapter 1.5");
+        fs::create_dir_all(&chapter).unwrap();
+        fs::write(chapter.join("010.jpg"), "last").unwrap();
+        fs::write(chapter.join("002.jpg"), "first").unwrap();
+
+        let covers = dir.path().join("covers").join("Manga");
+        let relative_path = Path::new("Manga").join("Chapter 1.5").into_os_string();
+        let library = dir.path().join("library");
+        let media = parse_comic(&covers, &library, &relative_path, true, CoverFormat::Jpeg)
+            .unwrap()
+            .unwrap();
+
+        let item = media.comic().unwrap();
+        assert_eq!(item.title(), "Chapter 1.5");
+        assert_eq!(item.posters(), r#"{"main":"Manga/Chapter 1.5"}"#);
+        assert_eq!(
+            fs::read(covers.join("Manga").join("Chapter 1.5")).unwrap(),
+            b"first"
+        );
+    }
+
+    fn issue(path: &str, dir: &str, series: &str, volume: &str, number: &str) -> Media {
+        let mut media = Media::default();
+        media.set_media_type(MediaType::Comic);
+        media.set_relative_path(OsString::from(path));
+        media.set_source_dir(OsString::from(dir));
+        media.set_title(path.to_string());
+        media.add_poster(strip_comic_extensions(path));
+        media.set_series(series.to_string());
+        if !volume.is_empty() {
+            media.set_season(volume.to_string());
+        }
+        if !number.is_empty() {
+            media.set_episode(number.to_string());
+        }
+        media
+    }
+
+    fn aggregate(comics: &[Media]) -> Vec<MediaItem> {
+        let mut items = aggregate_comics(&comics.iter().collect::<Vec<&Media>>());
+        items.sort_by(|a, b| a.path().cmp(b.path()));
+        items
+    }
+
+    fn issue_paths(item: &MediaItem) -> Vec<(String, String)> {
+        let volumes: BTreeMap<String, Vec<serde_json::Value>> =
+            serde_json::from_str(item.seasons()).unwrap();
+        volumes
+            .into_iter()
+            .flat_map(|(volume, issues)| {
+                issues
+                    .into_iter()
+                    .map(move |o| (volume.clone(), o["path"].as_str().unwrap().to_string()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aggregate_comics_groups_directories_into_series() {
+        let items = aggregate(&[
+            issue("Saga/Saga 10.cbz", "Saga", "", "", ""),
+            issue("Saga/Saga 2.cbz", "Saga", "", "", ""),
+            issue("Saga/Saga 1.cbz", "Saga", "", "", ""),
+            issue("Single/One Shot.cbz", "Single", "", "", ""),
+            issue("Loose 1.cbz", "", "", "", ""),
+            issue("Loose 2.cbz", "", "", "", ""),
+        ]);
+
+        let paths: Vec<&str> = items.iter().map(|o| o.path()).collect();
+        assert_eq!(
+            paths,
+            vec!["Loose 1.cbz", "Loose 2.cbz", "Saga", "Single/One Shot.cbz"]
+        );
+
+        let saga = &items[2];
+        assert_eq!(saga.title(), "Saga");
+        assert_eq!(saga.dir(), "Saga");
+        assert_eq!(saga.posters(), r#"{"main":"Saga/Saga 1"}"#);
+        assert_eq!(
+            issue_paths(saga),
+            vec![
+                ("01".to_string(), "Saga/Saga 1.cbz".to_string()),
+                ("01".to_string(), "Saga/Saga 2.cbz".to_string()),
+                ("01".to_string(), "Saga/Saga 10.cbz".to_string()),
+            ]
+        );
+        assert_eq!(items[3].seasons(), "");
+    }
+
+    #[test]
+    fn aggregate_comics_splits_directories_by_series() {
+        let items = aggregate(&[
+            issue("Mixed/b.cbz", "Mixed", "Hawkeye", "2", "1"),
+            issue("Mixed/a.cbz", "Mixed", "Hawkeye", "1", "11"),
+            issue("Mixed/c.cbz", "Mixed", "Hawkeye", "1", "2"),
+            issue("Mixed/x.cbz", "Mixed", "Other", "", ""),
+            issue("root-1.cbz", "", "Saga", "", "1"),
+            issue("root-2.cbz", "", "Saga", "", "2"),
+        ]);
+
+        let paths: Vec<String> = items.iter().map(|o| o.path().replace('\\', "/")).collect();
+        assert_eq!(
+            paths,
+            vec!["Mixed/Hawkeye#series", "Mixed/x.cbz", "Saga#series"]
+        );
+        assert_eq!(items[0].title(), "Hawkeye");
+        assert_eq!(
+            issue_paths(&items[0]),
+            vec![
+                ("01".to_string(), "Mixed/c.cbz".to_string()),
+                ("01".to_string(), "Mixed/a.cbz".to_string()),
+                ("02".to_string(), "Mixed/b.cbz".to_string()),
+            ]
+        );
+        assert_eq!(items[2].title(), "Saga");
+    }
+
+    #[test]
+    fn aggregate_comics_keeps_series_apart_from_directories_of_the_same_name() {
+        let items = aggregate(&[
+            issue("Saga/1.cbz", "Saga", "", "", ""),
+            issue("Saga/2.cbz", "Saga", "", "", ""),
+            issue("root-1.cbz", "", "Saga", "", "1"),
+            issue("root-2.cbz", "", "Saga", "", "2"),
+            issue("Marvel/Hawkeye/1.cbz", "Marvel/Hawkeye", "", "", ""),
+            issue("Marvel/Hawkeye/2.cbz", "Marvel/Hawkeye", "", "", ""),
+            issue("Marvel/a.cbz", "Marvel", "Hawkeye", "", "1"),
+            issue("Marvel/b.cbz", "Marvel", "Hawkeye", "", "2"),
+        ]);
+
+        let paths: Vec<String> = items.iter().map(|o| o.path().replace('\\', "/")).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "Marvel/Hawkeye",
+                "Marvel/Hawkeye#series",
+                "Saga",
+                "Saga#series"
+            ]
+        );
+        let titles: Vec<&str> = items.iter().map(|o| o.title()).collect();
+        assert_eq!(titles, vec!["Hawkeye", "Hawkeye", "Saga", "Saga"]);
+    }
+
+    #[test]
+    fn aggregate_comics_groups_image_folders_by_parent() {
+        let chapter = |n: &str| {
+            let path = format!("Manga/Chapter {}", n);
+            issue(&path, &path, "", "", "")
+        };
+        let items = aggregate(&[chapter("2"), chapter("1"), chapter("1.5")]);
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].path(), "Manga");
+        assert_eq!(items[0].posters(), r#"{"main":"Manga/Chapter 1"}"#);
+        let paths: Vec<String> = issue_paths(&items[0]).into_iter().map(|o| o.1).collect();
+        assert_eq!(
+            paths,
+            vec!["Manga/Chapter 1", "Manga/Chapter 1.5", "Manga/Chapter 2"]
+        );
+    }
+
+    #[test]
+    fn aggregate_comics_merges_issue_tags() {
+        let mut first = issue("Saga/1.cbz", "Saga", "", "", "");
+        first.add_genre("Fantasy".to_string());
+        first.set_year("2012".to_string());
+        let mut second = issue("Saga/2.cbz", "Saga", "", "", "");
+        second.add_genre("Fantasy".to_string());
+        second.add_genre("Drama".to_string());
+
+        let items = aggregate(&[second, first]);
+        assert_eq!(items[0].genres(), &["Fantasy", "Drama"]);
+        assert_eq!(items[0].year(), "2012");
+    }
+
+    #[test]
+    fn find_comic_info_ignores_case_and_directories() {
+        let names = vec!["001.jpg".to_string(), "meta/comicinfo.XML".to_string()];
+        assert_eq!(
+            find_comic_info(&names),
+            Some(&"meta/comicinfo.XML".to_string())
+        );
+        assert_eq!(find_comic_info(&names[..1]), None);
+    }
+}
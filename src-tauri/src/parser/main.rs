@@ -3,7 +3,6 @@ use std::{
     ffi::OsString,
     fs,
     path::{Path, PathBuf},
-    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use log::error;
@@ -11,35 +10,392 @@ use rayon::prelude::*;
 use tauri::Manager;
 use tauri_plugin_notification::NotificationExt;
 
-use crate::helper::main::strip_image_extensions;
-use crate::model::parser::{Media, MediaItem, MediaSource, MediaType};
-use crate::parser::comic_parser::parse_comics;
+use crate::helper::main::{
+    get_art_type, get_disc_parent, get_disc_target, get_legal_file_name, is_hidden_entry,
+    is_image_file, strip_comic_extensions, strip_image_extensions,
+};
+use crate::model::parser::{
+    CoverFormat, FileFingerprint, Media, MediaItem, MediaSource, MediaType, ScanResult,
+    ScanSettings,
+};
+use crate::parser::comic_parser::{aggregate_comics, parse_comics};
+use crate::parser::name_parser::{infer_episodes, infer_movies};
+use crate::parser::nfo_parser::{parse_nfo, read_movie_set};
+use crate::parser::probe::probe_media;
+use crate::parser::trickplay::remove_trickplay;
+use crate::parser::utilities;
 use crate::parser::utilities::convert_image;
-use crate::{parser::nfo_parser::parse_nfo, parser::utilities};
+
+/// Files found in a single directory of the library, relative to the library root.
+#[derive(Debug, Default)]
+struct DirectoryListing {
+    dir: OsString,
+    nfo_files: Vec<OsString>,
+    media_source: MediaSource,
+    fingerprints: Vec<FileFingerprint>,
+}
+
+/// Frame to grab from a video for a media without artwork. The cover is saved where the one of
+/// the missing Kodi artwork file would go, relative to the covers of the library.
+#[derive(Debug, PartialEq)]
+struct VideoFrame {
+    video: PathBuf,
+    cover: PathBuf,
+    poster: String, // image the media gets, relative to its directory
+    duration: u32,
+}
+
+#[derive(Debug, Default)]
+struct FingerprintDiff {
+    changed: Vec<FileFingerprint>,
+    removed: Vec<String>,
+    dirty_dirs: HashSet<String>,
+}
 
 pub fn parse<R: tauri::Runtime>(
     app_handle: &tauri::AppHandle<R>,
     name: &str,
     path: &str,
     skip_paths: &HashSet<String>,
-) -> Vec<MediaItem> {
+    previous: &HashMap<String, FileFingerprint>,
+    scope: Option<&[PathBuf]>,
+    settings: &ScanSettings,
+) -> Result<ScanResult, String> {
     let app_dir = app_handle.path().app_data_dir().unwrap();
-    let (major_media, secondary_media) = read_dir(app_handle, path, skip_paths);
-    let (data, posters) = aggregate_data(&major_media, &secondary_media);
-    handle_images(&app_dir, name, path, &posters);
-    data
+    let root_path = Path::new(path);
+    let image_comics = settings.image_comics();
+
+    // without any history only a scan of the whole library can produce a complete diff
+    let scope = scope.filter(|_| !previous.is_empty());
+    let scope = scope.map(|dirs| {
+        let dirs = dirs
+            .iter()
+            .filter(|o| !is_skipped(o, skip_paths))
+            // changes inside a disc structure are changes to the directory holding it
+            .map(|o| get_disc_parent(o).unwrap_or_else(|| o.clone()))
+            .collect::<Vec<PathBuf>>();
+        let dirs = if image_comics {
+            // image folder comics are grouped by their parent, which has to be walked as well
+            get_parent_dirs(&dirs)
+        } else {
+            dirs
+        };
+        // episodes are aggregated into their show, so the whole show has to be walked
+        remove_nested_dirs(
+            dirs.into_iter()
+                .map(|o| find_show_dir(root_path, &o).unwrap_or(o))
+                .collect(),
+        )
+    });
+
+    let mut listings = read_dir(path, scope.as_deref(), skip_paths, image_comics)?;
+    // the movies of a set may lie anywhere in the library, so changed set artwork needs a full walk
+    let scope = match scope {
+        Some(_) if listings.iter().any(is_set_listing) => {
+            listings = read_dir(path, None, skip_paths, image_comics)?;
+            None
+        }
+        scope => scope,
+    };
+    let mut diff = diff_fingerprints(&listings, previous, scope.as_deref());
+    if image_comics {
+        mark_image_comic_series(&listings, &mut diff.dirty_dirs);
+    }
+    mark_show_dirs(root_path, &listings, &mut diff.dirty_dirs);
+    mark_collection_dirs(root_path, &listings, &mut diff.dirty_dirs);
+    let changed_files: HashSet<OsString> = diff
+        .changed
+        .iter()
+        .map(|o| OsString::from(o.path()))
+        .collect();
+
+    let mut major_media = Vec::new();
+    let mut secondary_media = Vec::new();
+    let mut failed_files = Vec::new();
+    for listing in listings
+        .iter()
+        .filter(|o| diff.dirty_dirs.contains(&*o.dir.to_string_lossy()))
+    {
+        let (mut media, failed) = handle_media_path(
+            app_handle,
+            name,
+            &listing.nfo_files,
+            root_path,
+            &listing.media_source,
+            &changed_files,
+            settings.cover_format(),
+        );
+        failed_files.extend(failed);
+        // videos without an NFO file are identified from their names
+        if find_show_dir(root_path, Path::new(&listing.dir)).is_some() {
+            let episodes = infer_episodes(&listing.media_source, &media);
+            media.extend(episodes);
+        } else if listing.nfo_files.is_empty() {
+            media.extend(infer_movies(&listing.dir, &listing.media_source));
+        }
+        for mut m in media {
+            m.set_source_dir(listing.dir.clone());
+            match m.media_type() {
+                MediaType::Movie | MediaType::TvShow | MediaType::Comic => {
+                    major_media.push(m);
+                }
+                MediaType::Episode => {
+                    secondary_media.push(m);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    major_media.par_sort_by(|a, b| a.relative_path().cmp(b.relative_path()));
+
+    // videos an NFO file does not describe are probed, unchanged ones are read from the cache
+    let probes = match settings.probes() {
+        Some(cache) => {
+            let fingerprints = listings
+                .iter()
+                .flat_map(|o| &o.fingerprints)
+                .map(|o| (o.path(), o))
+                .collect::<HashMap<&str, &FileFingerprint>>();
+            major_media
+                .par_iter_mut()
+                .chain(secondary_media.par_iter_mut())
+                .filter_map(|o| probe_media(root_path, o, &fingerprints, cache))
+                .collect()
+        }
+        None => vec![],
+    };
+
+    add_video_frames(
+        &app_dir,
+        name,
+        root_path,
+        &mut major_media,
+        &mut secondary_media,
+        &changed_files,
+        settings.cover_format(),
+    );
+
+    let mut posters = find_collection_posters(root_path, &mut major_media);
+    let (data, media_posters) = aggregate_data(&major_media, &secondary_media);
+    posters.extend(media_posters);
+    handle_images(
+        &app_dir,
+        name,
+        path,
+        &posters,
+        &changed_files,
+        settings.cover_format(),
+    );
+    remove_covers(&app_dir, name, root_path, &diff.removed);
+    remove_trickplay(&app_dir, name, &diff.removed);
+
+    drop_failed_fingerprints(&mut diff.changed, &failed_files);
+    let mut scan = ScanResult::new(
+        data,
+        diff.changed,
+        diff.removed,
+        diff.dirty_dirs.into_iter().collect(),
+        previous.is_empty(),
+    );
+    scan.set_probes(probes);
+    Ok(scan)
 }
 
-fn read_dir<R: tauri::Runtime>(
-    app_handle: &tauri::AppHandle<R>,
+/// Forget the files that failed to parse, so the next scan sees them as new and tries them again.
+/// An image folder comic fails as a whole, its images are dropped with it.
+fn drop_failed_fingerprints(fingerprints: &mut Vec<FileFingerprint>, failed_files: &[OsString]) {
+    fingerprints.retain(|o| {
+        !failed_files
+            .iter()
+            .any(|failed| Path::new(o.path()).starts_with(failed))
+    });
+}
+
+/// Replace every directory with its parent, dropping directories nested in another one.
+fn get_parent_dirs(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    remove_nested_dirs(
+        dirs.iter()
+            .map(|o| match o.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => o.clone(),
+            })
+            .collect(),
+    )
+}
+
+/// Sort the directories and drop the ones nested in another one.
+fn remove_nested_dirs(mut dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    // sorted order puts every directory before its descendants
+    dirs.sort();
+    let mut result: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !result.iter().any(|o| dir.starts_with(o)) {
+            result.push(dir);
+        }
+    }
+    result
+}
+
+/// Episodes and season directories belong to the show they are nested in. When anything inside a
+/// show changes, the show directory and all of its season directories are parsed again so the
+/// seasons are rebuilt as a whole.
+fn mark_show_dirs(
+    root_path: &Path,
+    listings: &[DirectoryListing],
+    dirty_dirs: &mut HashSet<String>,
+) {
+    let show_dirs = dirty_dirs
+        .iter()
+        .filter_map(|o| find_show_dir(root_path, Path::new(o)))
+        .collect::<HashSet<PathBuf>>();
+    if show_dirs.is_empty() {
+        return;
+    }
+    for listing in listings {
+        if show_dirs
+            .iter()
+            .any(|o| Path::new(&listing.dir).starts_with(o))
+        {
+            dirty_dirs.insert(listing.dir.to_string_lossy().into_owned());
+        }
+    }
+}
+
+/// Folder at the library root holding only artwork, the artwork of the movie set it is named after.
+fn is_set_listing(listing: &DirectoryListing) -> bool {
+    Path::new(&listing.dir).components().count() == 1
+        && listing.nfo_files.is_empty()
+        && listing.media_source.media().is_empty()
+        && listing.media_source.comic().is_empty()
+        && !listing.media_source.poster().is_empty()
+}
+
+/// Movies pick up the artwork of their set while parsed. When the artwork in a set folder changes,
+/// the movies whose NFO file puts them in that set are parsed again.
+fn mark_collection_dirs(
+    root_path: &Path,
+    listings: &[DirectoryListing],
+    dirty_dirs: &mut HashSet<String>,
+) {
+    let set_dirs = listings
+        .iter()
+        .filter(|o| is_set_listing(o))
+        .map(|o| o.dir.to_string_lossy().into_owned())
+        .filter(|o| dirty_dirs.contains(o))
+        .collect::<HashSet<String>>();
+    if set_dirs.is_empty() {
+        return;
+    }
+    for listing in listings {
+        let dir = listing.dir.to_string_lossy().into_owned();
+        if dirty_dirs.contains(&dir) {
+            continue;
+        }
+        let in_set = listing
+            .nfo_files
+            .iter()
+            .filter_map(|o| read_movie_set(&root_path.join(o)))
+            .any(|o| set_dirs.contains(&get_legal_file_name(&o)));
+        if in_set {
+            dirty_dirs.insert(dir);
+        }
+    }
+}
+
+/// Image folder comics form a series with the other image folders of their parent directory. When
+/// one of them changes, the parent and all of its image folder comics are parsed again so the series
+/// is rebuilt as a whole.
+fn mark_image_comic_series(listings: &[DirectoryListing], dirty_dirs: &mut HashSet<String>) {
+    let listed_dirs = listings
+        .iter()
+        .map(|o| o.dir.to_string_lossy().into_owned())
+        .collect::<HashSet<String>>();
+    let parents = dirty_dirs
+        .iter()
+        .filter_map(|o| Path::new(o).parent())
+        .map(|o| o.to_string_lossy().into_owned())
+        .filter(|o| !o.is_empty() && listed_dirs.contains(o))
+        .collect::<HashSet<String>>();
+
+    for listing in listings {
+        let is_image_comic = listing.media_source.comic().contains(&listing.dir);
+        let parent = Path::new(&listing.dir)
+            .parent()
+            .map(|o| o.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if is_image_comic && parents.contains(&parent) {
+            dirty_dirs.insert(listing.dir.to_string_lossy().into_owned());
+        }
+    }
+    dirty_dirs.extend(parents);
+}
+
+/// The nearest directory holding a `tvshow.nfo`, starting from `relative_dir` itself.
+fn find_show_dir(root_path: &Path, relative_dir: &Path) -> Option<PathBuf> {
+    relative_dir
+        .ancestors()
+        .filter(|o| !o.as_os_str().is_empty())
+        .find(|o| root_path.join(o).join("tvshow.nfo").is_file())
+        .map(|o| o.to_path_buf())
+}
+
+fn is_skipped(relative_dir: &Path, skip_paths: &HashSet<String>) -> bool {
+    relative_dir.components().any(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name.starts_with('.') || skip_paths.contains(&*name)
+    })
+}
+
+fn find_disc_target(disc_path: &Path, target: &str) -> Option<PathBuf> {
+    fs::read_dir(disc_path)
+        .ok()?
+        .flatten()
+        .find(|o| o.file_name().to_string_lossy().eq_ignore_ascii_case(target))
+        .map(|o| o.path())
+}
+
+fn add_disc(listing: &mut DirectoryListing, target_path: &Path, root_path: &Path) {
+    let Some(relative_path) = utilities::get_relative_path(target_path, root_path) else {
+        return;
+    };
+    match fs::metadata(target_path) {
+        Ok(metadata) => listing
+            .fingerprints
+            .push(utilities::get_fingerprint(&relative_path, &metadata)),
+        Err(e) => error!("Failed to read metadata for {:?}: {}", target_path, e),
+    }
+    listing
+        .media_source
+        .add_media(relative_path.into_os_string());
+}
+
+/// Walk the library from `scope` (directories relative to the root), or from the root itself when
+/// no scope is given. With `image_comics`, leaf directories holding only images are listed as comics.
+fn read_dir(
     path: &str,
+    scope: Option<&[PathBuf]>,
     skip_paths: &HashSet<String>,
-) -> (Vec<Media>, Vec<Media>) {
+    image_comics: bool,
+) -> Result<Vec<DirectoryListing>, String> {
     let root_path = Path::new(path);
-    let mut queue = VecDeque::from([OsString::from(path)]);
+    // an unreachable root must not be mistaken for an emptied library
+    if let Err(e) = fs::read_dir(root_path) {
+        return Err(format!(
+            "Failed to read library root {:?}. Raising error {}",
+            root_path, e
+        ));
+    }
 
-    let mut major_media = Vec::new();
-    let mut secondary_media = Vec::new();
+    let mut queue = match scope {
+        Some(dirs) => dirs
+            .iter()
+            .map(|o| root_path.join(o).into_os_string())
+            .collect::<VecDeque<OsString>>(),
+        None => VecDeque::from([OsString::from(path)]),
+    };
+
+    let mut listings = Vec::new();
 
     while let Some(curr_dir) = queue.pop_front() {
         let entries = match fs::read_dir(&curr_dir) {
@@ -50,8 +406,15 @@ fn read_dir<R: tauri::Runtime>(
             }
         };
 
-        let mut nfo_files = Vec::new();
-        let mut media_source = MediaSource::default();
+        let mut listing = DirectoryListing {
+            dir: utilities::get_relative_path(Path::new(&curr_dir), root_path)
+                .unwrap_or_default()
+                .into_os_string(),
+            ..Default::default()
+        };
+        let mut has_sub_dir = false;
+        let mut only_images = true;
+        let mut images = Vec::new();
 
         for entry in entries.flatten() {
             let path = entry.path();
@@ -79,11 +442,29 @@ fn read_dir<R: tauri::Runtime>(
             }
 
             if file_type.is_dir() {
+                has_sub_dir = true;
+                // a disc structure is a single video played by its target file, its streams are
+                // not walked
+                if let Some(target) = get_disc_target(file_name) {
+                    only_images = false;
+                    if let Some(target_path) = find_disc_target(&path, target) {
+                        add_disc(&mut listing, &target_path, root_path);
+                    }
+                    continue;
+                }
                 queue.push_back(path.into_os_string());
                 continue;
             }
 
-            let relative_path = utilities::get_relative_path(path.as_path(), root_path);
+            let is_image = is_image_file(file_name);
+            if !is_image && !is_hidden_entry(file_name) {
+                only_images = false;
+            }
+
+            let Some(relative_path) = utilities::get_relative_path(path.as_path(), root_path)
+            else {
+                continue;
+            };
             let extension = path.extension();
             if extension.is_none() {
                 error!("File does not have proper extension. {:?}", &path);
@@ -93,49 +474,123 @@ fn read_dir<R: tauri::Runtime>(
                 error!("Failed to read file extension as UTF-8: {:?}", path);
                 continue;
             };
+            let media_source = &mut listing.media_source;
             match ext {
-                "nfo" => nfo_files.push(relative_path.unwrap().into_os_string()),
-                "jpg" | "png" if file_name.contains("poster") => {
-                    media_source.add_poster(relative_path.unwrap().into_os_string())
+                "nfo" => listing
+                    .nfo_files
+                    .push(relative_path.clone().into_os_string()),
+                "jpg" | "png" if get_art_type(file_name).is_some() => {
+                    media_source.add_poster(relative_path.clone().into_os_string())
                 }
                 "m4v" | "avi" | "mpg" | "mp4" | "mkv" | "f4v" | "wmv" | "rmvb" => {
-                    media_source.add_media(relative_path.unwrap().into_os_string())
+                    media_source.add_media(relative_path.clone().into_os_string())
                 }
                 "cbr" | "cbz" | "cbt" | "cb7" => {
-                    media_source.add_comic(relative_path.unwrap().into_os_string())
+                    media_source.add_comic(relative_path.clone().into_os_string())
                 }
-                _ => {}
+                // loose images only count once the directory turns out to be a comic
+                _ if is_image && image_comics => {
+                    match entry.metadata() {
+                        Ok(metadata) => {
+                            images.push(utilities::get_fingerprint(&relative_path, &metadata))
+                        }
+                        Err(e) => error!("Failed to read metadata for {:?}: {}", path, e),
+                    }
+                    continue;
+                }
+                _ => continue,
+            }
+
+            match entry.metadata() {
+                Ok(metadata) => listing
+                    .fingerprints
+                    .push(utilities::get_fingerprint(&relative_path, &metadata)),
+                Err(e) => error!("Failed to read metadata for {:?}: {}", path, e),
             }
         }
 
-        let media = handle_media_path(app_handle, &nfo_files, root_path, &media_source);
-        for m in media {
-            match m.media_type() {
-                MediaType::Movie | MediaType::TvShow | MediaType::Comic => {
-                    major_media.push(m);
-                }
-                MediaType::Episode => {
-                    secondary_media.push(m);
-                }
-                _ => {}
+        if image_comics
+            && !has_sub_dir
+            && only_images
+            && !images.is_empty()
+            && !listing.dir.is_empty()
+        {
+            listing.media_source.add_comic(listing.dir.clone());
+            listing.fingerprints.extend(images);
+        }
+
+        listings.push(listing);
+    }
+
+    Ok(listings)
+}
+
+/// Compare the files on disk against the fingerprints stored by the previous scan. A directory is
+/// dirty when any of its files was added, modified or removed, and only dirty directories are parsed.
+fn diff_fingerprints(
+    listings: &[DirectoryListing],
+    previous: &HashMap<String, FileFingerprint>,
+    scope: Option<&[PathBuf]>,
+) -> FingerprintDiff {
+    let mut diff = FingerprintDiff::default();
+    let mut seen = HashSet::new();
+
+    for listing in listings {
+        for fingerprint in &listing.fingerprints {
+            seen.insert(fingerprint.path());
+            let unchanged = previous
+                .get(fingerprint.path())
+                .is_some_and(|o| o.same_content(fingerprint));
+            if !unchanged {
+                diff.dirty_dirs
+                    .insert(listing.dir.to_string_lossy().into_owned());
+                diff.changed.push(fingerprint.clone());
             }
         }
     }
 
-    major_media.par_sort_by(|a, b| a.relative_path().cmp(b.relative_path()));
+    for path in previous.keys() {
+        if seen.contains(path.as_str()) {
+            continue;
+        }
+        // files outside of a scoped walk were not looked at, so they are not gone
+        if scope.is_some_and(|dirs| !dirs.iter().any(|o| Path::new(path).starts_with(o))) {
+            continue;
+        }
+        // the target of a disc structure belongs to the directory holding the disc
+        let dir = get_disc_parent(Path::new(path))
+            .or_else(|| Path::new(path).parent().map(|o| o.to_path_buf()))
+            .map(|o| o.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        diff.dirty_dirs.insert(dir);
+        diff.removed.push(path.clone());
+    }
 
-    (major_media, secondary_media)
+    diff
 }
 
+/// Parse the NFO files and comics of a directory. Returns the media with the files that failed to
+/// parse, which keep no fingerprint so the next scan tries them again.
 fn handle_media_path<R: tauri::Runtime>(
     app_handle: &tauri::AppHandle<R>,
+    name: &str,
     nfo_files: &[OsString],
     root_path: &Path,
     media_source: &MediaSource,
-) -> Vec<Media> {
+    changed_files: &HashSet<OsString>,
+    cover_format: CoverFormat,
+) -> (Vec<Media>, Vec<OsString>) {
     let app_dir = app_handle.path().app_data_dir().unwrap();
-    let comic_media = match parse_comics(app_handle, &app_dir, root_path, media_source.comic()) {
-        Ok(media) => media,
+    let (comic_media, mut failed) = match parse_comics(
+        app_handle,
+        &app_dir,
+        name,
+        root_path,
+        media_source.comic(),
+        changed_files,
+        cover_format,
+    ) {
+        Ok(result) => result,
         Err(e) => {
             let _ = app_handle
                 .notification()
@@ -143,27 +598,35 @@ fn handle_media_path<R: tauri::Runtime>(
                 .title("MediaDB: Encounter Error when parsing comic files.")
                 .body(e)
                 .show();
-            Vec::new()
+            (Vec::new(), media_source.comic().to_vec())
         }
     };
-    let nfo_error_count = AtomicUsize::new(0);
-    let results: Vec<Media> = nfo_files
+    let results: Vec<Result<Vec<Media>, &OsString>> = nfo_files
         .into_par_iter()
-        .filter_map(
+        .map(
             |nfo_file| match parse_nfo(root_path, nfo_file, media_source) {
-                Ok(media) => Some(media),
+                Ok(media) => Ok(media),
                 Err(e) => {
                     error!("Failed to parse NFO file: {}", e);
-                    nfo_error_count.fetch_add(1, Ordering::Relaxed);
-                    None
+                    Err(nfo_file)
                 }
             },
         )
-        .flatten()
-        .chain(comic_media)
         .collect();
 
-    let count = nfo_error_count.load(Ordering::Relaxed);
+    let mut media = Vec::new();
+    let mut count = 0;
+    for result in results {
+        match result {
+            Ok(m) => media.extend(m),
+            Err(nfo_file) => {
+                failed.push(nfo_file.clone());
+                count += 1;
+            }
+        }
+    }
+    media.extend(comic_media);
+
     if count > 0 {
         let _ = app_handle
             .notification()
@@ -176,7 +639,7 @@ fn handle_media_path<R: tauri::Runtime>(
             .show();
     }
 
-    results
+    (media, failed)
 }
 
 fn aggregate_data(
@@ -188,29 +651,47 @@ fn aggregate_data(
 
     // aggregate attributes
     for m in major_media {
-        if !m.posters().is_empty() {
-            match m.media_type() {
-                MediaType::Movie | MediaType::TvShow => posters.extend(
-                    m.posters()
-                        .iter()
-                        .map(|o| Path::new(m.relative_path()).join(o))
-                        .collect::<Vec<PathBuf>>(),
-                ),
-                _ => {}
-            }
+        match m.media_type() {
+            MediaType::Movie | MediaType::TvShow => posters.extend(
+                m.local_artwork()
+                    .map(|o| Path::new(m.relative_path()).join(o))
+                    .collect::<Vec<PathBuf>>(),
+            ),
+            _ => {}
         }
     }
 
-    // aggregate episode into seasons
+    // episode stills are converted like the artwork of their show
     for m in secondary_media {
+        posters.extend(
+            m.local_artwork()
+                .map(|o| Path::new(m.relative_path()).join(o)),
+        );
+    }
+
+    // aggregate episode into seasons, episodes from NFO files go first to take precedence
+    let (nfo_episodes, inferred_episodes): (Vec<&Media>, Vec<&Media>) =
+        secondary_media.iter().partition(|o| !o.inferred());
+    let show_dirs = major_media
+        .iter()
+        .filter(|o| matches!(o.media_type(), MediaType::TvShow))
+        .map(|o| Path::new(o.relative_path()))
+        .collect::<HashSet<&Path>>();
+    for m in nfo_episodes.into_iter().chain(inferred_episodes) {
+        // episodes sit in the show directory itself or in a season directory below it
         let key = Path::new(m.relative_path())
-            .parent()
+            .ancestors()
+            .find(|o| show_dirs.contains(o))
+            .or_else(|| Path::new(m.relative_path()).parent())
             .unwrap()
             .as_os_str()
             .to_os_string();
         let season_number = m.season().to_string();
         match seasons_map.get_mut(&key) {
             Some(season) => match season.get_mut(&season_number) {
+                // an inferred episode never replaces one described by an NFO file
+                Some(media) if m.inferred() && media.iter().any(|o| o.episode() == m.episode()) => {
+                }
                 Some(media) => media.push(m),
                 None => {
                     season.insert(season_number, vec![m]);
@@ -222,12 +703,17 @@ fn aggregate_data(
         }
     }
 
-    let data = major_media
+    let comics = major_media
+        .iter()
+        .filter(|o| matches!(o.media_type(), MediaType::Comic))
+        .collect::<Vec<&Media>>();
+
+    let mut data = major_media
         .into_par_iter()
         .map(|o| match o.media_type() {
             MediaType::Movie => o.movie(),
             MediaType::TvShow => o.tv_show(seasons_map.get(o.relative_path())),
-            MediaType::Comic => o.comic(),
+            MediaType::Comic => None,
             _ => {
                 error!("Unexpected media type: {:?}", o.media_type());
                 None
@@ -235,11 +721,62 @@ fn aggregate_data(
         })
         .flatten()
         .collect::<Vec<MediaItem>>();
+    data.extend(aggregate_comics(&comics));
 
     (data, posters)
 }
 
-fn handle_images(app_dir: &Path, name: &str, path: &str, posters: &HashSet<PathBuf>) {
+/// Look up the artwork of the movie sets, kept in a folder named after the set at the library
+/// root. Returns the posters to convert.
+fn find_collection_posters(root_path: &Path, media: &mut [Media]) -> HashSet<PathBuf> {
+    let mut posters: HashMap<String, PathBuf> = HashMap::new();
+    for m in media.iter() {
+        let Some(collection) = m.collection() else {
+            continue;
+        };
+        if posters.contains_key(collection.name()) {
+            continue;
+        }
+        let set_dir = PathBuf::from(get_legal_file_name(collection.name()));
+        if let Some(poster) = find_poster(root_path, &set_dir) {
+            posters.insert(collection.name().to_string(), poster);
+        }
+    }
+
+    for collection in media.iter_mut().filter_map(|o| o.collection_mut()) {
+        if let Some(poster) = posters.get(collection.name()) {
+            collection.set_poster(poster.to_string_lossy().into_owned());
+        }
+    }
+    posters.into_values().collect()
+}
+
+/// Poster image directly inside `dir`, relative to the library root.
+fn find_poster(root_path: &Path, dir: &Path) -> Option<PathBuf> {
+    let mut posters = fs::read_dir(root_path.join(dir))
+        .ok()?
+        .flatten()
+        .filter(|o| o.path().is_file())
+        .map(|o| o.file_name().to_string_lossy().into_owned())
+        .filter(|o| {
+            get_art_type(o) == Some("poster")
+                && Path::new(o)
+                    .extension()
+                    .is_some_and(|ext| ext == "jpg" || ext == "png")
+        })
+        .collect::<Vec<String>>();
+    posters.sort();
+    posters.first().map(|o| dir.join(o))
+}
+
+fn handle_images(
+    app_dir: &Path,
+    name: &str,
+    path: &str,
+    posters: &HashSet<PathBuf>,
+    changed_files: &HashSet<OsString>,
+    cover_format: CoverFormat,
+) {
     let root_path = Path::new(path);
     let cover_path = app_dir.join("covers");
 
@@ -261,11 +798,145 @@ fn handle_images(app_dir: &Path, name: &str, path: &str, posters: &HashSet<PathB
 
         let cover_dest_path = cover_folder_path.join(&file_path);
 
-        save_cover(&source_path, &cover_dest_path);
+        // unchanged posters keep the cover converted by a previous scan
+        if !changed_files.contains(poster_path.as_os_str()) && cover_dest_path.exists() {
+            return;
+        }
+
+        save_cover(&source_path, &cover_dest_path, cover_format);
     });
 }
 
-fn save_cover(source_path: &Path, dest_path: &Path) {
+/// Give the movies, episodes and shows without artwork a frame of their video as poster, or still.
+/// Shows take it from their first episode.
+fn add_video_frames(
+    app_dir: &Path,
+    name: &str,
+    root_path: &Path,
+    major_media: &mut [Media],
+    secondary_media: &mut [Media],
+    changed_files: &HashSet<OsString>,
+    cover_format: CoverFormat,
+) {
+    let cover_folder_path = app_dir.join("covers").join(name);
+    let frames = major_media
+        .par_iter()
+        .chain(secondary_media.par_iter())
+        .map(|o| get_video_frame(o, secondary_media))
+        .collect::<Vec<Option<VideoFrame>>>();
+    let posters = frames
+        .into_par_iter()
+        .map(|o| {
+            o.filter(|o| {
+                save_frame(
+                    &cover_folder_path,
+                    root_path,
+                    o,
+                    changed_files,
+                    cover_format,
+                )
+            })
+            .map(|o| o.poster)
+        })
+        .collect::<Vec<Option<String>>>();
+    for (media, poster) in major_media
+        .iter_mut()
+        .chain(secondary_media.iter_mut())
+        .zip(posters)
+    {
+        if let Some(poster) = poster {
+            media.set_posters(vec![poster]);
+        }
+    }
+}
+
+fn get_video_frame(media: &Media, episodes: &[Media]) -> Option<VideoFrame> {
+    if media.has_artwork() {
+        return None;
+    }
+    let relative_path = Path::new(media.relative_path());
+    let (video, poster) = match media.media_type() {
+        MediaType::Movie => (media, String::from("poster")),
+        // named like the still Kodi would look for next to the episode
+        MediaType::Episode if !media.file().is_empty() => {
+            let stem = Path::new(media.file()).file_stem()?.to_string_lossy();
+            (media, format!("{}-thumb", stem))
+        }
+        MediaType::TvShow => {
+            let first_episode = episodes
+                .iter()
+                .filter(|o| !o.file().is_empty())
+                .filter(|o| Path::new(o.relative_path()).starts_with(relative_path))
+                // specials come after the regular seasons
+                .min_by_key(|o| {
+                    let number = |v: &str| v.parse::<u32>().unwrap_or(u32::MAX);
+                    let season = number(o.season());
+                    (season == 0, season, number(o.episode()))
+                })?;
+            (first_episode, String::from("poster"))
+        }
+        _ => return None,
+    };
+    let video_path = video.video_path();
+    if get_disc_parent(&video_path).is_some() {
+        return None;
+    }
+    Some(VideoFrame {
+        video: video_path,
+        cover: relative_path.join(&poster),
+        poster,
+        duration: video.streams().duration(),
+    })
+}
+
+/// Grab the frame unless the cover of an unchanged video is already there. Returns whether the
+/// cover exists.
+fn save_frame(
+    cover_folder_path: &Path,
+    root_path: &Path,
+    frame: &VideoFrame,
+    changed_files: &HashSet<OsString>,
+    cover_format: CoverFormat,
+) -> bool {
+    let cover_dest_path = cover_folder_path.join(&frame.cover);
+    if !changed_files.contains(frame.video.as_os_str()) && cover_dest_path.exists() {
+        return true;
+    }
+    let parent_path = cover_dest_path.parent().unwrap();
+    if let Err(e) = fs::create_dir_all(parent_path) {
+        error!(
+            "Fail to create directory {:?}. Raising error {}",
+            parent_path, e
+        );
+        return false;
+    }
+
+    let source_path = root_path.join(&frame.video);
+    for offset in get_frame_offsets(frame.duration) {
+        match utilities::grab_frame(
+            &source_path.to_string_lossy(),
+            &cover_dest_path.to_string_lossy(),
+            offset,
+            cover_format,
+        ) {
+            Ok(()) => return true,
+            Err(e) => error!("{}", e),
+        }
+    }
+    false
+}
+
+/// Seconds to seek to before looking for a frame. Intros and opening credits are skipped by
+/// starting a tenth into the video, the start is the last resort for short or unknown lengths.
+fn get_frame_offsets(duration: u32) -> Vec<u32> {
+    match duration {
+        0 => vec![30, 0],
+        1..=9 => vec![0],
+        _ => vec![duration / 10, 0],
+    }
+}
+
+fn save_cover(source_path: &Path, dest_path: &Path, cover_format: CoverFormat) {
     let parent_path = dest_path.parent().unwrap();
     if let Err(e) = fs::create_dir_all(parent_path) {
         error!(
@@ -278,7 +949,630 @@ fn save_cover(source_path: &Path, dest_path: &Path) {
     let cover_src_path = source_path.to_string_lossy();
     let cover_output_path = dest_path.to_string_lossy();
 
-    if let Err(e) = convert_image(&cover_src_path, &cover_output_path) {
+    if let Err(e) = convert_image(&cover_src_path, &cover_output_path, cover_format) {
         error!("{}", e);
     }
 }
+
+fn remove_covers(app_dir: &Path, name: &str, root_path: &Path, removed_files: &[String]) {
+    let cover_path = app_dir.join("covers");
+    for file in removed_files {
+        let mut cover_dest_paths = Vec::new();
+        match Path::new(file).extension().and_then(|o| o.to_str()) {
+            Some("jpg" | "png") => {
+                cover_dest_paths.push(cover_path.join(name).join(strip_image_extensions(file)))
+            }
+            Some("cbr" | "cbz" | "cbt" | "cb7") => {
+                cover_dest_paths.push(cover_path.join(name).join(strip_comic_extensions(file)))
+            }
+            // frames grabbed for an episode without still, or a loose movie without poster
+            Some("m4v" | "avi" | "mpg" | "mp4" | "mkv" | "f4v" | "wmv" | "rmvb") => {
+                let path = Path::new(file);
+                if let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) {
+                    let thumb = format!("{}-thumb", stem.to_string_lossy());
+                    cover_dest_paths.push(cover_path.join(name).join(dir).join(thumb));
+                }
+                cover_dest_paths.push(cover_path.join(name).join(file).join("poster"));
+            }
+            _ => {}
+        }
+        // an image folder comic is gone together with its directory
+        if let Some(dir) = Path::new(file)
+            .parent()
+            .filter(|o| !o.as_os_str().is_empty())
+        {
+            if is_image_file(file) && !root_path.join(dir).exists() {
+                cover_dest_paths.push(cover_path.join(name).join(dir));
+            }
+        }
+
+        for cover_dest_path in cover_dest_paths.into_iter().filter(|o| o.is_file()) {
+            if let Err(e) = fs::remove_file(&cover_dest_path) {
+                error!(
+                    "Fail to remove cover {:?}. Raising error {}",
+                    cover_dest_path, e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::parser::MovieSet;
+
+    fn fingerprint(path: &str, size: i64, mtime: i64) -> FileFingerprint {
+        FileFingerprint::new(path.to_string(), size, mtime)
+    }
+
+    fn listing(dir: &str, fingerprints: Vec<FileFingerprint>) -> DirectoryListing {
+        DirectoryListing {
+            dir: OsString::from(dir),
+            fingerprints,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_dir_collects_fingerprints_for_known_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie_dir = dir.path().join("Heat");
+        fs::create_dir_all(&movie_dir).unwrap();
+        fs::write(movie_dir.join("movie.nfo"), "<movie/>").unwrap();
+        fs::write(movie_dir.join("poster.jpg"), "image").unwrap();
+        fs::write(movie_dir.join("Heat.mkv"), "video").unwrap();
+        fs::write(movie_dir.join("notes.txt"), "ignored").unwrap();
+
+        let listings =
+            read_dir(&dir.path().to_string_lossy(), None, &HashSet::new(), false).unwrap();
+        let heat = listings.iter().find(|o| o.dir == "Heat").unwrap();
+
+        let mut paths: Vec<&str> = heat.fingerprints.iter().map(|o| o.path()).collect();
+        paths.sort();
+        let expected: Vec<String> = ["Heat.mkv", "movie.nfo", "poster.jpg"]
+            .iter()
+            .map(|o| Path::new("Heat").join(o).to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(paths, expected);
+        assert_eq!(heat.nfo_files.len(), 1);
+    }
+
+    #[test]
+    fn read_dir_lists_disc_structures_as_videos() {
+        let dir = tempfile::tempdir().unwrap();
+        let stream = dir.path().join("Heat").join("BDMV").join("STREAM");
+        fs::create_dir_all(&stream).unwrap();
+        fs::write(stream.join("00001.m2ts"), "video").unwrap();
+        fs::write(stream.parent().unwrap().join("index.bdmv"), "index").unwrap();
+        fs::write(dir.path().join("Heat").join("movie.nfo"), "<movie/>").unwrap();
+
+        let listings =
+            read_dir(&dir.path().to_string_lossy(), None, &HashSet::new(), false).unwrap();
+        let dirs: Vec<&OsString> = listings.iter().map(|o| &o.dir).collect();
+        assert_eq!(dirs, vec!["", "Heat"]);
+
+        let target = Path::new("Heat").join("BDMV").join("index.bdmv");
+        let heat = &listings[1];
+        assert_eq!(
+            heat.media_source.media(),
+            &[target.clone().into_os_string()]
+        );
+        assert!(heat
+            .fingerprints
+            .iter()
+            .any(|o| Path::new(o.path()) == target));
+
+        // a vanished disc marks the directory holding it
+        let previous = HashMap::from([(
+            target.to_string_lossy().into_owned(),
+            fingerprint(&target.to_string_lossy(), 5, 1),
+        )]);
+        let diff = diff_fingerprints(&[], &previous, None);
+        assert_eq!(diff.dirty_dirs, HashSet::from(["Heat".to_string()]));
+    }
+
+    #[test]
+    fn read_dir_fails_for_missing_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+
+        assert!(read_dir(&missing.to_string_lossy(), None, &HashSet::new(), false).is_err());
+    }
+
+    #[test]
+    fn diff_fingerprints_marks_everything_dirty_without_history() {
+        let listings = vec![listing("Heat", vec![fingerprint("Heat/movie.nfo", 1, 1)])];
+
+        let diff = diff_fingerprints(&listings, &HashMap::new(), None);
+        assert_eq!(diff.changed.len(), 1);
+        assert!(diff.dirty_dirs.contains("Heat"));
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn diff_fingerprints_only_marks_changed_directories() {
+        let listings = vec![
+            listing("Heat", vec![fingerprint("Heat/movie.nfo", 1, 1)]),
+            listing("Ronin", vec![fingerprint("Ronin/movie.nfo", 2, 5)]),
+        ];
+        let previous = HashMap::from([
+            (
+                "Heat/movie.nfo".to_string(),
+                fingerprint("Heat/movie.nfo", 1, 1),
+            ),
+            (
+                "Ronin/movie.nfo".to_string(),
+                fingerprint("Ronin/movie.nfo", 2, 1),
+            ),
+        ]);
+
+        let diff = diff_fingerprints(&listings, &previous, None);
+        assert_eq!(diff.changed, vec![fingerprint("Ronin/movie.nfo", 2, 5)]);
+        assert_eq!(diff.dirty_dirs, HashSet::from(["Ronin".to_string()]));
+    }
+
+    #[test]
+    fn diff_fingerprints_reports_removed_files() {
+        let listings = vec![listing("Heat", vec![fingerprint("Heat/movie.nfo", 1, 1)])];
+        let previous = HashMap::from([
+            (
+                "Heat/movie.nfo".to_string(),
+                fingerprint("Heat/movie.nfo", 1, 1),
+            ),
+            (
+                "Ronin/movie.nfo".to_string(),
+                fingerprint("Ronin/movie.nfo", 2, 1),
+            ),
+        ]);
+
+        let diff = diff_fingerprints(&listings, &previous, None);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.removed, vec!["Ronin/movie.nfo".to_string()]);
+        assert_eq!(diff.dirty_dirs, HashSet::from(["Ronin".to_string()]));
+    }
+
+    #[test]
+    fn diff_fingerprints_ignores_files_outside_scope() {
+        let listings = vec![listing("Heat", vec![fingerprint("Heat/movie.nfo", 1, 1)])];
+        let previous = HashMap::from([
+            (
+                "Heat/movie.nfo".to_string(),
+                fingerprint("Heat/movie.nfo", 1, 1),
+            ),
+            (
+                "Ronin/movie.nfo".to_string(),
+                fingerprint("Ronin/movie.nfo", 2, 1),
+            ),
+        ]);
+        let scope = [PathBuf::from("Heat")];
+
+        let diff = diff_fingerprints(&listings, &previous, Some(&scope));
+        assert!(diff.removed.is_empty());
+        assert!(diff.dirty_dirs.is_empty());
+    }
+
+    #[test]
+    fn read_dir_walks_only_scoped_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Heat", "Ronin"] {
+            fs::create_dir_all(dir.path().join(name)).unwrap();
+            fs::write(dir.path().join(name).join("movie.nfo"), "<movie/>").unwrap();
+        }
+        let scope = [PathBuf::from("Ronin")];
+
+        let listings = read_dir(
+            &dir.path().to_string_lossy(),
+            Some(&scope),
+            &HashSet::new(),
+            false,
+        )
+        .unwrap();
+        let dirs: Vec<&OsString> = listings.iter().map(|o| &o.dir).collect();
+        assert_eq!(dirs, vec!["Ronin"]);
+    }
+
+    #[test]
+    fn is_skipped_checks_every_component() {
+        let skip_paths = HashSet::from(["_Todo".to_string()]);
+
+        assert!(is_skipped(Path::new("_Todo/Heat"), &skip_paths));
+        assert!(is_skipped(Path::new("Heat/.trash"), &skip_paths));
+        assert!(!is_skipped(Path::new("Heat/Extras"), &skip_paths));
+    }
+
+    #[test]
+    fn read_dir_lists_image_folders_as_comics() {
+        let dir = tempfile::tempdir().unwrap();
+        let chapter = dir.path().join("Manga").join("Chapter 1");
+        fs::create_dir_all(&chapter).unwrap();
+        fs::write(chapter.join("001.jpg"), "image").unwrap();
+        fs::write(chapter.join("002.webp"), "image").unwrap();
+        fs::write(chapter.join(".DS_Store"), "").unwrap();
+        fs::write(dir.path().join("Manga").join("cover.jpg"), "image").unwrap();
+        let mixed = dir.path().join("Scans");
+        fs::create_dir_all(&mixed).unwrap();
+        fs::write(mixed.join("001.jpg"), "image").unwrap();
+        fs::write(mixed.join("notes.txt"), "text").unwrap();
+
+        let root = dir.path().to_string_lossy();
+        let listings = read_dir(&root, None, &HashSet::new(), true).unwrap();
+        let comic_dirs: Vec<&OsString> = listings
+            .iter()
+            .flat_map(|o| o.media_source.comic())
+            .collect();
+        let chapter_dir = Path::new("Manga").join("Chapter 1").into_os_string();
+        assert_eq!(comic_dirs, vec![&chapter_dir]);
+        let chapter_listing = listings.iter().find(|o| o.dir == chapter_dir).unwrap();
+        assert_eq!(chapter_listing.fingerprints.len(), 2);
+
+        // without the opt-in loose images are not tracked at all
+        let listings = read_dir(&root, None, &HashSet::new(), false).unwrap();
+        assert!(listings.iter().all(|o| o.media_source.comic().is_empty()));
+        assert!(listings.iter().all(|o| o.fingerprints.is_empty()));
+    }
+
+    #[test]
+    fn remove_covers_removes_image_folder_comic_covers() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("library");
+        fs::create_dir_all(root.join("Kept")).unwrap();
+        let covers = dir.path().join("covers").join("Manga");
+        fs::create_dir_all(&covers).unwrap();
+        fs::write(covers.join("Gone"), "cover").unwrap();
+        fs::write(covers.join("Kept"), "cover").unwrap();
+        fs::write(covers.join("Saga"), "cover").unwrap();
+
+        let removed = [
+            Path::new("Gone").join("001.jpg"),
+            Path::new("Kept").join("002.jpg"),
+            PathBuf::from("Saga.cbz"),
+        ]
+        .iter()
+        .map(|o| o.to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+        remove_covers(dir.path(), "Manga", &root, &removed);
+
+        assert!(!covers.join("Gone").exists());
+        assert!(covers.join("Kept").exists());
+        assert!(!covers.join("Saga").exists());
+    }
+
+    #[test]
+    fn get_parent_dirs_collapses_siblings() {
+        let dirs = [
+            Path::new("Manga").join("Chapter 1"),
+            Path::new("Manga").join("Chapter 2"),
+            PathBuf::from("Heat"),
+            Path::new("Manga").join("Chapter 3").join("extras"),
+        ];
+        assert_eq!(
+            get_parent_dirs(&dirs),
+            vec![PathBuf::from("Heat"), PathBuf::from("Manga")]
+        );
+    }
+
+    #[test]
+    fn mark_image_comic_series_marks_siblings_and_parent() {
+        let image_comic = |dir: &Path| {
+            let mut listing = listing(&dir.to_string_lossy(), vec![]);
+            listing
+                .media_source
+                .add_comic(dir.as_os_str().to_os_string());
+            listing
+        };
+        let chapter_1 = Path::new("Manga").join("Chapter 1");
+        let chapter_2 = Path::new("Manga").join("Chapter 2");
+        let other = Path::new("Other").join("Chapter 1");
+        let listings = vec![
+            listing("Manga", vec![]),
+            image_comic(&chapter_1),
+            image_comic(&chapter_2),
+            image_comic(&other),
+        ];
+
+        // a removed chapter no longer has a listing of its own
+        let removed = Path::new("Manga").join("Chapter 3");
+        let mut dirty_dirs = HashSet::from([removed.to_string_lossy().into_owned()]);
+        mark_image_comic_series(&listings, &mut dirty_dirs);
+
+        let expected = [removed, chapter_1, chapter_2, PathBuf::from("Manga")]
+            .iter()
+            .map(|o| o.to_string_lossy().into_owned())
+            .collect::<HashSet<String>>();
+        assert_eq!(dirty_dirs, expected);
+    }
+
+    #[test]
+    fn find_show_dir_checks_ancestors_for_tvshow_nfo() {
+        let dir = tempfile::tempdir().unwrap();
+        let season = Path::new("Show").join("Season 01");
+        fs::create_dir_all(dir.path().join(&season)).unwrap();
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+        fs::write(dir.path().join("Show").join("tvshow.nfo"), "<tvshow/>").unwrap();
+
+        let show = Some(PathBuf::from("Show"));
+        assert_eq!(find_show_dir(dir.path(), Path::new("Show")), show);
+        assert_eq!(find_show_dir(dir.path(), &season), show);
+        assert_eq!(find_show_dir(dir.path(), Path::new("Heat")), None);
+        assert_eq!(find_show_dir(dir.path(), Path::new("")), None);
+    }
+
+    #[test]
+    fn aggregate_data_prefers_nfo_episodes() {
+        let season = Path::new("Show").join("Season 01").into_os_string();
+        let mut show = Media::default();
+        show.set_media_type(MediaType::TvShow);
+        show.set_relative_path(OsString::from("Show"));
+        let episode = |title: &str, number: &str, inferred: bool| {
+            let mut media = Media::default();
+            media.set_media_type(MediaType::Episode);
+            media.set_relative_path(season.clone());
+            media.set_title(title.to_string());
+            media.set_season("1".to_string());
+            media.set_episode(number.to_string());
+            media.set_inferred(inferred);
+            media
+        };
+        let episodes = [
+            episode("Episode 1", "1", true),
+            episode("Pilot", "1", false),
+            episode("Episode 2", "2", true),
+        ];
+
+        let (data, _) = aggregate_data(&[show], &episodes);
+        let seasons: serde_json::Value = serde_json::from_str(data[0].seasons()).unwrap();
+        let titles = seasons["01"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o["title"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(titles, vec!["Pilot", "Episode 2"]);
+    }
+
+    #[test]
+    fn aggregate_data_groups_flat_and_season_dir_episodes() {
+        let mut show = Media::default();
+        show.set_media_type(MediaType::TvShow);
+        show.set_relative_path(OsString::from("Show"));
+        let episode = |dir: &Path, season: &str, number: &str| {
+            let mut media = Media::default();
+            media.set_media_type(MediaType::Episode);
+            media.set_relative_path(dir.as_os_str().to_os_string());
+            media.set_season(season.to_string());
+            media.set_episode(number.to_string());
+            media
+        };
+        let episodes = [
+            episode(Path::new("Show"), "1", "1"),
+            episode(&Path::new("Show").join("Season 02"), "2", "1"),
+            episode(
+                &Path::new("Show").join("Season 02").join("Disc 1"),
+                "2",
+                "2",
+            ),
+        ];
+
+        let (data, _) = aggregate_data(&[show], &episodes);
+        let seasons: serde_json::Value = serde_json::from_str(data[0].seasons()).unwrap();
+        assert_eq!(seasons["01"].as_array().unwrap().len(), 1);
+        assert_eq!(seasons["02"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn drop_failed_fingerprints_keeps_parsed_files() {
+        let fingerprint = |path: &Path| FileFingerprint::new(path.to_string_lossy().into(), 1, 1);
+        let mut fingerprints = vec![
+            fingerprint(&Path::new("Heat").join("movie.nfo")),
+            fingerprint(&Path::new("Heat").join("Heat.mkv")),
+            fingerprint(&Path::new("Ronin").join("movie.nfo")),
+            fingerprint(&Path::new("Saga").join("01").join("001.jpg")),
+            fingerprint(&Path::new("Saga").join("01").join("002.jpg")),
+            fingerprint(&Path::new("Saga").join("02.cbz")),
+        ];
+        let failed_files = [
+            Path::new("Heat").join("movie.nfo").into_os_string(),
+            Path::new("Saga").join("01").into_os_string(),
+        ];
+
+        drop_failed_fingerprints(&mut fingerprints, &failed_files);
+        let result = fingerprints
+            .iter()
+            .map(|o| PathBuf::from(o.path()))
+            .collect::<Vec<PathBuf>>();
+        assert_eq!(
+            result,
+            vec![
+                Path::new("Heat").join("Heat.mkv"),
+                Path::new("Ronin").join("movie.nfo"),
+                Path::new("Saga").join("02.cbz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn mark_show_dirs_marks_the_whole_show() {
+        let dir = tempfile::tempdir().unwrap();
+        let season_1 = Path::new("Show").join("Season 01");
+        let season_2 = Path::new("Show").join("Season 02");
+        fs::create_dir_all(dir.path().join(&season_1)).unwrap();
+        fs::write(dir.path().join("Show").join("tvshow.nfo"), "<tvshow/>").unwrap();
+        let listings = vec![
+            listing("Show", vec![]),
+            listing(&season_1.to_string_lossy(), vec![]),
+            listing(&season_2.to_string_lossy(), vec![]),
+            listing("Heat", vec![]),
+        ];
+
+        let mut dirty_dirs = HashSet::from([season_1.to_string_lossy().into_owned()]);
+        mark_show_dirs(dir.path(), &listings, &mut dirty_dirs);
+
+        let expected = [PathBuf::from("Show"), season_1, season_2]
+            .iter()
+            .map(|o| o.to_string_lossy().into_owned())
+            .collect::<HashSet<String>>();
+        assert_eq!(dirty_dirs, expected);
+    }
+
+    #[test]
+    fn get_video_frame_covers_media_without_artwork() {
+        let media = |media_type: MediaType, path: &Path, file: &str| {
+            let mut media = Media::default();
+            media.set_media_type(media_type);
+            media.set_relative_path(path.as_os_str().to_os_string());
+            media.set_file(file.to_string());
+            media
+        };
+        let season = Path::new("Show").join("Season 1");
+        let episode = |dir: &Path, file: &str, season: &str, episode: &str| {
+            let mut media = media(MediaType::Episode, dir, file);
+            media.set_season(season.to_string());
+            media.set_episode(episode.to_string());
+            media
+        };
+        let mut pilot = episode(&season, "S01E01.mkv", "1", "1");
+        pilot.streams_mut().set_duration(1320);
+        let episodes = [
+            episode(&season, "S01E02.mkv", "1", "2"),
+            episode(&Path::new("Show").join("Specials"), "S00E01.mkv", "0", "1"),
+            pilot,
+        ];
+
+        assert_eq!(
+            get_video_frame(&episodes[0], &episodes),
+            Some(VideoFrame {
+                video: season.join("S01E02.mkv"),
+                cover: season.join("S01E02-thumb"),
+                poster: "S01E02-thumb".to_string(),
+                duration: 0,
+            })
+        );
+        // shows take the frame of their first episode, specials come last
+        let show = media(MediaType::TvShow, Path::new("Show"), "");
+        assert_eq!(
+            get_video_frame(&show, &episodes),
+            Some(VideoFrame {
+                video: season.join("S01E01.mkv"),
+                cover: Path::new("Show").join("poster"),
+                poster: "poster".to_string(),
+                duration: 1320,
+            })
+        );
+
+        // a loose movie is keyed by its video
+        let loose = media(MediaType::Movie, Path::new("Heat.mkv"), "");
+        assert_eq!(
+            get_video_frame(&loose, &[]).map(|o| (o.video, o.cover)),
+            Some((
+                PathBuf::from("Heat.mkv"),
+                Path::new("Heat.mkv").join("poster")
+            ))
+        );
+
+        let mut movie = media(MediaType::Movie, Path::new("Ronin"), "Ronin.mkv");
+        movie.set_posters(vec!["Ronin-poster.jpg".to_string()]);
+        assert_eq!(get_video_frame(&movie, &[]), None);
+        movie.set_posters(vec!["fanart.jpg".to_string()]);
+        assert!(get_video_frame(&movie, &[]).is_some());
+        let disc = media(
+            MediaType::Movie,
+            Path::new("Alien"),
+            &Path::new("BDMV").join("index.bdmv").to_string_lossy(),
+        );
+        assert_eq!(get_video_frame(&disc, &[]), None);
+    }
+
+    #[test]
+    fn get_frame_offsets_skip_the_intro() {
+        assert_eq!(get_frame_offsets(7200), vec![720, 0]);
+        assert_eq!(get_frame_offsets(5), vec![0]);
+        assert_eq!(get_frame_offsets(0), vec![30, 0]);
+    }
+
+    #[test]
+    fn find_collection_posters_only_checks_set_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let set_dir = dir.path().join("Mission_ Impossible Collection");
+        let franchise_dir = dir.path().join("Alien").join("Alien (1979)");
+        fs::create_dir_all(&set_dir).unwrap();
+        fs::create_dir_all(&franchise_dir).unwrap();
+        fs::write(set_dir.join("poster.jpg"), "image").unwrap();
+        fs::write(dir.path().join("Alien").join("poster.png"), "image").unwrap();
+
+        let movie = |path: &Path, set: Option<&str>| {
+            let mut media = Media::default();
+            media.set_media_type(MediaType::Movie);
+            media.set_relative_path(path.as_os_str().to_os_string());
+            if let Some(name) = set {
+                media.set_collection(MovieSet::new(name.to_string(), String::new()));
+            }
+            media
+        };
+        let mut media = [
+            movie(
+                Path::new("Mission Impossible (1996)"),
+                Some("Mission: Impossible Collection"),
+            ),
+            movie(
+                &Path::new("Alien").join("Alien (1979)"),
+                Some("Alien Collection"),
+            ),
+            movie(Path::new("Heat (1995)"), Some("Heat Collection")),
+            movie(Path::new("Ronin (1998)"), None),
+        ];
+
+        let posters = find_collection_posters(dir.path(), &mut media);
+        // the folder grouping the movies may hold the poster of something else
+        let expected = [Path::new("Mission_ Impossible Collection").join("poster.jpg")];
+        assert_eq!(posters, HashSet::from(expected.clone()));
+        let result = media
+            .iter()
+            .filter_map(|o| o.collection())
+            .map(|o| o.poster().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            result,
+            vec![
+                expected[0].to_string_lossy().into_owned(),
+                String::new(),
+                String::new()
+            ]
+        );
+    }
+
+    #[test]
+    fn mark_collection_dirs_marks_movies_of_changed_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie = |name: &str, nfo: &str| {
+            fs::create_dir_all(dir.path().join(name)).unwrap();
+            fs::write(dir.path().join(name).join("movie.nfo"), nfo).unwrap();
+        };
+        movie(
+            "Alien",
+            "<movie><set><name>Alien: Collection</name></set></movie>",
+        );
+        movie("Aliens", "<movie><set>Alien: Collection</set></movie>");
+        movie("Heat", "<movie><title>Heat</title></movie>");
+        fs::create_dir_all(dir.path().join("Alien_ Collection")).unwrap();
+        fs::write(
+            dir.path().join("Alien_ Collection").join("poster.jpg"),
+            "image",
+        )
+        .unwrap();
+
+        let path = dir.path().to_string_lossy();
+        let listings = read_dir(&path, None, &HashSet::new(), false).unwrap();
+        let set_dirs = listings
+            .iter()
+            .filter(|o| is_set_listing(o))
+            .map(|o| o.dir.clone())
+            .collect::<Vec<OsString>>();
+        assert_eq!(set_dirs, vec![OsString::from("Alien_ Collection")]);
+
+        let mut dirty_dirs = HashSet::from(["Alien_ Collection".to_string()]);
+        mark_collection_dirs(dir.path(), &listings, &mut dirty_dirs);
+        let expected = ["Alien_ Collection", "Alien", "Aliens"].map(String::from);
+        assert_eq!(dirty_dirs, HashSet::from(expected));
+    }
+}
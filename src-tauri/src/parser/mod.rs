@@ -1,4 +1,8 @@
-mod comic_parser;
+mod archive;
+pub mod comic_parser;
 pub mod main;
+mod name_parser;
 mod nfo_parser;
+mod probe;
+pub mod trickplay;
 mod utilities;
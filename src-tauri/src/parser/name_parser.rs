@@ -0,0 +1,1077 @@
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::helper::main::{get_disc_parent, natural_cmp};
+use crate::model::parser::{Media, MediaSource, MediaType, MovieVersion};
+use crate::parser::nfo_parser::get_poster_filename;
+
+// tokens that end the title part of a release name
+const RELEASE_TOKENS: &[&str] = &[
+    "480p", "576p", "720p", "1080p", "1080i", "2160p", "4k", "uhd", "bluray", "blu-ray", "bdrip",
+    "brrip", "dvdrip", "dvd", "webrip", "web-dl", "webdl", "web", "hdtv", "hdrip", "remux", "x264",
+    "x265", "h264", "h265", "hevc", "avc", "xvid", "divx", "10bit", "hdr", "dts", "ac3", "aac",
+    "proper", "repack",
+];
+
+// edition markers, longest first so "extended cut" wins over "extended"
+const EDITIONS: &[&str] = &[
+    "director's cut",
+    "directors cut",
+    "extended edition",
+    "extended cut",
+    "special edition",
+    "ultimate edition",
+    "collector's edition",
+    "anniversary edition",
+    "theatrical cut",
+    "final cut",
+    "extended",
+    "unrated",
+    "uncut",
+    "theatrical",
+    "remastered",
+    "criterion",
+    "imax",
+];
+
+// directories and file suffixes holding bonus material rather than the movie itself
+const EXTRA_DIRS: &[&str] = &[
+    "extras",
+    "featurettes",
+    "trailers",
+    "samples",
+    "sample",
+    "behind the scenes",
+    "deleted scenes",
+    "interviews",
+    "scenes",
+    "shorts",
+    "other",
+];
+const EXTRA_SUFFIXES: &[&str] = &[
+    "-trailer",
+    "-sample",
+    "-featurette",
+    "-behindthescenes",
+    "-deleted",
+    "-interview",
+    "-scene",
+    "-short",
+    "-other",
+];
+
+// markers of a movie split over several files, Kodi stacks the same ones
+const STACK_MARKERS: &[&str] = &["cd", "dvd", "part", "pt", "disc", "disk"];
+
+/// Title, year and edition read from a file or folder name.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ReleaseName {
+    pub title: String,
+    pub year: String,
+    pub edition: String,
+}
+
+/// Read a `Title (Year)` or dotted release style name such as `Heat.1995.1080p.BluRay.x264`.
+pub(crate) fn parse_release_name(name: &str) -> ReleaseName {
+    let mut name = name.trim().to_string();
+    let mut edition = String::new();
+
+    // Plex and Jellyfin mark editions as {edition-Director's Cut}
+    if let Some(start) = name.find("{edition-") {
+        if let Some(end) = name[start..].find('}') {
+            edition = name[start + "{edition-".len()..start + end]
+                .trim()
+                .to_string();
+            name.replace_range(start..start + end + 1, " ");
+        }
+    }
+
+    // release names use dots or underscores instead of spaces
+    if !name.contains(' ') {
+        name = name.replace(['.', '_'], " ");
+    }
+    // bracketed parts are dropped from the title, but often hold the edition, e.g. [Director's Cut]
+    if edition.is_empty() {
+        edition = find_edition(&bracket_contents(&name, '[', ']')).unwrap_or_default();
+    }
+    let name = strip_brackets(&name, '[', ']');
+
+    let tokens = name
+        .split(' ')
+        .filter(|o| !o.is_empty())
+        .collect::<Vec<&str>>();
+
+    let mut title_end = tokens.len();
+    let mut year = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let lowercase = token.to_lowercase();
+        let value = token.trim_matches(|c| c == '(' || c == ')');
+        // the first token is always title, "1917 (2019)" is a movie named 1917
+        if i > 0 && is_year(value) {
+            year = value.to_string();
+            title_end = i;
+            // a later year belongs to the title, e.g. "2001 A Space Odyssey 1968"
+            if tokens[i + 1..]
+                .iter()
+                .any(|o| is_year(o.trim_matches(|c| c == '(' || c == ')')))
+            {
+                continue;
+            }
+            break;
+        }
+        if RELEASE_TOKENS.contains(&lowercase.as_str()) {
+            title_end = i;
+            break;
+        }
+    }
+
+    if edition.is_empty() {
+        edition = find_edition(&tokens[title_end..].join(" ")).unwrap_or_default();
+    }
+
+    let title = tokens[..title_end]
+        .join(" ")
+        .trim_end_matches(['-', ' '])
+        .to_string();
+    ReleaseName {
+        title,
+        year,
+        edition,
+    }
+}
+
+fn is_year(value: &str) -> bool {
+    value.len() == 4
+        && value.chars().all(|c| c.is_ascii_digit())
+        && (value.starts_with("19") || value.starts_with("20"))
+}
+
+fn find_edition(value: &str) -> Option<String> {
+    let value = value.to_lowercase();
+    EDITIONS
+        .iter()
+        .find(|o| value.contains(*o))
+        .map(|o| title_case(o))
+}
+
+fn bracket_contents(value: &str, open: char, close: char) -> String {
+    let mut result = String::new();
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            c if c == open => {
+                depth += 1;
+                result.push(' ');
+            }
+            c if c == close && depth > 0 => depth -= 1,
+            c if depth > 0 => result.push(c),
+            _ => {}
+        }
+    }
+    result
+}
+
+fn strip_brackets(value: &str, open: char, close: char) -> String {
+    let mut result = String::new();
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            c if c == open => depth += 1,
+            c if c == close && depth > 0 => depth -= 1,
+            c if depth == 0 => result.push(c),
+            _ => {}
+        }
+    }
+    result
+}
+
+fn title_case(value: &str) -> String {
+    value
+        .split(' ')
+        .map(|o| {
+            let mut chars = o.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Season, episode and title read from the file name of an episode.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct EpisodeName {
+    pub season: String,
+    pub episodes: Vec<String>, // several for multi-episode files like S02E05E06
+    pub title: String,
+}
+
+// byte range of an episode marker in a name with the season and episode numbers it holds
+struct EpisodeMarker {
+    start: usize,
+    end: usize,
+    season: String,
+    episodes: Vec<String>,
+}
+
+/// Whether the name carries an episode marker like `S01E02` or `1x02`.
+pub(crate) fn is_episode_name(name: &str) -> bool {
+    find_episode_marker(name).is_some()
+}
+
+/// Whether the name carries a marker for the episode, also among the episodes of a multi-episode
+/// file. Numbers are compared without their zero padding.
+pub(crate) fn matches_episode(name: &str, season: &str, episode: &str) -> bool {
+    find_episode_marker(name).is_some_and(|o| {
+        o.season == trim_number(season) && o.episodes.contains(&trim_number(episode))
+    })
+}
+
+fn find_episode_marker(name: &str) -> Option<EpisodeMarker> {
+    let bytes = name.to_ascii_lowercase().into_bytes();
+    let digits_from = |i: usize| {
+        bytes[i.min(bytes.len())..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count()
+    };
+    let is_boundary = |i: usize| i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+
+    for i in 0..bytes.len() {
+        // S01E02
+        if bytes[i] == b's' && is_boundary(i) {
+            let season = digits_from(i + 1);
+            let e = i + 1 + season;
+            if (1..=2).contains(&season) && bytes.get(e) == Some(&b'e') {
+                let episode = digits_from(e + 1);
+                if (1..=3).contains(&episode) {
+                    let mut end = e + 1 + episode;
+                    let mut episodes = vec![trim_number(&name[e + 1..end])];
+                    // further episodes as S02E05E06, S02E05-E06 or S02E05-06
+                    loop {
+                        let next = match bytes.get(end..end + 2) {
+                            Some([b'e', _]) => end + 1,
+                            Some([b'-', b'e']) => end + 2,
+                            Some([b'-', _]) => end + 1,
+                            _ => break,
+                        };
+                        let digits = digits_from(next);
+                        let after = bytes.get(next + digits);
+                        if !(1..=3).contains(&digits)
+                            || after.is_some_and(u8::is_ascii_alphanumeric)
+                        {
+                            break;
+                        }
+                        end = next + digits;
+                        episodes.push(trim_number(&name[next..end]));
+                    }
+                    return Some(EpisodeMarker {
+                        start: i,
+                        end,
+                        season: trim_number(&name[i + 1..e]),
+                        episodes,
+                    });
+                }
+            }
+        }
+        // 1x02, but not a resolution like 1920x1080
+        if bytes[i] == b'x' {
+            let season = bytes[..i]
+                .iter()
+                .rev()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            let episode = digits_from(i + 1);
+            if (1..=2).contains(&season) && (2..=3).contains(&episode) && is_boundary(i - season) {
+                return Some(EpisodeMarker {
+                    start: i - season,
+                    end: i + 1 + episode,
+                    season: trim_number(&name[i - season..i]),
+                    episodes: vec![trim_number(&name[i + 1..i + 1 + episode])],
+                });
+            }
+        }
+    }
+    None
+}
+
+// daily shows are named by air date, 2024-03-01 or 2024.03.01
+fn find_air_date(name: &str) -> Option<EpisodeMarker> {
+    let bytes = name.as_bytes();
+    for i in 0..bytes.len().saturating_sub(9) {
+        let candidate = &bytes[i..i + 10];
+        let separator = candidate[4];
+        if !matches!(separator, b'-' | b'.' | b'_' | b' ') || candidate[7] != separator {
+            continue;
+        }
+        // only digits are sliced, so the name is never cut inside a multi-byte character
+        let is_digits =
+            |range: std::ops::Range<usize>| candidate[range].iter().all(u8::is_ascii_digit);
+        if !is_digits(0..4) || !is_digits(5..7) || !is_digits(8..10) {
+            continue;
+        }
+        let year = &name[i..i + 4];
+        let month = &name[i + 5..i + 7];
+        let day = &name[i + 8..i + 10];
+        if !is_year(year) {
+            continue;
+        }
+        let (Ok(m), Ok(d)) = (month.parse::<u8>(), day.parse::<u8>()) else {
+            continue;
+        };
+        if (1..=12).contains(&m) && (1..=31).contains(&d) {
+            return Some(EpisodeMarker {
+                start: i,
+                end: i + 10,
+                season: year.to_string(),
+                episodes: vec![format!("{}{}", month, day)],
+            });
+        }
+    }
+    None
+}
+
+fn trim_number(value: &str) -> String {
+    let value = value.trim_start_matches('0');
+    if value.is_empty() {
+        "0".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Season number of a `Season 1`, `S01` or `Specials` directory.
+pub(crate) fn parse_season_dir(name: &str) -> Option<String> {
+    let lowercase = name.trim().to_lowercase();
+    if lowercase == "specials" {
+        return Some("0".to_string());
+    }
+    let number = lowercase
+        .strip_prefix("season")
+        .or_else(|| lowercase.strip_prefix('s'))?
+        .trim_start_matches([' ', '.', '_']);
+    if !number.is_empty() && number.len() <= 3 && number.chars().all(|c| c.is_ascii_digit()) {
+        return Some(trim_number(number));
+    }
+    None
+}
+
+/// Read an episode from its path relative to the library root. `S01E02` and `1x02` markers and air
+/// dates are found anywhere in the file name, while a plain leading number such as `02 - Title`
+/// only counts inside a season directory.
+pub(crate) fn parse_episode_name(relative_path: &Path) -> Option<EpisodeName> {
+    let stem = relative_path.file_stem()?.to_string_lossy();
+    let season_dir = relative_path
+        .parent()
+        .and_then(|o| o.file_name())
+        .and_then(|o| parse_season_dir(&o.to_string_lossy()));
+
+    let (season, episodes, rest) = if let Some(marker) = find_episode_marker(&stem) {
+        (marker.season, marker.episodes, &stem[marker.end..])
+    } else if let Some(date) = find_air_date(&stem) {
+        let rest = &stem[date.end..];
+        // a date without a title keeps the date itself as the title
+        if rest.trim_matches(['-', '.', '_', ' ']).is_empty() {
+            return Some(EpisodeName {
+                title: stem[date.start..date.end].replace(['.', '_', ' '], "-"),
+                season: date.season,
+                episodes: date.episodes,
+            });
+        }
+        (date.season, date.episodes, rest)
+    } else {
+        let season = season_dir?;
+        let digits = stem.chars().take_while(|c| c.is_ascii_digit()).count();
+        if !(1..=3).contains(&digits) {
+            return None;
+        }
+        (season, vec![trim_number(&stem[..digits])], &stem[digits..])
+    };
+
+    let rest = rest.trim_start_matches(['-', '.', '_', ' ']);
+    let title = parse_release_name(rest).title;
+    let title = if title.is_empty() {
+        format!("Episode {}", episodes.join("-"))
+    } else {
+        title
+    };
+    Some(EpisodeName {
+        season,
+        episodes,
+        title,
+    })
+}
+
+/// Still of an episode named after its video, `Show S01E01-thumb.jpg` for `Show S01E01.mkv`.
+pub(crate) fn get_episode_thumb(media_source: &MediaSource, video: &Path) -> Option<String> {
+    let thumb_stem = format!("{}-thumb", video.file_stem()?.to_string_lossy()).to_lowercase();
+    get_poster_filename(media_source).into_iter().find(|o| {
+        Path::new(o)
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().to_lowercase() == thumb_stem)
+    })
+}
+
+pub(crate) fn is_extra(relative_path: &Path) -> bool {
+    let in_extra_dir = relative_path
+        .parent()
+        .and_then(|o| o.file_name())
+        .is_some_and(|o| EXTRA_DIRS.contains(&o.to_string_lossy().to_lowercase().as_str()));
+    let stem = relative_path
+        .file_stem()
+        .map(|o| o.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    in_extra_dir || stem == "sample" || EXTRA_SUFFIXES.iter().any(|o| stem.ends_with(o))
+}
+
+/// Split a stacked file name like `Movie.cd2` or `Movie - part 2` into the name without the
+/// marker and the part number.
+fn split_stack_marker(stem: &str) -> Option<(String, u32)> {
+    let lowercase = stem.to_ascii_lowercase();
+    let bytes = lowercase.as_bytes();
+    let is_separator = |c: u8| matches!(c, b' ' | b'.' | b'_' | b'-');
+    for marker in STACK_MARKERS {
+        for (start, _) in lowercase.match_indices(marker) {
+            if start > 0 && bytes[start - 1].is_ascii_alphanumeric() {
+                continue;
+            }
+            let mut i = start + marker.len();
+            while i < bytes.len() && is_separator(bytes[i]) {
+                i += 1;
+            }
+            let digits = bytes[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+            let end = i + digits;
+            if !(1..=2).contains(&digits) || bytes.get(end).is_some_and(u8::is_ascii_alphanumeric) {
+                continue;
+            }
+            let Ok(part) = lowercase[i..end].parse::<u32>() else {
+                continue;
+            };
+            let base = format!(
+                "{}{}",
+                stem[..start].trim_end_matches(|c: char| c.is_ascii() && is_separator(c as u8)),
+                &stem[end..]
+            );
+            return Some((base, part));
+        }
+    }
+    None
+}
+
+// a disc structure is named by the directory holding it, Heat (1995)/BDMV/index.bdmv
+fn get_video_stem(video: &Path) -> String {
+    get_disc_parent(video)
+        .and_then(|o| o.file_name().map(|o| o.to_string_lossy().into_owned()))
+        .or_else(|| video.file_stem().map(|o| o.to_string_lossy().into_owned()))
+        .unwrap_or_default()
+}
+
+fn get_stack_base(video: &Path) -> (String, u32) {
+    let stem = get_video_stem(video);
+    split_stack_marker(&stem).unwrap_or((stem, 0))
+}
+
+fn get_resolution(name: &str) -> u32 {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter_map(|o| match o {
+            "2160p" | "4k" | "uhd" => Some(2160),
+            "1080p" | "1080i" => Some(1080),
+            "720p" => Some(720),
+            "576p" => Some(576),
+            "480p" => Some(480),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+// Jellyfin names versions as "Movie (2010) - 1080p", otherwise resolution and edition are used
+fn get_version_label(base: &str) -> String {
+    if let Some((_, suffix)) = base.rsplit_once(" - ") {
+        if !suffix.trim().is_empty() {
+            return suffix.trim().to_string();
+        }
+    }
+    let label = [
+        match get_resolution(base) {
+            0 => String::new(),
+            2160 => "4K".to_string(),
+            v => format!("{}p", v),
+        },
+        parse_release_name(base).edition,
+    ]
+    .into_iter()
+    .filter(|o| !o.is_empty())
+    .collect::<Vec<String>>()
+    .join(" ");
+    if label.is_empty() {
+        base.to_string()
+    } else {
+        label
+    }
+}
+
+/// Versions of a single movie from its video files. Stacked files such as `cd1`/`cd2` are parts
+/// of the same version, and versions go from the highest resolution down.
+pub(crate) fn get_movie_versions(videos: &[&Path]) -> Vec<MovieVersion> {
+    let mut stacks: Vec<(String, Vec<(u32, &Path)>)> = Vec::new();
+    for video in videos {
+        let (base, part) = get_stack_base(video);
+        match stacks
+            .iter_mut()
+            .find(|(o, _)| o.to_lowercase() == base.to_lowercase())
+        {
+            Some((_, parts)) => parts.push((part, video)),
+            None => stacks.push((base, vec![(part, video)])),
+        }
+    }
+
+    let mut versions = stacks
+        .into_iter()
+        .map(|(base, mut parts)| {
+            parts.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| natural_cmp_path(a.1, b.1)));
+            let resolution = get_resolution(&base);
+            let parts = parts
+                .into_iter()
+                .map(|(_, o)| o.to_string_lossy().into_owned())
+                .collect::<Vec<String>>();
+            (
+                resolution,
+                MovieVersion::new(get_version_label(&base), parts),
+            )
+        })
+        .collect::<Vec<(u32, MovieVersion)>>();
+    versions.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| natural_cmp(a.1.label(), b.1.label()))
+    });
+    versions.into_iter().map(|(_, o)| o).collect()
+}
+
+fn natural_cmp_path(a: &Path, b: &Path) -> Ordering {
+    natural_cmp(&a.to_string_lossy(), &b.to_string_lossy())
+}
+
+// videos belong to the same movie when their names share title and year
+fn get_movie_key(video: &Path) -> String {
+    let name = parse_release_name(&get_stack_base(video).0);
+    format!("{} {}", name.title.to_lowercase(), name.year)
+}
+
+/// Movies for the videos of a directory that has no NFO file. A directory holding a single movie,
+/// possibly split into parts or versions, is keyed by the directory like an NFO movie, while loose
+/// movies sharing a directory are keyed by their first file. Episodes and bonus material are left
+/// alone.
+pub(crate) fn infer_movies(dir: &OsString, media_source: &MediaSource) -> Vec<Media> {
+    let videos = media_source
+        .media()
+        .iter()
+        .map(Path::new)
+        .filter(|o| !is_extra(o))
+        .filter(|o| {
+            !o.file_stem()
+                .is_some_and(|s| is_episode_name(&s.to_string_lossy()))
+        })
+        .collect::<Vec<&Path>>();
+
+    let mut movies: Vec<(String, Vec<&Path>)> = Vec::new();
+    for video in videos {
+        let key = get_movie_key(video);
+        match movies.iter_mut().find(|(o, _)| *o == key) {
+            Some((_, files)) => files.push(video),
+            None => movies.push((key, vec![video])),
+        }
+    }
+
+    let dir_path = Path::new(dir);
+    if let [(_, videos)] = movies.as_slice() {
+        if !dir.is_empty() {
+            let mut media = infer_movie(videos);
+            // the folder name is usually the cleaner one, "Heat (1995)/heat.1995.1080p.mkv"
+            let folder = parse_release_name(
+                &dir_path
+                    .file_name()
+                    .map(|o| o.to_string_lossy())
+                    .unwrap_or_default(),
+            );
+            if !folder.title.is_empty() && (!folder.year.is_empty() || media.year().is_empty()) {
+                media.set_title(folder.title);
+                media.set_year(folder.year);
+            }
+            if let Some(file) = media.versions().first().and_then(|o| o.parts().first()) {
+                // disc structures are played from a file below the movie directory
+                let file = Path::new(file)
+                    .strip_prefix(dir_path)
+                    .unwrap_or(Path::new(file));
+                media.set_file(file.to_string_lossy().into_owned());
+            }
+            media.set_relative_path(dir.clone());
+            media.set_posters(get_poster_filename(media_source));
+            return vec![media];
+        }
+    }
+
+    movies
+        .into_iter()
+        .map(|(_, videos)| {
+            let mut media = infer_movie(&videos);
+            if let Some(file) = media.versions().first().and_then(|o| o.parts().first()) {
+                media.set_relative_path(OsString::from(file));
+            }
+            // the path already points to the video
+            media.set_file(String::new());
+            media
+        })
+        .collect()
+}
+
+fn infer_movie(videos: &[&Path]) -> Media {
+    let versions = get_movie_versions(videos);
+    let first = versions
+        .first()
+        .and_then(|o| o.parts().first())
+        .map(Path::new)
+        .unwrap_or(Path::new(""));
+    let stem = get_stack_base(first).0;
+    let name = parse_release_name(&stem);
+
+    let mut media = Media::default();
+    media.set_media_type(MediaType::Movie);
+    media.set_inferred(true);
+    media.set_title(if name.title.is_empty() {
+        stem
+    } else {
+        name.title
+    });
+    media.set_year(name.year);
+    media.details_mut().set_edition(name.edition);
+    media.set_file(
+        first
+            .file_name()
+            .map(|o| o.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    );
+    media.set_versions(versions);
+    media
+}
+
+/// Episodes for the videos of a show directory that no episode NFO file describes.
+pub(crate) fn infer_episodes(media_source: &MediaSource, nfo_media: &[Media]) -> Vec<Media> {
+    let described = nfo_media
+        .iter()
+        .filter(|o| matches!(o.media_type(), MediaType::Episode))
+        .map(|o| Path::new(o.relative_path()).join(o.file()))
+        .collect::<Vec<PathBuf>>();
+
+    media_source
+        .media()
+        .iter()
+        .map(Path::new)
+        .filter(|o| !is_extra(o) && !described.iter().any(|d| d == o))
+        .filter_map(|video| Some((video, parse_episode_name(video)?)))
+        .flat_map(|(video, name)| {
+            // a multi-episode file shows up once for each of its episodes
+            name.episodes.into_iter().map(move |episode| {
+                let mut media = Media::default();
+                media.set_media_type(MediaType::Episode);
+                media.set_inferred(true);
+                media.set_relative_path(
+                    video
+                        .parent()
+                        .map(|o| o.as_os_str().to_os_string())
+                        .unwrap_or_default(),
+                );
+                media.set_file(
+                    video
+                        .file_name()
+                        .map(|o| o.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                );
+                media.set_title(name.title.clone());
+                media.set_season(name.season.clone());
+                media.set_episode(episode);
+                if let Some(thumb) = get_episode_thumb(media_source, video) {
+                    media.add_poster(thumb);
+                }
+                media
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(title: &str, year: &str, edition: &str) -> ReleaseName {
+        ReleaseName {
+            title: title.to_string(),
+            year: year.to_string(),
+            edition: edition.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_release_name_reads_title_and_year() {
+        assert_eq!(
+            parse_release_name("Heat (1995)"),
+            release("Heat", "1995", "")
+        );
+        assert_eq!(
+            parse_release_name("The.Dark.Knight.2008.1080p.BluRay.x264-GROUP"),
+            release("The Dark Knight", "2008", "")
+        );
+        assert_eq!(
+            parse_release_name("Blade_Runner_1982_Final_Cut_2160p"),
+            release("Blade Runner", "1982", "Final Cut")
+        );
+        assert_eq!(parse_release_name("Heat"), release("Heat", "", ""));
+    }
+
+    #[test]
+    fn parse_release_name_keeps_numbers_in_titles() {
+        assert_eq!(
+            parse_release_name("1917 (2019)"),
+            release("1917", "2019", "")
+        );
+        assert_eq!(
+            parse_release_name("2001.A.Space.Odyssey.1968.REMASTERED.720p"),
+            release("2001 A Space Odyssey", "1968", "Remastered")
+        );
+        assert_eq!(
+            parse_release_name("Blade Runner 2049 (2017)"),
+            release("Blade Runner 2049", "2017", "")
+        );
+    }
+
+    #[test]
+    fn parse_release_name_reads_editions() {
+        assert_eq!(
+            parse_release_name("Aliens (1986) {edition-Special Edition}"),
+            release("Aliens", "1986", "Special Edition")
+        );
+        assert_eq!(
+            parse_release_name("Kingdom of Heaven (2005) [Director's Cut]"),
+            release("Kingdom of Heaven", "2005", "Director's Cut")
+        );
+        assert_eq!(
+            parse_release_name("Kingdom.of.Heaven.2005.Directors.Cut.1080p"),
+            release("Kingdom of Heaven", "2005", "Directors Cut")
+        );
+        assert_eq!(
+            parse_release_name("Akira.1080p.Remastered"),
+            release("Akira", "", "Remastered")
+        );
+    }
+
+    #[test]
+    fn is_episode_name_detects_markers() {
+        assert!(is_episode_name("Show.S01E02.720p"));
+        assert!(is_episode_name("show 1x02"));
+        assert!(!is_episode_name("Heat.1995.1920x1080"));
+        assert!(!is_episode_name("Se7en (1995)"));
+    }
+
+    fn source(files: &[&str]) -> MediaSource {
+        let mut media_source = MediaSource::default();
+        for file in files {
+            if file.ends_with(".jpg") {
+                media_source.add_poster(OsString::from(file));
+            } else {
+                media_source.add_media(OsString::from(file));
+            }
+        }
+        media_source
+    }
+
+    #[test]
+    fn infer_movies_uses_folder_for_single_video() {
+        let dir = OsString::from("Heat (1995)");
+        let media_source = source(&[
+            "Heat (1995)/heat.1080p.mkv",
+            "Heat (1995)/heat-trailer.mkv",
+            "Heat (1995)/poster.jpg",
+        ]);
+
+        let movies = infer_movies(&dir, &media_source);
+        assert_eq!(movies.len(), 1);
+        let item = movies[0].movie().unwrap();
+        assert_eq!(item.title(), "Heat");
+        assert_eq!(item.year(), "1995");
+        assert_eq!(item.path(), "Heat (1995)");
+        assert_eq!(item.file(), "heat.1080p.mkv");
+        assert_eq!(item.posters(), r#"{"main":"poster.jpg"}"#);
+        assert!(item.inferred());
+    }
+
+    #[test]
+    fn infer_movies_keys_loose_videos_by_file() {
+        let media_source = source(&[
+            "Heat.1995.mkv",
+            "Ronin.1998.Directors.Cut.mkv",
+            "Show.S01E01.mkv",
+        ]);
+
+        let movies = infer_movies(&OsString::new(), &media_source);
+        let items = movies.iter().filter_map(|o| o.movie()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].path(), "Heat.1995.mkv");
+        assert_eq!(items[0].file(), "");
+        assert_eq!(items[1].title(), "Ronin");
+        assert!(items[1].tags().is_empty());
+        let details: serde_json::Value = serde_json::from_str(items[1].details()).unwrap();
+        assert_eq!(details["edition"], "Directors Cut");
+    }
+
+    #[test]
+    fn infer_movies_skips_extras_directories() {
+        let dir = Path::new("Heat").join("Extras").into_os_string();
+        let video = Path::new("Heat").join("Extras").join("Making of.mkv");
+        let media_source = source(&[&video.to_string_lossy()]);
+
+        assert!(infer_movies(&dir, &media_source).is_empty());
+    }
+
+    fn episode(season: &str, episode: &str, title: &str) -> Option<EpisodeName> {
+        Some(EpisodeName {
+            season: season.to_string(),
+            episodes: episode.split(',').map(|o| o.to_string()).collect(),
+            title: title.to_string(),
+        })
+    }
+
+    #[test]
+    fn parse_episode_name_reads_markers() {
+        assert_eq!(
+            parse_episode_name(Path::new("Show/Show.S01E02.The.Title.720p.mkv")),
+            episode("1", "2", "The Title")
+        );
+        assert_eq!(
+            parse_episode_name(Path::new("Show/Show - 2x10 - Finale.mkv")),
+            episode("2", "10", "Finale")
+        );
+        assert_eq!(
+            parse_episode_name(Path::new("Show/s03e04.mkv")),
+            episode("3", "4", "Episode 4")
+        );
+        assert_eq!(
+            parse_episode_name(Path::new("Show/Heat.1920x1080.mkv")),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_episode_name_reads_multi_episode_markers() {
+        assert_eq!(
+            parse_episode_name(Path::new("Show/Show.S02E05E06.Finale.mkv")),
+            episode("2", "5,6", "Finale")
+        );
+        assert_eq!(
+            parse_episode_name(Path::new("Show/S02E05-E06.mkv")),
+            episode("2", "5,6", "Episode 5-6")
+        );
+        assert_eq!(
+            parse_episode_name(Path::new("Show/S02E05-06 - Finale.mkv")),
+            episode("2", "5,6", "Finale")
+        );
+        // a resolution after the marker is not another episode
+        assert_eq!(
+            parse_episode_name(Path::new("Show/S02E05-720p.mkv")),
+            episode("2", "5", "Episode 5")
+        );
+        assert!(matches_episode("S02E05E06", "02", "06"));
+        assert!(!matches_episode("S02E05E06", "02", "07"));
+    }
+
+    #[test]
+    fn parse_episode_name_reads_air_dates() {
+        assert_eq!(
+            parse_episode_name(Path::new("Daily/Daily.2024-03-01.Guest.mkv")),
+            episode("2024", "0301", "Guest")
+        );
+        assert_eq!(
+            parse_episode_name(Path::new("Daily/2024.12.31.mkv")),
+            episode("2024", "1231", "2024-12-31")
+        );
+        assert_eq!(parse_episode_name(Path::new("Daily/2024-13-01.mkv")), None);
+        assert_eq!(
+            parse_episode_name(Path::new("Daily/2024-03-01.Émission.mkv")),
+            episode("2024", "0301", "Émission")
+        );
+        assert_eq!(
+            parse_episode_name(Path::new("Daily/Amélie-le-film.mkv")),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_episode_name_reads_numbers_in_season_dirs() {
+        let season = Path::new("Show").join("Season 1");
+        assert_eq!(
+            parse_episode_name(&season.join("02 - Title.mkv")),
+            episode("1", "2", "Title")
+        );
+        assert_eq!(
+            parse_episode_name(&Path::new("Show").join("Specials").join("1.mkv")),
+            episode("0", "1", "Episode 1")
+        );
+        // a leading number outside of a season directory is part of the title
+        assert_eq!(parse_episode_name(Path::new("Show/02 - Title.mkv")), None);
+    }
+
+    #[test]
+    fn infer_episodes_splits_multi_episode_files() {
+        let media_source = source(&["Show/S01E01E02.mkv"]);
+
+        let episodes = infer_episodes(&media_source, &[]);
+        let numbers = episodes
+            .iter()
+            .map(|o| (o.episode(), o.file()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            numbers,
+            vec![("01", "S01E01E02.mkv"), ("02", "S01E01E02.mkv")]
+        );
+    }
+
+    #[test]
+    fn infer_episodes_picks_up_episode_stills() {
+        let mut media_source = source(&["Show/Show S01E01.mkv", "Show/Show S01E02.mkv"]);
+        media_source.add_poster(OsString::from("Show/Show S01E01-thumb.jpg"));
+        media_source.add_poster(OsString::from("Show/poster.jpg"));
+
+        let episodes = infer_episodes(&media_source, &[]);
+        let thumbs = episodes
+            .iter()
+            .map(|o| o.posters().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            thumbs,
+            vec![vec!["Show S01E01-thumb.jpg".to_string()], vec![]]
+        );
+    }
+
+    #[test]
+    fn infer_episodes_skips_videos_described_by_nfo() {
+        let season = Path::new("Show").join("Season 01");
+        let described = season.join("S01E01.mkv");
+        let undescribed = season.join("S01E02.mkv");
+        let media_source = source(&[
+            &described.to_string_lossy(),
+            &undescribed.to_string_lossy(),
+            &season.join("S01E02-sample.mkv").to_string_lossy(),
+        ]);
+        let mut nfo_episode = Media::default();
+        nfo_episode.set_media_type(MediaType::Episode);
+        nfo_episode.set_relative_path(season.clone().into_os_string());
+        nfo_episode.set_file("S01E01.mkv".to_string());
+
+        let episodes = infer_episodes(&media_source, &[nfo_episode]);
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].relative_path(), season.as_os_str());
+        assert_eq!(episodes[0].file(), "S01E02.mkv");
+        assert_eq!(episodes[0].season(), "01");
+        assert_eq!(episodes[0].episode(), "02");
+        assert!(episodes[0].inferred());
+    }
+
+    #[test]
+    fn split_stack_marker_reads_part_numbers() {
+        assert_eq!(
+            split_stack_marker("Heat.cd2"),
+            Some(("Heat".to_string(), 2))
+        );
+        assert_eq!(
+            split_stack_marker("Heat (1995) - Part 1.1080p"),
+            Some(("Heat (1995).1080p".to_string(), 1))
+        );
+        assert_eq!(
+            split_stack_marker("Heat-disc01"),
+            Some(("Heat".to_string(), 1))
+        );
+        assert_eq!(split_stack_marker("Departed"), None);
+        assert_eq!(split_stack_marker("Heat.cd2017"), None);
+    }
+
+    #[test]
+    fn get_movie_versions_stacks_parts_and_orders_versions() {
+        let files = [
+            "Heat/Heat - 1080p.mkv",
+            "Heat/Heat - 2160p.cd2.mkv",
+            "Heat/Heat - 2160p.cd1.mkv",
+        ];
+        let videos = files.iter().map(Path::new).collect::<Vec<&Path>>();
+
+        let versions = get_movie_versions(&videos);
+        assert_eq!(
+            versions,
+            vec![
+                MovieVersion::new(
+                    "2160p".to_string(),
+                    vec![
+                        "Heat/Heat - 2160p.cd1.mkv".to_string(),
+                        "Heat/Heat - 2160p.cd2.mkv".to_string()
+                    ]
+                ),
+                MovieVersion::new(
+                    "1080p".to_string(),
+                    vec!["Heat/Heat - 1080p.mkv".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn get_version_label_uses_resolution_and_edition() {
+        assert_eq!(
+            get_version_label("Aliens.1986.2160p.Extended"),
+            "4K Extended"
+        );
+        assert_eq!(
+            get_version_label("Aliens (1986) - Theatrical"),
+            "Theatrical"
+        );
+        assert_eq!(get_version_label("Aliens"), "Aliens");
+    }
+
+    #[test]
+    fn infer_movies_keeps_parts_in_one_movie() {
+        let dir = OsString::from("Heat (1995)");
+        let media_source = source(&["Heat (1995)/Heat.cd2.mkv", "Heat (1995)/Heat.cd1.mkv"]);
+
+        let movies = infer_movies(&dir, &media_source);
+        assert_eq!(movies.len(), 1);
+        let item = movies[0].movie().unwrap();
+        assert_eq!(item.path(), "Heat (1995)");
+        assert_eq!(item.file(), "Heat.cd1.mkv");
+        assert_eq!(
+            item.versions(),
+            r#"[{"label":"Heat","parts":["Heat (1995)/Heat.cd1.mkv","Heat (1995)/Heat.cd2.mkv"]}]"#
+        );
+
+        // loose stacked movies are keyed by their first part
+        let media_source = source(&["Heat.cd2.mkv", "Heat.cd1.mkv", "Ronin.mkv"]);
+        let movies = infer_movies(&OsString::new(), &media_source);
+        let paths = movies
+            .iter()
+            .map(|o| o.relative_path().to_string_lossy().into_owned())
+            .collect::<Vec<String>>();
+        assert_eq!(paths, vec!["Heat.cd1.mkv", "Ronin.mkv"]);
+        assert_eq!(movies[1].movie().unwrap().versions(), "");
+    }
+
+    #[test]
+    fn infer_movies_names_disc_structures_by_their_directory() {
+        let dir = OsString::from("Heat (1995)");
+        let media_source = source(&["Heat (1995)/BDMV/index.bdmv"]);
+
+        let movies = infer_movies(&dir, &media_source);
+        let item = movies[0].movie().unwrap();
+        assert_eq!(item.title(), "Heat");
+        assert_eq!(item.year(), "1995");
+        assert_eq!(item.file(), "BDMV/index.bdmv");
+    }
+}
@@ -1,4 +1,8 @@
-use crate::model::parser::{Media, MediaSource, MediaType};
+use crate::helper::main::get_art_type;
+use crate::model::parser::{Media, MediaDetails, MediaSource, MediaType, MovieSet, StreamDetails};
+use crate::parser::name_parser::{
+    get_episode_thumb, get_movie_versions, is_extra, matches_episode,
+};
 use log::error;
 use roxmltree::Node;
 use std::collections::VecDeque;
@@ -10,7 +14,7 @@ pub(crate) fn parse_nfo(
     root_path: &Path,
     nfo_dir: &OsString,
     media_source: &MediaSource,
-) -> Result<Option<Media>, String> {
+) -> Result<Vec<Media>, String> {
     let nfo_path = PathBuf::from(nfo_dir);
     let file_path = root_path.join(&nfo_path);
     let content = fs::read_to_string(file_path).map_err(|e| {
@@ -21,6 +25,14 @@ pub(crate) fn parse_nfo(
         )
     })?;
 
+    // a multi-episode file has one <episodedetails> root per episode, which is not valid XML
+    let multi_episode = content.matches("<episodedetails").count() > 1;
+    let content = if multi_episode {
+        wrap_documents(&content)
+    } else {
+        content
+    };
+
     let doc = roxmltree::Document::parse(content.as_str()).map_err(|e| {
         format!(
             "Error when parsing nfo file {}. Raising error {}",
@@ -29,41 +41,57 @@ pub(crate) fn parse_nfo(
         )
     })?;
 
-    let nfo_node = doc
-        .root()
+    let parent_node = if multi_episode {
+        doc.root_element()
+    } else {
+        doc.root()
+    };
+    let nfo_nodes = parent_node
         .children()
-        .find(|o| is_valid_source(o))
-        .ok_or_else(|| {
-            format!(
-                "NFO file does not have any valid tag for parsing. {}",
-                nfo_dir.to_string_lossy()
-            )
-        })?;
-    let nfo_type = nfo_node.tag_name().name();
+        .filter(is_valid_source)
+        .collect::<Vec<Node>>();
+    if nfo_nodes.is_empty() {
+        return Err(format!(
+            "NFO file does not have any valid tag for parsing. {}",
+            nfo_dir.to_string_lossy()
+        ));
+    }
 
-    let mut media = Media::default();
     let parent = nfo_path.parent().ok_or_else(|| {
         format!(
             "Cannot get parent directory for nfo path {}",
             nfo_dir.to_string_lossy()
         )
     })?;
-    media.set_relative_path(parent.as_os_str().to_os_string());
-
-    match nfo_type {
-        "movie" => parse_movie_nfo(&mut media, &nfo_node, media_source),
-        "tvshow" => parse_tvshow_nfo(&mut media, &nfo_node, media_source),
-        "episodedetails" => parse_episode_nfo(&mut media, &nfo_node, &nfo_path, media_source),
-        _ => {
-            return Err(format!("Unknown nfo type '{}' for parsing", nfo_type));
+
+    let mut result = Vec::new();
+    for nfo_node in nfo_nodes {
+        let mut media = Media::default();
+        media.set_relative_path(parent.as_os_str().to_os_string());
+
+        let nfo_type = nfo_node.tag_name().name();
+        match nfo_type {
+            "movie" => parse_movie_nfo(&mut media, &nfo_node, media_source),
+            "tvshow" => parse_tvshow_nfo(&mut media, &nfo_node, media_source),
+            "episodedetails" => parse_episode_nfo(&mut media, &nfo_node, &nfo_path, media_source),
+            _ => {
+                return Err(format!("Unknown nfo type '{}' for parsing", nfo_type));
+            }
         }
-    }
 
-    // TODO: filter out bdmv until find a better way solve it.
-    if media.file().to_lowercase().contains("bdmv") {
-        return Ok(None);
+        result.push(media);
     }
-    Ok(Some(media))
+    Ok(result)
+}
+
+/// Put several XML documents under a single `<nfo>` root, dropping the XML declaration.
+fn wrap_documents(content: &str) -> String {
+    let content = content.trim_start();
+    let body = match content.strip_prefix("<?xml") {
+        Some(rest) => rest.split_once("?>").map_or(rest, |o| o.1),
+        None => content,
+    };
+    format!("<nfo>{}</nfo>", body)
 }
 
 fn is_valid_source(o: &Node) -> bool {
@@ -114,7 +142,16 @@ fn parse_movie_nfo(media: &mut Media, root: &Node, media_source: &MediaSource) {
                 }
             }
             "actor" => media.extend_actors(get_actor_name(&curr_node)),
-            &_ => {}
+            "thumb" | "fanart" => parse_artwork(media, &curr_node, root),
+            "fileinfo" if curr_node.parent() == Some(*root) => {
+                parse_stream_details(media.streams_mut(), &curr_node)
+            }
+            "set" => {
+                if let Some(v) = get_movie_set(&curr_node) {
+                    media.set_collection(v);
+                }
+            }
+            &_ => parse_details(media.details_mut(), &curr_node, root),
         }
         if curr_node.has_children() {
             for child in curr_node.children() {
@@ -128,12 +165,26 @@ fn parse_movie_nfo(media: &mut Media, root: &Node, media_source: &MediaSource) {
         media.add_posters(&mut get_poster_filename(media_source));
     }
 
-    // handle movie file
+    // handle movie file, split into its parts and versions
     if media.file().is_empty() && !media_source.media().is_empty() {
-        let file_path = Path::new(media_source.media().first().as_ref().unwrap().as_os_str());
-        media.set_file(String::from(
-            file_path.file_name().unwrap().to_string_lossy(),
-        ));
+        let videos = media_source
+            .media()
+            .iter()
+            .map(Path::new)
+            .filter(|o| !is_extra(o))
+            .collect::<Vec<&Path>>();
+        let versions = get_movie_versions(&videos);
+        let file_path = versions
+            .first()
+            .and_then(|o| o.parts().first())
+            .map(Path::new)
+            .unwrap_or(Path::new(media_source.media().first().unwrap().as_os_str()));
+        // disc structures are played from a file below the movie directory, BDMV/index.bdmv
+        let file = file_path
+            .strip_prefix(Path::new(media.relative_path()))
+            .unwrap_or(Path::new(file_path.file_name().unwrap()));
+        media.set_file(String::from(file.to_string_lossy()));
+        media.set_versions(versions);
     }
 }
 
@@ -167,7 +218,8 @@ fn parse_tvshow_nfo(media: &mut Media, root: &Node, media_source: &MediaSource)
                 }
             }
             "actor" => media.extend_actors(get_actor_name(&curr_node)),
-            &_ => {}
+            "thumb" | "fanart" => parse_artwork(media, &curr_node, root),
+            &_ => parse_details(media.details_mut(), &curr_node, root),
         }
         if curr_node.has_children() {
             for child in curr_node.children() {
@@ -176,8 +228,13 @@ fn parse_tvshow_nfo(media: &mut Media, root: &Node, media_source: &MediaSource)
         }
     }
 
-    // handle poster
-    media.set_posters(get_poster_filename(media_source));
+    // handle poster, thumbs in the show directory are episode stills
+    media.set_posters(
+        get_poster_filename(media_source)
+            .into_iter()
+            .filter(|o| get_art_type(o) != Some("thumb"))
+            .collect(),
+    );
 }
 
 fn parse_episode_nfo(media: &mut Media, root: &Node, nfo_path: &Path, media_source: &MediaSource) {
@@ -204,7 +261,16 @@ fn parse_episode_nfo(media: &mut Media, root: &Node, nfo_path: &Path, media_sour
                     media.set_episode(v.to_string());
                 }
             }
-            &_ => {}
+            // actors have a <thumb> as well
+            "thumb" if curr_node.parent() == Some(*root) => {
+                if let Some(v) = text {
+                    media.add_artwork("", "thumb", v.trim().to_string());
+                }
+            }
+            "fileinfo" if curr_node.parent() == Some(*root) => {
+                parse_stream_details(media.streams_mut(), &curr_node)
+            }
+            &_ => parse_details(media.details_mut(), &curr_node, root),
         }
         if curr_node.has_children() {
             for child in curr_node.children() {
@@ -224,6 +290,171 @@ fn parse_episode_nfo(media: &mut Media, root: &Node, nfo_path: &Path, media_sour
             media_source.media()
         )
     }
+
+    // the still is named after the video, or after the NFO file when the video is missing
+    let video = if media.file().is_empty() {
+        nfo_path
+    } else {
+        Path::new(media.file())
+    };
+    if let Some(thumb) = get_episode_thumb(media_source, video) {
+        media.add_poster(thumb);
+    }
+}
+
+// <thumb aspect="banner">..</thumb>, <thumb aspect="poster" type="season" season="1">..</thumb> and
+// <fanart url=".."><thumb>..</thumb></fanart>, with files next to the NFO file or URLs
+fn parse_artwork(media: &mut Media, node: &Node, root: &Node) {
+    // actors have a <thumb> as well
+    if node.parent() != Some(*root) {
+        return;
+    }
+    let text = |node: &Node| node.text().unwrap_or_default().trim().to_string();
+    match node.tag_name().name() {
+        "thumb" => {
+            let Some(art_type) = get_art_type(node.attribute("aspect").unwrap_or("poster")) else {
+                return;
+            };
+            let season = match (node.attribute("type"), node.attribute("season")) {
+                (Some("season"), Some("-1")) => String::from("all"),
+                (Some("season"), Some(v)) => format!("{:0>2}", v),
+                _ => String::new(),
+            };
+            media.add_artwork(&season, art_type, text(node));
+        }
+        "fanart" => {
+            let base = node.attribute("url").unwrap_or_default();
+            for thumb in node.children().filter(|o| o.tag_name().name() == "thumb") {
+                let image = text(&thumb);
+                if !image.is_empty() {
+                    media.add_artwork("", "fanart", format!("{}{}", base, image));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// <fileinfo><streamdetails><video>..</video><audio>..</audio><subtitle>..</subtitle></streamdetails>
+// </fileinfo>, a file with several video streams is described by the first one
+fn parse_stream_details(streams: &mut StreamDetails, node: &Node) {
+    let child_text = |parent: &Node, name: &str| {
+        parent
+            .children()
+            .find(|o| o.tag_name().name() == name)
+            .and_then(|o| o.text())
+            .map(|o| o.trim().to_string())
+            .unwrap_or_default()
+    };
+    let number = |parent: &Node, name: &str| child_text(parent, name).parse::<u32>().unwrap_or(0);
+
+    let Some(details) = node
+        .children()
+        .find(|o| o.tag_name().name() == "streamdetails")
+    else {
+        return;
+    };
+    let mut has_video = false;
+    for stream in details.children().filter(|o| o.is_element()) {
+        match stream.tag_name().name() {
+            "video" if !has_video => {
+                has_video = true;
+                streams.set_video_codec(child_text(&stream, "codec"));
+                streams.set_size(number(&stream, "width"), number(&stream, "height"));
+                streams.set_aspect(child_text(&stream, "aspect"));
+                streams.set_hdr_type(child_text(&stream, "hdrtype"));
+                // older NFO files only carry the duration in minutes
+                let duration = match number(&stream, "durationinseconds") {
+                    0 => number(&stream, "duration") * 60,
+                    v => v,
+                };
+                streams.set_duration(duration);
+            }
+            "audio" => streams.add_audio(
+                child_text(&stream, "codec"),
+                child_text(&stream, "language"),
+                number(&stream, "channels"),
+            ),
+            "subtitle" => streams.add_subtitle(child_text(&stream, "language")),
+            _ => {}
+        }
+    }
+}
+
+/// Descriptive tags shared by movie, show and episode NFO files.
+fn parse_details(details: &mut MediaDetails, node: &Node, root: &Node) {
+    let text = node
+        .text()
+        .map(|o| o.trim().to_string())
+        .unwrap_or_default();
+    // nested tags like the ones of <set> or <fileinfo> reuse some of these names
+    let is_top_level = node.parent().is_some_and(|o| o == *root);
+    match node.tag_name().name() {
+        "originaltitle" if is_top_level => details.set_original_title(text),
+        "sorttitle" if is_top_level => details.set_sort_title(text),
+        "plot" if is_top_level => details.set_plot(text),
+        "outline" if is_top_level => details.set_outline(text),
+        "tagline" if is_top_level => details.set_tagline(text),
+        "runtime" if is_top_level => details.set_runtime(text),
+        "mpaa" if is_top_level => details.set_mpaa(text),
+        "premiered" | "aired" if is_top_level => details.set_premiered(text),
+        "director" => details.add_director(text),
+        "credits" => details.add_credit(text),
+        "country" => details.add_country(text),
+        "uniqueid" => details.add_unique_id(
+            node.attribute("type").unwrap_or("unknown"),
+            text,
+            node.attribute("default") == Some("true"),
+        ),
+        // older NFO files only carry a single imdb id
+        "id" if is_top_level && text.starts_with("tt") => {
+            details.add_unique_id("imdb", text, false)
+        }
+        "rating" => parse_rating(details, node, is_top_level),
+        &_ => {}
+    }
+}
+
+// <ratings><rating name="imdb" max="10" default="true"><value>7.5</value><votes>1</votes></rating>
+// </ratings>, or a plain <rating>7.5</rating> with <votes> beside it in older NFO files
+fn parse_rating(details: &mut MediaDetails, node: &Node, is_top_level: bool) {
+    let child_text = |parent: &Node, name: &str| {
+        parent
+            .children()
+            .find(|o| o.tag_name().name() == name)
+            .and_then(|o| o.text())
+            .map(|o| o.trim().to_string())
+    };
+    if is_top_level {
+        let Some(value) = node.text().and_then(|o| o.trim().parse::<f64>().ok()) else {
+            return;
+        };
+        let votes = node
+            .parent()
+            .and_then(|o| child_text(&o, "votes"))
+            .and_then(|o| o.replace(',', "").parse().ok());
+        details.add_rating("default", value, votes, 10.0, true);
+        return;
+    }
+    if node
+        .parent()
+        .is_none_or(|o| o.tag_name().name() != "ratings")
+    {
+        return;
+    }
+    let Some(value) = child_text(node, "value").and_then(|o| o.parse::<f64>().ok()) else {
+        return;
+    };
+    let votes = child_text(node, "votes").and_then(|o| o.replace(',', "").parse().ok());
+    details.add_rating(
+        node.attribute("name").unwrap_or("default"),
+        value,
+        votes,
+        node.attribute("max")
+            .and_then(|o| o.parse().ok())
+            .unwrap_or(10.0),
+        node.attribute("default") == Some("true"),
+    );
 }
 
 fn get_actor_name(node: &Node) -> Vec<String> {
@@ -235,7 +466,40 @@ fn get_actor_name(node: &Node) -> Vec<String> {
         .collect::<Vec<String>>()
 }
 
-fn get_poster_filename(media_source: &MediaSource) -> Vec<String> {
+// <set><name>..</name><overview>..</overview></set>, older NFO files only carry the name
+fn get_movie_set(node: &Node) -> Option<MovieSet> {
+    let child_text = |name: &str| {
+        node.children()
+            .find(|o| o.tag_name().name() == name)
+            .and_then(|o| o.text())
+            .map(|o| o.trim().to_string())
+            .unwrap_or_default()
+    };
+    let name = if node.children().any(|o| o.is_element()) {
+        child_text("name")
+    } else {
+        node.text().unwrap_or_default().trim().to_string()
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(MovieSet::new(name, child_text("overview")))
+}
+
+/// Name of the movie set a movie NFO file puts the movie in, without parsing the rest of the movie.
+pub(crate) fn read_movie_set(file_path: &Path) -> Option<String> {
+    let content = fs::read_to_string(file_path).ok()?;
+    let doc = roxmltree::Document::parse(&content).ok()?;
+    let set = doc
+        .root()
+        .children()
+        .find(|o| o.tag_name().name() == "movie")?
+        .children()
+        .find(|o| o.tag_name().name() == "set")?;
+    get_movie_set(&set).map(|o| o.name().to_string())
+}
+
+pub(crate) fn get_poster_filename(media_source: &MediaSource) -> Vec<String> {
     media_source
         .poster()
         .iter()
@@ -252,14 +516,13 @@ fn get_episode_filename(
     episode: &str,
 ) -> Option<String> {
     let nfo_stem = nfo_path.file_stem()?;
-    let pattern = format!("s{}e{}", season, episode);
     for media in media_source.media() {
         let media_path = Path::new(media);
         if let Some(media_stem) = media_path.file_stem() {
             let matches_stem = media_stem.eq(nfo_stem);
             let matches_pattern = media_stem
                 .to_str()
-                .is_some_and(|s| s.to_lowercase().contains(&pattern));
+                .is_some_and(|s| matches_episode(s, season, episode));
             if matches_stem || matches_pattern {
                 return media_path
                     .file_name()
@@ -297,7 +560,7 @@ mod tests {
 
         let result = parse_nfo(dir.path(), &nfo_path, &media_source)
             .unwrap()
-            .unwrap();
+            .remove(0);
         let item = result.movie().unwrap();
         assert_eq!(item.actors(), &["Actor One", "Actor Two", "Actor Three"]);
     }
@@ -321,7 +584,7 @@ mod tests {
 
         let result = parse_nfo(dir.path(), &nfo_path, &media_source)
             .unwrap()
-            .unwrap();
+            .remove(0);
         let item = result.tv_show(Some(&HashMap::new())).unwrap();
         assert_eq!(item.actors(), &["Actor A", "Actor B"]);
     }
@@ -347,6 +610,455 @@ mod tests {
         assert_eq!(result, Some("My Show s01e03 720p.mkv".to_string()));
     }
 
+    #[test]
+    fn parse_movie_nfo_reads_details() {
+        let dir = tempfile::tempdir().unwrap();
+        let nfo_content = r#"<movie>
+    <title>Heat</title>
+    <originaltitle>Heat</originaltitle>
+    <plot>A group of professional bank robbers.</plot>
+    <runtime>170</runtime>
+    <premiered>1995-12-15</premiered>
+    <director>Michael Mann</director>
+    <credits>Michael Mann</credits>
+    <country>United States of America</country>
+    <uniqueid type="imdb" default="true">tt0113277</uniqueid>
+    <uniqueid type="tmdb">949</uniqueid>
+    <ratings>
+        <rating name="imdb" max="10" default="true">
+            <value>8.3</value>
+            <votes>712,000</votes>
+        </rating>
+    </ratings>
+    <set><name>Heat Collection</name><overview>Not a plot</overview></set>
+</movie>"#;
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+        fs::write(dir.path().join("Heat").join("movie.nfo"), nfo_content).unwrap();
+
+        let result = parse_nfo(
+            dir.path(),
+            &OsString::from("Heat/movie.nfo"),
+            &MediaSource::default(),
+        )
+        .unwrap()
+        .remove(0);
+        let item = result.movie().unwrap();
+        assert_eq!(item.directors(), &["Michael Mann"]);
+        assert_eq!(item.countries(), &["United States of America"]);
+
+        let details: serde_json::Value = serde_json::from_str(item.details()).unwrap();
+        assert_eq!(details["plot"], "A group of professional bank robbers.");
+        assert_eq!(details["runtime"], "170");
+        assert_eq!(details["premiered"], "1995-12-15");
+        assert_eq!(details["credits"], serde_json::json!(["Michael Mann"]));
+        assert_eq!(
+            details["uniqueIds"],
+            serde_json::json!([
+                {"type": "imdb", "value": "tt0113277", "default": true},
+                {"type": "tmdb", "value": "949", "default": false}
+            ])
+        );
+        assert_eq!(
+            details["ratings"],
+            serde_json::json!([
+                {"name": "imdb", "value": 8.3, "votes": 712000, "max": 10.0, "default": true}
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_movie_nfo_reads_legacy_rating_and_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let nfo_content =
+            "<movie><title>Heat</title><id>tt0113277</id><rating>8.2</rating><votes>100</votes></movie>";
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+        fs::write(dir.path().join("Heat").join("movie.nfo"), nfo_content).unwrap();
+
+        let result = parse_nfo(
+            dir.path(),
+            &OsString::from("Heat/movie.nfo"),
+            &MediaSource::default(),
+        )
+        .unwrap()
+        .remove(0);
+        let details: serde_json::Value =
+            serde_json::from_str(result.movie().unwrap().details()).unwrap();
+        assert_eq!(details["uniqueIds"][0]["value"], "tt0113277");
+        assert_eq!(details["ratings"][0]["value"], 8.2);
+        assert_eq!(details["ratings"][0]["votes"], 100);
+    }
+
+    #[test]
+    fn parse_movie_nfo_reads_movie_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Alien")).unwrap();
+        fs::create_dir_all(dir.path().join("Aliens")).unwrap();
+        fs::write(
+            dir.path().join("Alien").join("movie.nfo"),
+            r#"<movie>
+    <title>Alien</title>
+    <set>
+        <name>Alien Collection</name>
+        <overview>The Alien franchise.</overview>
+    </set>
+</movie>"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("Aliens").join("movie.nfo"),
+            "<movie><title>Aliens</title><set>Alien Collection</set></movie>",
+        )
+        .unwrap();
+
+        let source = MediaSource::default();
+        let alien = parse_nfo(dir.path(), &OsString::from("Alien/movie.nfo"), &source)
+            .unwrap()
+            .remove(0);
+        let collection = alien.collection().unwrap();
+        assert_eq!(collection.name(), "Alien Collection");
+        assert_eq!(collection.overview(), "The Alien franchise.");
+        // the overview of the set is not the plot of the movie
+        assert_eq!(alien.movie().unwrap().details(), "");
+
+        let aliens = parse_nfo(dir.path(), &OsString::from("Aliens/movie.nfo"), &source)
+            .unwrap()
+            .remove(0);
+        let collection = aliens.collection().unwrap();
+        assert_eq!(collection.name(), "Alien Collection");
+        assert_eq!(collection.overview(), "");
+    }
+
+    #[test]
+    fn parse_movie_nfo_keys_artwork_by_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let nfo_content = r#"<movie>
+    <title>Heat</title>
+    <thumb aspect="poster">https://example.com/poster.jpg</thumb>
+    <thumb aspect="banner">https://example.com/banner.jpg</thumb>
+    <thumb aspect="keyart">https://example.com/keyart.jpg</thumb>
+    <fanart url="https://example.com/"><thumb>fanart.jpg</thumb></fanart>
+    <actor><name>Al Pacino</name><thumb>https://example.com/pacino.jpg</thumb></actor>
+</movie>"#;
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+        fs::write(dir.path().join("Heat").join("movie.nfo"), nfo_content).unwrap();
+        let mut media_source = MediaSource::default();
+        for file in [
+            "Heat/poster.jpg",
+            "Heat/Heat-clearlogo.png",
+            "Heat/Heat-thumb.jpg",
+        ] {
+            media_source.add_poster(OsString::from(file));
+        }
+
+        let result = parse_nfo(dir.path(), &OsString::from("Heat/movie.nfo"), &media_source)
+            .unwrap()
+            .remove(0);
+        let posters: serde_json::Value =
+            serde_json::from_str(result.movie().unwrap().posters()).unwrap();
+        assert_eq!(
+            posters,
+            serde_json::json!({
+                "main": "poster.jpg",
+                "clearlogo": "Heat-clearlogo.png",
+                "thumb": "Heat-thumb.jpg",
+                "banner": "https://example.com/banner.jpg",
+                "fanart": "https://example.com/fanart.jpg"
+            })
+        );
+        // only the images next to the movie are converted into covers
+        assert_eq!(result.local_artwork().count(), 3);
+    }
+
+    #[test]
+    fn parse_tvshow_nfo_keys_season_artwork() {
+        let dir = tempfile::tempdir().unwrap();
+        let nfo_content = r#"<tvshow>
+    <title>Show</title>
+    <thumb aspect="banner" type="season" season="1">season01-banner.jpg</thumb>
+    <thumb aspect="poster" type="season" season="-1">https://example.com/all.jpg</thumb>
+</tvshow>"#;
+        fs::create_dir_all(dir.path().join("Show")).unwrap();
+        fs::write(dir.path().join("Show").join("tvshow.nfo"), nfo_content).unwrap();
+        let mut media_source = MediaSource::default();
+        for file in [
+            "Show/poster.jpg",
+            "Show/season01-poster.jpg",
+            "Show/season-specials-fanart.jpg",
+            "Show/S01E01-thumb.jpg",
+        ] {
+            media_source.add_poster(OsString::from(file));
+        }
+
+        let result = parse_nfo(
+            dir.path(),
+            &OsString::from("Show/tvshow.nfo"),
+            &media_source,
+        )
+        .unwrap()
+        .remove(0);
+        let posters: serde_json::Value =
+            serde_json::from_str(result.tv_show(Some(&HashMap::new())).unwrap().posters()).unwrap();
+        assert_eq!(
+            posters,
+            serde_json::json!({
+                "main": "poster.jpg",
+                "01": "season01-poster.jpg",
+                "00-fanart": "season-specials-fanart.jpg",
+                "01-banner": "season01-banner.jpg",
+                "all": "https://example.com/all.jpg"
+            })
+        );
+        assert_eq!(result.local_artwork().count(), 4);
+    }
+
+    #[test]
+    fn parse_movie_nfo_reads_stream_details() {
+        let dir = tempfile::tempdir().unwrap();
+        let nfo_content = r#"<movie>
+    <title>Heat</title>
+    <fileinfo>
+        <streamdetails>
+            <video>
+                <codec>hevc</codec>
+                <aspect>2.40</aspect>
+                <width>3840</width>
+                <height>1600</height>
+                <durationinseconds>10227</durationinseconds>
+                <hdrtype>hdr10</hdrtype>
+            </video>
+            <audio><codec>truehd</codec><language>eng</language><channels>8</channels></audio>
+            <audio><codec>ac3</codec><language>ger</language><channels>6</channels></audio>
+            <audio><codec>ac3</codec><language>eng</language><channels>2</channels></audio>
+            <subtitle><language>eng</language></subtitle>
+        </streamdetails>
+    </fileinfo>
+</movie>"#;
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+        fs::write(dir.path().join("Heat").join("movie.nfo"), nfo_content).unwrap();
+
+        let result = parse_nfo(
+            dir.path(),
+            &OsString::from("Heat/movie.nfo"),
+            &MediaSource::default(),
+        )
+        .unwrap()
+        .remove(0);
+        let item = result.movie().unwrap();
+        assert_eq!(item.resolutions(), &["4K"]);
+        assert_eq!(item.codecs(), &["HEVC"]);
+        assert_eq!(item.languages(), &["eng", "ger"]);
+        // nested <codec> and <language> tags are not read as details of the movie
+        assert_eq!(item.details(), "");
+
+        let streams: serde_json::Value = serde_json::from_str(item.streams()).unwrap();
+        assert_eq!(streams["videoCodec"], "hevc");
+        assert_eq!(streams["hdrType"], "hdr10");
+        assert_eq!(streams["duration"], 10227);
+        assert_eq!(streams["audio"].as_array().unwrap().len(), 3);
+        assert_eq!(streams["audio"][0]["channels"], 8);
+        assert_eq!(streams["subtitles"], serde_json::json!(["eng"]));
+    }
+
+    #[test]
+    fn parse_tvshow_nfo_collects_stream_tags_of_its_episodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let show_dir = dir.path().join("Show");
+        fs::create_dir_all(&show_dir).unwrap();
+        fs::write(
+            show_dir.join("tvshow.nfo"),
+            "<tvshow><title>Show</title></tvshow>",
+        )
+        .unwrap();
+        let episode_nfo = |episode: &str, width: &str, language: &str| {
+            format!(
+                "<episodedetails><season>1</season><episode>{}</episode><fileinfo><streamdetails>\
+                 <video><codec>h264</codec><width>{}</width><duration>45</duration></video>\
+                 <audio><language>{}</language></audio></streamdetails></fileinfo></episodedetails>",
+                episode, width, language
+            )
+        };
+        fs::write(show_dir.join("S01E01.nfo"), episode_nfo("1", "1920", "eng")).unwrap();
+        fs::write(show_dir.join("S01E02.nfo"), episode_nfo("2", "1280", "eng")).unwrap();
+
+        let source = MediaSource::default();
+        let parse = |path: &str| {
+            parse_nfo(dir.path(), &OsString::from(path), &source)
+                .unwrap()
+                .remove(0)
+        };
+        let show = parse("Show/tvshow.nfo");
+        let pilot = parse("Show/S01E01.nfo");
+        let second = parse("Show/S01E02.nfo");
+        let seasons = HashMap::from([("01".to_string(), vec![&pilot, &second])]);
+        let item = show.tv_show(Some(&seasons)).unwrap();
+        let mut resolutions = item.resolutions().to_vec();
+        resolutions.sort();
+        assert_eq!(resolutions, vec!["1080p", "720p"]);
+        assert_eq!(item.codecs(), &["H.264"]);
+        assert_eq!(item.languages(), &["eng"]);
+
+        let seasons: serde_json::Value = serde_json::from_str(item.seasons()).unwrap();
+        assert_eq!(seasons["01"][0]["streams"]["duration"], 2700);
+    }
+
+    #[test]
+    fn parse_movie_nfo_collects_parts_and_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie_dir = dir.path().join("Heat");
+        fs::create_dir_all(&movie_dir).unwrap();
+        fs::write(
+            movie_dir.join("movie.nfo"),
+            "<movie><title>Heat</title></movie>",
+        )
+        .unwrap();
+
+        let mut media_source = MediaSource::default();
+        for file in [
+            "Heat/Heat-trailer.mkv",
+            "Heat/Heat.720p.part2.mkv",
+            "Heat/Heat.720p.part1.mkv",
+            "Heat/Heat.1080p.mkv",
+        ] {
+            media_source.add_media(OsString::from(file));
+        }
+
+        let result = parse_nfo(dir.path(), &OsString::from("Heat/movie.nfo"), &media_source)
+            .unwrap()
+            .remove(0);
+        assert_eq!(result.file(), "Heat.1080p.mkv");
+        let versions = result
+            .versions()
+            .iter()
+            .map(|o| (o.label(), o.parts().len()))
+            .collect::<Vec<_>>();
+        assert_eq!(versions, vec![("1080p", 1), ("720p", 2)]);
+    }
+
+    #[test]
+    fn parse_movie_nfo_plays_disc_structures_from_their_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie_dir = dir.path().join("Heat");
+        fs::create_dir_all(&movie_dir).unwrap();
+        fs::write(
+            movie_dir.join("movie.nfo"),
+            "<movie><title>Heat</title></movie>",
+        )
+        .unwrap();
+
+        let mut media_source = MediaSource::default();
+        media_source.add_media(OsString::from("Heat/VIDEO_TS/VIDEO_TS.IFO"));
+
+        let result = parse_nfo(dir.path(), &OsString::from("Heat/movie.nfo"), &media_source)
+            .unwrap()
+            .remove(0);
+        assert_eq!(result.file(), "VIDEO_TS/VIDEO_TS.IFO");
+    }
+
+    #[test]
+    fn parse_episode_nfo_reads_still_and_rating() {
+        let dir = tempfile::tempdir().unwrap();
+        let nfo_content = r#"<episodedetails>
+    <title>Pilot</title>
+    <season>1</season>
+    <episode>1</episode>
+    <plot>It begins.</plot>
+    <aired>2008-01-20</aired>
+    <runtime>58</runtime>
+    <thumb>https://example.com/still.jpg</thumb>
+    <ratings>
+        <rating name="tmdb" max="10"><value>7.9</value></rating>
+        <rating name="imdb" max="10" default="true"><value>8.2</value></rating>
+    </ratings>
+    <actor><name>Bryan Cranston</name><thumb>https://example.com/cranston.jpg</thumb></actor>
+</episodedetails>"#;
+        fs::create_dir_all(dir.path().join("Show")).unwrap();
+        fs::write(dir.path().join("Show").join("Show S01E01.nfo"), nfo_content).unwrap();
+        let mut media_source = MediaSource::default();
+        media_source.add_media(OsString::from("Show/Show S01E01.mkv"));
+        media_source.add_poster(OsString::from("Show/Show S01E01-thumb.jpg"));
+
+        let episode = parse_nfo(
+            dir.path(),
+            &OsString::from("Show/Show S01E01.nfo"),
+            &media_source,
+        )
+        .unwrap()
+        .remove(0);
+        let mut show = Media::default();
+        show.set_media_type(MediaType::TvShow);
+        let seasons = HashMap::from([("01".to_string(), vec![&episode])]);
+        let item = show.tv_show(Some(&seasons)).unwrap();
+        let seasons: serde_json::Value = serde_json::from_str(item.seasons()).unwrap();
+        let json = &seasons["01"][0];
+        assert_eq!(json["thumb"], "Show S01E01-thumb.jpg");
+        assert_eq!(json["plot"], "It begins.");
+        assert_eq!(json["aired"], "2008-01-20");
+        assert_eq!(json["runtime"], "58");
+        assert_eq!(json["rating"], 8.2);
+
+        // without a still next to the video the one of the NFO file is used
+        let media_source = MediaSource::default();
+        let episode = parse_nfo(
+            dir.path(),
+            &OsString::from("Show/Show S01E01.nfo"),
+            &media_source,
+        )
+        .unwrap()
+        .remove(0);
+        let seasons = HashMap::from([("01".to_string(), vec![&episode])]);
+        let item = show.tv_show(Some(&seasons)).unwrap();
+        let seasons: serde_json::Value = serde_json::from_str(item.seasons()).unwrap();
+        assert_eq!(seasons["01"][0]["thumb"], "https://example.com/still.jpg");
+    }
+
+    #[test]
+    fn parse_nfo_reads_every_episode_of_a_multi_episode_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let nfo_content = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<episodedetails>
+    <title>Part One</title>
+    <season>2</season>
+    <episode>5</episode>
+</episodedetails>
+<episodedetails>
+    <title>Part Two</title>
+    <season>2</season>
+    <episode>6</episode>
+</episodedetails>"#;
+
+        let show_dir = dir.path().join("Show");
+        fs::create_dir_all(&show_dir).unwrap();
+        fs::write(show_dir.join("Show.S02E05E06.nfo"), nfo_content).unwrap();
+
+        let nfo_path = OsString::from("Show/Show.S02E05E06.nfo");
+        let mut media_source = MediaSource::default();
+        media_source.add_media(OsString::from("Show/Show.S02E05E06.mkv"));
+
+        let result = parse_nfo(dir.path(), &nfo_path, &media_source).unwrap();
+        let episodes = result
+            .iter()
+            .map(|o| (o.title(), o.episode(), o.file()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            episodes,
+            vec![
+                ("Part One", "05", "Show.S02E05E06.mkv"),
+                ("Part Two", "06", "Show.S02E05E06.mkv")
+            ]
+        );
+    }
+
+    #[test]
+    fn get_episode_filename_matches_multi_episode_files() {
+        let nfo_path = Path::new("Show/episode.nfo");
+        let mut source = MediaSource::default();
+        source.add_media(OsString::from("Show/S02E04.mkv"));
+        source.add_media(OsString::from("Show/S02E05-E06.mkv"));
+
+        let result = get_episode_filename(nfo_path, &source, "02", "06");
+        assert_eq!(result, Some("S02E05-E06.mkv".to_string()));
+    }
+
     #[test]
     fn get_episode_filename_returns_none_when_no_match() {
         let nfo_path = Path::new("Show/S01E01.nfo");
@@ -0,0 +1,291 @@
+use std::{collections::HashMap, path::Path};
+
+use log::error;
+use serde_json::Value;
+
+use crate::helper::main::get_disc_parent;
+use crate::model::parser::{FileFingerprint, Media, MediaProbe, MediaType, StreamDetails};
+use crate::parser::utilities;
+
+/// Fill in the stream details of a movie or an episode its NFO file does not describe, from the
+/// ffprobe output of its video. The cached output is reused while the video is unchanged, only
+/// the output of a video probed by this call is returned.
+pub fn probe_media(
+    root_path: &Path,
+    media: &mut Media,
+    fingerprints: &HashMap<&str, &FileFingerprint>,
+    cache: &HashMap<String, MediaProbe>,
+) -> Option<MediaProbe> {
+    if !matches!(media.media_type(), MediaType::Movie | MediaType::Episode)
+        || !media.streams().is_empty()
+    {
+        return None;
+    }
+    let video = media.video_path();
+    // disc structures are spread over many streams, ffprobe can not tell the length of the movie
+    if get_disc_parent(&video).is_some() {
+        return None;
+    }
+    let fingerprint = fingerprints.get(&*video.to_string_lossy())?;
+
+    let (output, probe) = match cache.get(fingerprint.path()) {
+        Some(probe) if probe.is_current(fingerprint) => (probe.output().to_string(), None),
+        _ => {
+            let output = match utilities::probe_video(&root_path.join(&video).to_string_lossy()) {
+                Ok(output) => output,
+                Err(e) => {
+                    error!("{}", e);
+                    return None;
+                }
+            };
+            (output.clone(), Some(MediaProbe::new(fingerprint, output)))
+        }
+    };
+    match parse_probe_output(&output) {
+        Some(streams) => *media.streams_mut() = streams,
+        None => error!("Fail to read ffprobe output of {:?}", video),
+    }
+    probe
+}
+
+/// Read the stream details out of the JSON printed by
+/// `ffprobe -print_format json -show_format -show_streams`.
+pub(crate) fn parse_probe_output(output: &str) -> Option<StreamDetails> {
+    let probe: Value = serde_json::from_str(output).ok()?;
+    let text = |value: &Value, name: &str| {
+        value
+            .get(name)
+            .and_then(|o| o.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let number = |value: &Value, name: &str| value.get(name).and_then(|o| o.as_u64()).unwrap_or(0);
+    // ffprobe prints the numbers of the container as strings
+    let decimal = |value: &Value, name: &str| {
+        value
+            .get(name)
+            .and_then(|o| o.as_str())
+            .and_then(|o| o.parse::<f64>().ok())
+            .unwrap_or(0.0)
+    };
+    let language = |value: &Value| {
+        let language = value.get("tags").map(|o| text(o, "language"));
+        // undetermined streams are as good as unlabelled ones
+        language.filter(|o| o != "und").unwrap_or_default()
+    };
+
+    let mut streams = StreamDetails::default();
+    if let Some(format) = probe.get("format") {
+        // e.g. `matroska,webm` or `mov,mp4,m4a,3gp,3g2,mj2`
+        let container = text(format, "format_name");
+        streams.set_container(container.split(',').next().unwrap_or_default().to_string());
+        streams.set_duration(decimal(format, "duration").round() as u32);
+        streams.set_bit_rate(decimal(format, "bit_rate") as u64);
+    }
+
+    let mut has_video = false;
+    for stream in probe
+        .get("streams")
+        .and_then(|o| o.as_array())
+        .into_iter()
+        .flatten()
+    {
+        match stream.get("codec_type").and_then(|o| o.as_str()) {
+            // cover art is attached to the container as a video stream of a single picture
+            Some("video")
+                if !has_video
+                    && stream
+                        .get("disposition")
+                        .map(|o| number(o, "attached_pic"))
+                        .unwrap_or(0)
+                        == 0 =>
+            {
+                has_video = true;
+                let width = number(stream, "width") as u32;
+                let height = number(stream, "height") as u32;
+                streams.set_video_codec(text(stream, "codec_name"));
+                streams.set_size(width, height);
+                streams.set_aspect(get_aspect(
+                    &text(stream, "display_aspect_ratio"),
+                    width,
+                    height,
+                ));
+                streams.set_hdr_type(get_hdr_type(stream).to_string());
+            }
+            Some("audio") => streams.add_audio(
+                text(stream, "codec_name"),
+                language(stream),
+                number(stream, "channels") as u32,
+            ),
+            Some("subtitle") => streams.add_subtitle(language(stream)),
+            _ => {}
+        }
+    }
+    Some(streams)
+}
+
+/// Aspect ratio the way NFO files write it, e.g. `1.78` for a display aspect ratio of `16:9`.
+fn get_aspect(display_aspect_ratio: &str, width: u32, height: u32) -> String {
+    let ratio = display_aspect_ratio
+        .split_once(':')
+        .and_then(|(w, h)| Some((w.parse::<f64>().ok()?, h.parse::<f64>().ok()?)))
+        .filter(|(w, h)| *w > 0.0 && *h > 0.0)
+        .or(Some((width as f64, height as f64)).filter(|(_, h)| *h > 0.0));
+    match ratio {
+        Some((w, h)) => format!("{:.2}", w / h),
+        None => String::new(),
+    }
+}
+
+/// HDR format of a video stream under the names Kodi uses for `<hdrtype>`.
+fn get_hdr_type(stream: &Value) -> &'static str {
+    let is_dolby_vision = stream
+        .get("side_data_list")
+        .and_then(|o| o.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|o| o.get("side_data_type").and_then(|o| o.as_str()))
+        .any(|o| o.starts_with("DOVI"));
+    if is_dolby_vision {
+        return "dolbyvision";
+    }
+    match stream.get("color_transfer").and_then(|o| o.as_str()) {
+        Some("smpte2084") => "hdr10",
+        Some("arib-std-b67") => "hlg",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROBE_OUTPUT: &str = r#"{
+        "streams": [
+            {
+                "index": 0,
+                "codec_name": "hevc",
+                "codec_type": "video",
+                "width": 3840,
+                "height": 1606,
+                "display_aspect_ratio": "12:5",
+                "color_transfer": "smpte2084",
+                "disposition": { "default": 1, "attached_pic": 0 }
+            },
+            {
+                "index": 1,
+                "codec_name": "eac3",
+                "codec_type": "audio",
+                "channels": 6,
+                "tags": { "language": "eng" }
+            },
+            {
+                "index": 2,
+                "codec_name": "aac",
+                "codec_type": "audio",
+                "channels": 2,
+                "tags": { "language": "und" }
+            },
+            {
+                "index": 3,
+                "codec_name": "subrip",
+                "codec_type": "subtitle",
+                "tags": { "language": "fre" }
+            },
+            {
+                "index": 4,
+                "codec_name": "mjpeg",
+                "codec_type": "video",
+                "width": 600,
+                "height": 900,
+                "disposition": { "default": 0, "attached_pic": 1 }
+            }
+        ],
+        "format": {
+            "format_name": "matroska,webm",
+            "duration": "10239.604000",
+            "bit_rate": "18204512"
+        }
+    }"#;
+
+    #[test]
+    fn parse_probe_output_reads_format_and_streams() {
+        let mut expected = StreamDetails::default();
+        expected.set_container("matroska".to_string());
+        expected.set_duration(10240);
+        expected.set_bit_rate(18204512);
+        expected.set_video_codec("hevc".to_string());
+        expected.set_size(3840, 1606);
+        expected.set_aspect("2.40".to_string());
+        expected.set_hdr_type("hdr10".to_string());
+        expected.add_audio("eac3".to_string(), "eng".to_string(), 6);
+        expected.add_audio("aac".to_string(), String::new(), 2);
+        expected.add_subtitle("fre".to_string());
+
+        let streams = parse_probe_output(PROBE_OUTPUT).unwrap();
+        assert_eq!(streams, expected);
+        assert_eq!(streams.resolution(), Some("4K"));
+        assert_eq!(streams.audio_languages(), vec!["eng".to_string()]);
+    }
+
+    #[test]
+    fn parse_probe_output_detects_dolby_vision_and_falls_back_to_frame_aspect() {
+        let output = r#"{
+            "streams": [{
+                "codec_name": "h264",
+                "codec_type": "video",
+                "width": 1280,
+                "height": 720,
+                "display_aspect_ratio": "0:1",
+                "color_transfer": "smpte2084",
+                "side_data_list": [{ "side_data_type": "DOVI configuration record" }]
+            }],
+            "format": { "format_name": "mov,mp4,m4a,3gp,3g2,mj2" }
+        }"#;
+        let mut expected = StreamDetails::default();
+        expected.set_container("mov".to_string());
+        expected.set_video_codec("h264".to_string());
+        expected.set_size(1280, 720);
+        expected.set_aspect("1.78".to_string());
+        expected.set_hdr_type("dolbyvision".to_string());
+
+        assert_eq!(parse_probe_output(output), Some(expected));
+        assert_eq!(parse_probe_output("not json"), None);
+    }
+
+    #[test]
+    fn probe_media_reuses_cached_output_of_unchanged_videos() {
+        let mut media = Media::default();
+        media.set_media_type(MediaType::Movie);
+        media.set_relative_path("Heat".into());
+        media.set_file("Heat.mkv".to_string());
+        let path = Path::new("Heat").join("Heat.mkv");
+        let fingerprint = FileFingerprint::new(path.to_string_lossy().into_owned(), 10, 20);
+        let fingerprints = HashMap::from([(fingerprint.path(), &fingerprint)]);
+        let cache = HashMap::from([(
+            fingerprint.path().to_string(),
+            MediaProbe::new(&fingerprint, PROBE_OUTPUT.to_string()),
+        )]);
+
+        // the cached output is read without running ffprobe
+        let probe = probe_media(Path::new("/library"), &mut media, &fingerprints, &cache);
+        assert_eq!(probe, None);
+        assert_eq!(media.streams().resolution(), Some("4K"));
+
+        // disc structures are not probed
+        let mut disc = Media::default();
+        disc.set_media_type(MediaType::Movie);
+        disc.set_relative_path("Ronin".into());
+        disc.set_file(
+            Path::new("BDMV")
+                .join("index.bdmv")
+                .to_string_lossy()
+                .into_owned(),
+        );
+        assert_eq!(
+            probe_media(Path::new("/library"), &mut disc, &fingerprints, &cache),
+            None
+        );
+        assert!(disc.streams().is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::error;
+
+use crate::model::parser::FileFingerprint;
+use crate::parser::{probe::parse_probe_output, utilities};
+
+const INTERVAL: u32 = 10; // seconds between thumbnails
+const TILE_WIDTH: u32 = 160;
+const COLUMNS: u32 = 10;
+const ROWS: u32 = 10;
+
+pub const INDEX_FILE: &str = "index.vtt";
+
+/// Directory holding the sprite sheets and the index of `video`, a path relative to the library.
+pub fn get_trickplay_dir(app_dir: &Path, folder_name: &str, video: &Path) -> PathBuf {
+    app_dir.join("trickplay").join(folder_name).join(video)
+}
+
+/// Whether the sprites were generated after the video was last modified.
+pub fn is_current(dest_dir: &Path, video: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|o| o.modified()).ok();
+    match (modified(&dest_dir.join(INDEX_FILE)), modified(video)) {
+        (Some(index), Some(video)) => index >= video,
+        _ => false,
+    }
+}
+
+/// Size and modification time of the video, sprites that failed are only tried again once they
+/// change.
+pub fn get_video_fingerprint(video: &Path) -> Result<FileFingerprint, String> {
+    fs::metadata(video)
+        .map(|o| utilities::get_fingerprint(video, &o))
+        .map_err(|e| format!("Fail to read video {:?}. Raising error {}", video, e))
+}
+
+/// Generate the sprite sheets of the video with their WebVTT index, replacing older ones. The
+/// index is written last, so the sprites are complete once it exists.
+pub fn generate_trickplay(video: &Path, dest_dir: &Path) -> Result<(), String> {
+    let output = utilities::probe_video(&video.to_string_lossy())?;
+    let duration = parse_probe_output(&output)
+        .map(|o| o.duration())
+        .unwrap_or_default();
+    if duration == 0 {
+        return Err(format!("Fail to read the length of video {:?}", video));
+    }
+
+    if dest_dir.exists() {
+        fs::remove_dir_all(dest_dir).map_err(|e| {
+            format!(
+                "Fail to remove trickplay directory {:?}. Raising error {}",
+                dest_dir, e
+            )
+        })?;
+    }
+    fs::create_dir_all(dest_dir).map_err(|e| {
+        format!(
+            "Fail to create trickplay directory {:?}. Raising error {}",
+            dest_dir, e
+        )
+    })?;
+
+    utilities::generate_sprites(
+        &video.to_string_lossy(),
+        &dest_dir.join("sprite-%d.jpg").to_string_lossy(),
+        INTERVAL,
+        TILE_WIDTH,
+        COLUMNS,
+        ROWS,
+    )?;
+
+    let sprites = (0..)
+        .take_while(|o| dest_dir.join(get_sprite_name(*o)).exists())
+        .count();
+    // every sheet is padded to the full grid, so the first one tells the size of a tile
+    let (width, height) = image::image_dimensions(dest_dir.join(get_sprite_name(0)))
+        .map_err(|e| format!("Fail to read sprites of {:?}. Raising error {}", video, e))?;
+    let index = build_index(duration, sprites, width / COLUMNS, height / ROWS);
+    fs::write(dest_dir.join(INDEX_FILE), index)
+        .map_err(|e| format!("Fail to write trickplay index. Raising error {}", e))
+}
+
+/// Drop the sprites of the removed videos.
+pub fn remove_trickplay(app_dir: &Path, folder_name: &str, removed_files: &[String]) {
+    for file in removed_files {
+        let dest_dir = get_trickplay_dir(app_dir, folder_name, Path::new(file));
+        if dest_dir.is_dir() {
+            if let Err(e) = fs::remove_dir_all(&dest_dir) {
+                error!(
+                    "Fail to remove trickplay directory {:?}. Raising error {}",
+                    dest_dir, e
+                );
+            }
+        }
+    }
+}
+
+fn get_sprite_name(index: usize) -> String {
+    format!("sprite-{}.jpg", index)
+}
+
+/// WebVTT cue for every thumbnail, pointing to its tile with a media fragment.
+fn build_index(duration: u32, sprites: usize, tile_width: u32, tile_height: u32) -> String {
+    let tiles = (COLUMNS * ROWS) as usize;
+    let count = (duration.div_ceil(INTERVAL) as usize).min(sprites * tiles);
+    let mut index = String::from("WEBVTT\n");
+    for i in 0..count {
+        let start = i as u32 * INTERVAL;
+        let tile = (i % tiles) as u32;
+        index.push_str(&format!(
+            "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+            format_timestamp(start),
+            format_timestamp((start + INTERVAL).min(duration)),
+            get_sprite_name(i / tiles),
+            tile % COLUMNS * tile_width,
+            tile / COLUMNS * tile_height,
+            tile_width,
+            tile_height
+        ));
+    }
+    index
+}
+
+fn format_timestamp(seconds: u32) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.000",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_index_points_cues_to_tiles() {
+        let index = build_index(1005, 2, 160, 90);
+        assert!(index.starts_with(
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:10.000\nsprite-0.jpg#xywh=0,0,160,90\n\n\
+             00:00:10.000 --> 00:00:20.000\nsprite-0.jpg#xywh=160,0,160,90\n"
+        ));
+        assert!(index.contains("00:01:40.000 --> 00:01:50.000\nsprite-0.jpg#xywh=0,90,160,90\n"));
+        assert!(index.ends_with("00:16:40.000 --> 00:16:45.000\nsprite-1.jpg#xywh=0,0,160,90\n"));
+        assert_eq!(index.matches(" --> ").count(), 101);
+
+        // cues never point past the generated sprites
+        assert_eq!(build_index(7200, 1, 160, 90).matches(" --> ").count(), 100);
+    }
+
+    #[test]
+    fn is_current_compares_against_the_video() {
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("Heat.mkv");
+        let dest_dir = get_trickplay_dir(dir.path(), "Movies", Path::new("Heat.mkv"));
+        fs::write(&video, "video").unwrap();
+        assert!(!is_current(&dest_dir, &video));
+
+        fs::create_dir_all(&dest_dir).unwrap();
+        fs::write(dest_dir.join(INDEX_FILE), "WEBVTT\n").unwrap();
+        assert!(is_current(&dest_dir, &video));
+
+        remove_trickplay(dir.path(), "Movies", &["Heat.mkv".to_string()]);
+        assert!(!dest_dir.exists());
+    }
+}
@@ -1,49 +1,150 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::process::Output;
+use std::time::UNIX_EPOCH;
 use std::{fs, path::Path, path::PathBuf, process::Command};
 
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader, ImageResult};
+use log::debug;
+
+use crate::model::parser::{CoverFormat, FileFingerprint};
+
+// covers fit into 320x480, keeping the aspect ratio of the image
+const COVER_WIDTH: u32 = 320;
+const COVER_HEIGHT: u32 = 480;
+const COVER_SCALE: &str = "scale='if(gt(a,320/480),320,-2)':'if(gt(a,320/480),-2,480)'";
+
 pub fn get_relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
     path.strip_prefix(base).ok().map(|p| p.to_path_buf())
 }
 
+pub fn get_fingerprint(relative_path: &Path, metadata: &fs::Metadata) -> FileFingerprint {
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default();
+    FileFingerprint::new(
+        relative_path.to_string_lossy().into_owned(),
+        metadata.len() as i64,
+        mtime,
+    )
+}
+
 #[cfg(target_os = "windows")]
-fn execute_command(args: &[&str]) -> Result<Output, String> {
+fn execute_command(program: &str, args: &[&str]) -> Result<Output, String> {
     use std::os::windows::process::CommandExt;
-    Command::new("ffmpeg")
+    Command::new(program)
         .args(args)
         // https://learn.microsoft.com/en-us/windows/win32/procthread/process-creation-flags
         .creation_flags(0x08000000)
         .output()
-        .map_err(|e| format!("Failed to execute ffmpeg: {}", e))
+        .map_err(|e| format!("Failed to execute {}: {}", program, e))
 }
 
 #[cfg(not(target_os = "windows"))]
-fn execute_command(args: &[&str]) -> Result<Output, String> {
-    Command::new("ffmpeg")
+fn execute_command(program: &str, args: &[&str]) -> Result<Output, String> {
+    Command::new(program)
         .args(args)
         .output()
-        .map_err(|e| format!("Failed to execute ffmpeg: {}", e))
+        .map_err(|e| format!("Failed to execute {}: {}", program, e))
 }
 
-pub fn convert_image(src_path: &str, dst_path: &str) -> Result<(), String> {
-    let dst_conversion_path = format!("{}.avif", dst_path);
+/// Shrink the image into the box of a cover and save it in `format`. JPEG, PNG and WebP covers are
+/// decoded and encoded in-process, ffmpeg is only needed for the AVIF encoder and images the image
+/// crate can not decode.
+pub fn convert_image(src_path: &str, dst_path: &str, format: CoverFormat) -> Result<(), String> {
+    if !format.needs_ffmpeg() {
+        match resize_image(src_path, dst_path, format) {
+            Ok(()) => return Ok(()),
+            Err(e) => debug!("{}, converting with ffmpeg instead", e),
+        }
+    }
+    convert_image_with_ffmpeg(src_path, dst_path, format)
+}
 
-    let args = vec![
-        "-y",
-        "-i",
-        src_path,
-        "-c:v",
-        "libsvtav1",
+fn resize_image(src_path: &str, dst_path: &str, format: CoverFormat) -> Result<(), String> {
+    let encode: fn(&DynamicImage, &mut BufWriter<File>) -> ImageResult<()> = match format {
+        CoverFormat::Jpeg => |cover: &DynamicImage, writer: &mut BufWriter<File>| {
+            DynamicImage::from(cover.to_rgb8())
+                .write_with_encoder(JpegEncoder::new_with_quality(writer, 85))
+        },
+        CoverFormat::Png => |cover: &DynamicImage, writer: &mut BufWriter<File>| {
+            cover.write_with_encoder(PngEncoder::new(writer))
+        },
+        // the image crate only writes lossless WebP
+        CoverFormat::WebP => |cover: &DynamicImage, writer: &mut BufWriter<File>| {
+            DynamicImage::from(cover.to_rgba8())
+                .write_with_encoder(WebPEncoder::new_lossless(writer))
+        },
+        CoverFormat::Avif => {
+            return Err(format!("{} covers are encoded by ffmpeg", format.as_str()));
+        }
+    };
+
+    let image = ImageReader::open(src_path)
+        .and_then(|o| o.with_guessed_format())
+        .map_err(|e| format!("Fail to read image {}. Raising error {}", src_path, e))?
+        .decode()
+        .map_err(|e| format!("Fail to decode image {}. Raising error {}", src_path, e))?;
+    let cover = image.resize(COVER_WIDTH, COVER_HEIGHT, FilterType::Triangle);
+
+    // the source may be the destination, so it is only replaced once the cover is complete
+    let dst_conversion_path = format!("{}.{}", dst_path, format.extension());
+    let mut writer = File::create(&dst_conversion_path)
+        .map(BufWriter::new)
+        .map_err(|e| {
+            format!(
+                "Fail to create file {:?}. Raising error {}",
+                &dst_conversion_path, e
+            )
+        })?;
+    let result = encode(&cover, &mut writer)
+        .map_err(|e| e.to_string())
+        .and_then(|_| writer.flush().map_err(|e| e.to_string()));
+    drop(writer);
+    if let Err(e) = result {
+        let _ = fs::remove_file(&dst_conversion_path);
+        return Err(format!(
+            "Fail to encode image {}. Raising error {}",
+            src_path, e
+        ));
+    }
+
+    if let Err(e) = fs::rename(&dst_conversion_path, dst_path) {
+        return Err(format!(
+            "Fail to rename file from {:?} to {:?}. Raising error {}",
+            &dst_conversion_path, dst_path, e
+        ));
+    }
+
+    Ok(())
+}
+
+fn convert_image_with_ffmpeg(
+    src_path: &str,
+    dst_path: &str,
+    format: CoverFormat,
+) -> Result<(), String> {
+    let dst_conversion_path = format!("{}.{}", dst_path, format.extension());
+
+    let mut args = vec!["-y", "-i", src_path];
+    args.extend(get_codec_args(format));
+    args.extend([
         "-an",
-        "-preset",
-        "4",
         "-vf",
-        "scale='if(gt(a,320/480),320,-2)':'if(gt(a,320/480),-2,480)'",
+        COVER_SCALE,
         "-loglevel",
         "fatal",
         &dst_conversion_path,
-    ];
+    ]);
 
-    let output = execute_command(&args)?;
+    let output = execute_command("ffmpeg", &args)?;
 
     if !&output.status.success() {
         return Err(format!(
@@ -61,3 +162,192 @@ pub fn convert_image(src_path: &str, dst_path: &str) -> Result<(), String> {
 
     Ok(())
 }
+
+fn get_codec_args(format: CoverFormat) -> [&'static str; 4] {
+    match format {
+        CoverFormat::Jpeg => ["-c:v", "mjpeg", "-q:v", "3"],
+        CoverFormat::Png => ["-c:v", "png", "-pred", "mixed"],
+        CoverFormat::WebP => ["-c:v", "libwebp", "-quality", "85"],
+        CoverFormat::Avif => ["-c:v", "libsvtav1", "-preset", "4"],
+    }
+}
+
+/// Save a representative frame of the video from `offset` seconds on as a cover. Frames that are
+/// mostly black are dropped, the `thumbnail` filter then picks the most typical of the next ones.
+pub fn grab_frame(
+    src_path: &str,
+    dst_path: &str,
+    offset: u32,
+    format: CoverFormat,
+) -> Result<(), String> {
+    let dst_conversion_path = format!("{}.{}", dst_path, format.extension());
+    let offset = offset.to_string();
+    let filter = format!(
+        "blackframe=amount=0,\
+         metadata=select:key=lavfi.blackframe.pblack:value=90:function=less,\
+         thumbnail=50,\
+         {}",
+        COVER_SCALE
+    );
+
+    let mut args = vec!["-y", "-ss", &offset, "-i", src_path];
+    args.extend(get_codec_args(format));
+    args.extend([
+        "-an",
+        "-vf",
+        &filter,
+        "-frames:v",
+        "1",
+        "-loglevel",
+        "fatal",
+        &dst_conversion_path,
+    ]);
+
+    let output = execute_command("ffmpeg", &args)?;
+
+    if !&output.status.success() {
+        return Err(format!(
+            "Fail to grab frame of {}. Raising error {}",
+            src_path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    // nothing is written when the offset lies past the end of the video
+    if let Err(e) = fs::rename(&dst_conversion_path, dst_path) {
+        return Err(format!(
+            "Fail to rename file from {:?} to {:?}. Raising error {}",
+            &dst_conversion_path, dst_path, e
+        ));
+    }
+
+    Ok(())
+}
+
+/// Save a thumbnail of the video every `interval` seconds, `width` pixels wide, tiled into sprite
+/// sheets of `columns` by `rows`. Only key frames are decoded, so thumbnails land on the nearest one.
+pub fn generate_sprites(
+    src_path: &str,
+    dst_pattern: &str,
+    interval: u32,
+    width: u32,
+    columns: u32,
+    rows: u32,
+) -> Result<(), String> {
+    let filter = format!(
+        "fps=1/{},scale={}:-2,tile={}x{}",
+        interval, width, columns, rows
+    );
+
+    let args = vec![
+        "-y",
+        "-skip_frame",
+        "nokey",
+        "-i",
+        src_path,
+        "-an",
+        "-sn",
+        "-vf",
+        &filter,
+        "-q:v",
+        "5",
+        "-start_number",
+        "0",
+        "-loglevel",
+        "fatal",
+        dst_pattern,
+    ];
+
+    let output = execute_command("ffmpeg", &args)?;
+
+    if !&output.status.success() {
+        return Err(format!(
+            "Fail to generate sprites of {}. Raising error {}",
+            src_path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Container and stream information of a video as ffprobe JSON.
+pub fn probe_video(src_path: &str) -> Result<String, String> {
+    let args = vec![
+        "-v",
+        "error",
+        "-print_format",
+        "json",
+        "-show_format",
+        "-show_streams",
+        src_path,
+    ];
+
+    let output = execute_command("ffprobe", &args)?;
+
+    if !&output.status.success() {
+        return Err(format!(
+            "Fail to probe video {}. Raising error {}",
+            src_path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, ImageFormat, RgbImage};
+
+    #[test]
+    fn convert_image_resizes_covers_in_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("poster.png");
+        RgbImage::new(1000, 500).save(&src_path).unwrap();
+        let src_path = src_path.to_string_lossy();
+
+        for (format, image_format) in [
+            (CoverFormat::Jpeg, ImageFormat::Jpeg),
+            (CoverFormat::Png, ImageFormat::Png),
+            (CoverFormat::WebP, ImageFormat::WebP),
+        ] {
+            let dst_path = dir.path().join(format.as_str());
+            convert_image(&src_path, &dst_path.to_string_lossy(), format).unwrap();
+
+            let content = fs::read(&dst_path).unwrap();
+            assert_eq!(image::guess_format(&content).unwrap(), image_format);
+            let cover = image::load_from_memory(&content).unwrap();
+            assert_eq!(cover.dimensions(), (320, 160));
+        }
+    }
+
+    #[test]
+    fn resize_image_leaves_avif_to_ffmpeg() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("poster.png");
+        RgbImage::new(10, 10).save(&src_path).unwrap();
+        let src_path = src_path.to_string_lossy();
+
+        let dst_path = dir.path().join("avif");
+        assert!(resize_image(&src_path, &dst_path.to_string_lossy(), CoverFormat::Avif).is_err());
+        assert!(!dst_path.exists());
+    }
+
+    #[test]
+    fn convert_image_replaces_the_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cover");
+        RgbImage::new(300, 900)
+            .save_with_format(&path, ImageFormat::Png)
+            .unwrap();
+        let path = path.to_string_lossy();
+
+        convert_image(&path, &path, CoverFormat::Jpeg).unwrap();
+
+        let cover = image::load_from_memory(&fs::read(&*path).unwrap()).unwrap();
+        assert_eq!(cover.dimensions(), (160, 480));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,289 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader};
+use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::db;
+use crate::model::parser::MediaType;
+use crate::parser::comic_parser::{get_comic_pages, read_comic_page};
+
+pub(super) type ApiError = (StatusCode, String);
+
+#[derive(Deserialize)]
+struct ComicQuery {
+    folder: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    folder: String,
+    path: String,
+    index: usize,
+    // downscale the page to at most this width, keeping the aspect ratio
+    width: Option<u32>,
+}
+
+/// Routes reading the pages of indexed comics straight out of their archives.
+pub(super) fn router(pool: Pool<Sqlite>) -> Router {
+    Router::new()
+        .route("/pages", get(get_pages))
+        .route("/page", get(get_page))
+        .with_state(pool)
+}
+
+async fn get_pages(
+    State(pool): State<Pool<Sqlite>>,
+    Query(query): Query<ComicQuery>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let comic_path = resolve_comic(&pool, &query.folder, &query.path).await?;
+    let pages = tauri::async_runtime::spawn_blocking(move || get_comic_pages(&comic_path))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(pages))
+}
+
+async fn get_page(
+    State(pool): State<Pool<Sqlite>>,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let comic_path = resolve_comic(&pool, &query.folder, &query.path).await?;
+    let index = query.index;
+    let width = query.width;
+    let (content_type, content) = tauri::async_runtime::spawn_blocking(move || {
+        let Some((name, content)) = read_comic_page(&comic_path, index)? else {
+            return Ok(None);
+        };
+        match width {
+            Some(width) => downscale(&name, content, width).map(Some),
+            None => Ok(Some((get_content_type(&name), content))),
+        }
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e: String| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Page {} not found", index)))?;
+
+    Ok(([(header::CONTENT_TYPE, content_type)], content))
+}
+
+/// Absolute path of an indexed comic, making sure it resolves inside its library root.
+async fn resolve_comic(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+    path: &str,
+) -> Result<PathBuf, ApiError> {
+    let root_path = db::main::get_media_library_path(pool, folder_name, path, MediaType::Comic)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Comic {} is not indexed in {}", path, folder_name),
+            )
+        })?;
+    resolve_library_path(Path::new(&root_path), path)
+}
+
+pub(super) fn resolve_library_path(root_path: &Path, path: &str) -> Result<PathBuf, ApiError> {
+    let not_found = |e: std::io::Error| (StatusCode::NOT_FOUND, e.to_string());
+    let root_path = root_path.canonicalize().map_err(not_found)?;
+    // canonicalize resolves `..` and symlinks before the prefix check
+    let full_path = root_path.join(path).canonicalize().map_err(not_found)?;
+    if !full_path.starts_with(&root_path) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("{} is outside of the library", path),
+        ));
+    }
+    Ok(full_path)
+}
+
+/// Shrink the page to `width` pixels wide. Pages already narrow enough are returned untouched.
+fn downscale(name: &str, content: Vec<u8>, width: u32) -> Result<(&'static str, Vec<u8>), String> {
+    let reader = ImageReader::new(Cursor::new(&content))
+        .with_guessed_format()
+        .map_err(|e| format!("Fail to read page {}. Raising error {}", name, e))?;
+    let (original_width, _) = reader
+        .into_dimensions()
+        .map_err(|e| format!("Fail to read page {}. Raising error {}", name, e))?;
+    if width == 0 || original_width <= width {
+        return Ok((get_content_type(name), content));
+    }
+
+    let page = image::load_from_memory(&content)
+        .map_err(|e| format!("Fail to decode page {}. Raising error {}", name, e))?;
+    let mut output = Cursor::new(Vec::new());
+    page.resize(width, u32::MAX, FilterType::Triangle)
+        .to_rgb8()
+        .write_to(&mut output, ImageFormat::Jpeg)
+        .map_err(|e| format!("Fail to encode page {}. Raising error {}", name, e))?;
+    Ok(("image/jpeg", output.into_inner()))
+}
+
+fn get_content_type(name: &str) -> &'static str {
+    let extension = Path::new(name)
+        .extension()
+        .map(|o| o.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("bmp") => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::main::{create_pool, create_tables, insert_folder_data, update_media};
+    use crate::model::parser::{Media, ScanResult};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use image::{GenericImageView, RgbImage};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io::Write;
+    use tower::ServiceExt;
+    use zip::write::SimpleFileOptions;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut content = Cursor::new(Vec::new());
+        RgbImage::new(width, height)
+            .write_to(&mut content, ImageFormat::Png)
+            .unwrap();
+        content.into_inner()
+    }
+
+    async fn setup(library: &Path) -> Pool<Sqlite> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in [
+            ("ComicInfo.xml", b"<ComicInfo/>".to_vec()),
+            ("page10.png", png(40, 20)),
+            ("page2.png", png(400, 200)),
+        ] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&content).unwrap();
+        }
+        fs::write(
+            library.join("Saga.cbz"),
+            writer.finish().unwrap().into_inner(),
+        )
+        .unwrap();
+
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        insert_folder_data(&pool, "Comics", &library.to_string_lossy())
+            .await
+            .unwrap();
+
+        let mut comic = Media::default();
+        comic.set_media_type(MediaType::Comic);
+        comic.set_title("Saga".to_string());
+        comic.set_relative_path(OsString::from("Saga.cbz"));
+        let items = vec![comic.comic().unwrap()];
+        update_media(
+            &pool,
+            "Comics",
+            &ScanResult::new(items, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
+        pool
+    }
+
+    async fn request(pool: Pool<Sqlite>, uri: &str) -> (StatusCode, Vec<u8>) {
+        let response = router(pool)
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn lists_pages_in_natural_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = setup(dir.path()).await;
+
+        let (status, body) = request(pool, "/pages?folder=Comics&path=Saga.cbz").await;
+        assert_eq!(status, StatusCode::OK);
+        let pages: Vec<String> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(pages, vec!["page2.png", "page10.png"]);
+    }
+
+    #[tokio::test]
+    async fn serves_page_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = setup(dir.path()).await;
+
+        let (status, body) =
+            request(pool.clone(), "/page?folder=Comics&path=Saga.cbz&index=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, png(40, 20));
+
+        let (status, _) = request(pool, "/page?folder=Comics&path=Saga.cbz&index=2").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn downscales_wide_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = setup(dir.path()).await;
+
+        let uri = "/page?folder=Comics&path=Saga.cbz&index=0&width=100";
+        let (status, body) = request(pool.clone(), uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            image::load_from_memory(&body).unwrap().dimensions(),
+            (100, 50)
+        );
+
+        // narrower pages are served as they are
+        let uri = "/page?folder=Comics&path=Saga.cbz&index=1&width=100";
+        let (_, body) = request(pool, uri).await;
+        assert_eq!(body, png(40, 20));
+    }
+
+    #[tokio::test]
+    async fn refuses_comics_that_are_not_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = setup(dir.path()).await;
+        fs::copy(dir.path().join("Saga.cbz"), dir.path().join("Other.cbz")).unwrap();
+
+        let (status, _) = request(pool.clone(), "/pages?folder=Comics&path=Other.cbz").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = request(pool, "/pages?folder=Movies&path=Saga.cbz").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn resolve_library_path_refuses_paths_outside_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("library");
+        fs::create_dir_all(&library).unwrap();
+        fs::write(dir.path().join("secret.cbz"), "").unwrap();
+        fs::write(library.join("Saga.cbz"), "").unwrap();
+
+        assert!(resolve_library_path(&library, "Saga.cbz").is_ok());
+        let (status, _) = resolve_library_path(&library, "../secret.cbz").unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let absolute = dir.path().join("secret.cbz");
+        let (status, _) = resolve_library_path(&library, &absolute.to_string_lossy()).unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}
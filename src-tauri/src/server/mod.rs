@@ -1,16 +1,17 @@
+mod comic;
+mod trickplay;
+
 use std::path::Path;
 
+use axum::http::{HeaderValue, Method};
 use axum::Router;
+use sqlx::{Pool, Sqlite};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
 use tower_http::set_header::SetResponseHeaderLayer;
 
-pub fn start(app_data_dir: &Path, port: u16) {
-    let router = Router::new()
-        .fallback_service(ServeDir::new(app_data_dir.join("covers")))
-        .layer(SetResponseHeaderLayer::if_not_present(
-            axum::http::header::CACHE_CONTROL,
-            axum::http::HeaderValue::from_static("public, max-age=3600"),
-        ));
+pub fn start(app_data_dir: &Path, port: u16, pool: Pool<Sqlite>) {
+    let router = router(app_data_dir, pool);
 
     // Bind synchronously so the port is listening before the WebView loads.
     let std_listener = std::net::TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
@@ -22,21 +23,54 @@ pub fn start(app_data_dir: &Path, port: u16) {
     });
 }
 
+// origins the webview loads the app from, tauri://localhost on macOS and Linux and
+// http(s)://tauri.localhost on Windows
+const APP_ORIGINS: &[&str] = &[
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+const DEV_ORIGIN: &str = "http://localhost:1420";
+
+/// Let the app, and only the app, fetch from the server, e.g. the pages of a comic.
+fn cors_layer() -> CorsLayer {
+    let origins = APP_ORIGINS
+        .iter()
+        .chain(cfg!(debug_assertions).then_some(&DEV_ORIGIN))
+        .copied()
+        .map(HeaderValue::from_static)
+        .collect::<Vec<HeaderValue>>();
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET])
+}
+
+// covers have a prefix of their own, so no folder name clashes with the other routes
+fn router(app_data_dir: &Path, pool: Pool<Sqlite>) -> Router {
+    Router::new()
+        .nest("/api/comic", comic::router(pool.clone()))
+        .nest("/api/trickplay", trickplay::router(pool, app_data_dir))
+        .nest_service("/trickplay", ServeDir::new(app_data_dir.join("trickplay")))
+        .nest_service("/covers", ServeDir::new(app_data_dir.join("covers")))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            axum::http::header::CACHE_CONTROL,
+            axum::http::HeaderValue::from_static("public, max-age=3600"),
+        ))
+        .layer(cors_layer())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::main::create_pool;
     use axum::body::Body;
     use axum::http::{Request, StatusCode};
     use std::fs;
     use tower::ServiceExt;
 
-    fn test_router(dir: &Path) -> Router {
-        Router::new()
-            .fallback_service(ServeDir::new(dir.join("covers")))
-            .layer(SetResponseHeaderLayer::if_not_present(
-                axum::http::header::CACHE_CONTROL,
-                axum::http::HeaderValue::from_static("public, max-age=3600"),
-            ))
+    async fn test_router(dir: &Path) -> Router {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        router(dir, pool)
     }
 
     #[tokio::test]
@@ -47,11 +81,11 @@ mod tests {
         let file_path = covers.join("test.txt");
         fs::write(&file_path, "hello").unwrap();
 
-        let app = test_router(dir.path());
+        let app = test_router(dir.path()).await;
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/test.txt")
+                    .uri("/covers/test.txt")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -66,11 +100,11 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         fs::create_dir_all(dir.path().join("covers")).unwrap();
 
-        let app = test_router(dir.path());
+        let app = test_router(dir.path()).await;
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/missing.txt")
+                    .uri("/covers/missing.txt")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -88,11 +122,11 @@ mod tests {
         let file_path = covers.join("image.jpg");
         fs::write(&file_path, "fake image").unwrap();
 
-        let app = test_router(dir.path());
+        let app = test_router(dir.path()).await;
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/image.jpg")
+                    .uri("/covers/image.jpg")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -113,11 +147,11 @@ mod tests {
         fs::create_dir_all(&nested).unwrap();
         fs::write(nested.join("poster"), "image data").unwrap();
 
-        let app = test_router(dir.path());
+        let app = test_router(dir.path()).await;
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/Movie/poster")
+                    .uri("/covers/Movie/poster")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -132,11 +166,11 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         fs::create_dir_all(dir.path().join("covers")).unwrap();
 
-        let app = test_router(dir.path());
+        let app = test_router(dir.path()).await;
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/../etc/passwd")
+                    .uri("/covers/../etc/passwd")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -145,4 +179,55 @@ mod tests {
 
         assert_ne!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn serves_covers_of_folders_named_like_routes() {
+        let dir = tempfile::tempdir().unwrap();
+        for folder in ["api", "trickplay"] {
+            let covers = dir.path().join("covers").join(folder);
+            fs::create_dir_all(&covers).unwrap();
+            fs::write(covers.join("poster"), "image data").unwrap();
+        }
+
+        for uri in ["/covers/api/poster", "/covers/trickplay/poster"] {
+            let app = test_router(dir.path()).await;
+            let response = app
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn allows_only_app_origins() {
+        let dir = tempfile::tempdir().unwrap();
+        let covers = dir.path().join("covers");
+        fs::create_dir_all(&covers).unwrap();
+        fs::write(covers.join("image.jpg"), "fake image").unwrap();
+
+        for (origin, allowed) in [
+            ("tauri://localhost", true),
+            ("http://tauri.localhost", true),
+            ("http://example.com", false),
+        ] {
+            let app = test_router(dir.path()).await;
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/covers/image.jpg")
+                        .header("origin", origin)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("access-control-allow-origin"),
+                allowed.then(|| HeaderValue::from_static(origin)).as_ref()
+            );
+        }
+    }
 }
@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::Router;
+use log::error;
+use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
+
+use super::comic::{resolve_library_path, ApiError};
+use crate::db;
+use crate::model::parser::FileFingerprint;
+use crate::parser::trickplay::{
+    generate_trickplay, get_trickplay_dir, get_video_fingerprint, is_current, INDEX_FILE,
+};
+
+// ffmpeg decodes the whole video for its sprites, so only a few run at once
+const MAX_JOBS: usize = 2;
+
+#[derive(Deserialize)]
+struct TrickplayQuery {
+    folder: String,
+    path: String, // path of the movie or the episode as listed in the folder
+    #[serde(default)]
+    file: String,
+}
+
+#[derive(Clone)]
+struct TrickplayState {
+    pool: Pool<Sqlite>,
+    app_data_dir: PathBuf,
+    jobs: Arc<TrickplayJobs>,
+}
+
+/// Sprite generation running in the background, at most `MAX_JOBS` at a time.
+struct TrickplayJobs {
+    workers: rayon::ThreadPool,
+    queued: Mutex<HashSet<PathBuf>>,
+    // error of the last run by sprite directory, kept as long as the video keeps its fingerprint
+    failed: Mutex<HashMap<PathBuf, (FileFingerprint, String)>>,
+}
+
+impl TrickplayJobs {
+    fn new() -> Self {
+        TrickplayJobs {
+            workers: rayon::ThreadPoolBuilder::new()
+                .num_threads(MAX_JOBS)
+                .build()
+                .expect("Fail to create trickplay workers"),
+            queued: Mutex::new(HashSet::new()),
+            failed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Error the sprites of the video failed with, unless the video changed since.
+    fn failure(&self, dest_dir: &Path, fingerprint: &FileFingerprint) -> Option<String> {
+        self.failed
+            .lock()
+            .unwrap()
+            .get(dest_dir)
+            .filter(|(o, _)| o == fingerprint)
+            .map(|(_, e)| e.clone())
+    }
+
+    /// Queue the sprites of the video unless they are queued already.
+    fn queue(self: &Arc<Self>, video: PathBuf, dest_dir: PathBuf, fingerprint: FileFingerprint) {
+        if !self.queued.lock().unwrap().insert(dest_dir.clone()) {
+            return;
+        }
+        let jobs = Arc::clone(self);
+        self.workers.spawn(move || {
+            let result = generate_trickplay(&video, &dest_dir);
+            let mut failed = jobs.failed.lock().unwrap();
+            match result {
+                Ok(()) => {
+                    failed.remove(&dest_dir);
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    failed.insert(dest_dir.clone(), (fingerprint, e));
+                }
+            }
+            drop(failed);
+            jobs.queued.lock().unwrap().remove(&dest_dir);
+        });
+    }
+}
+
+/// Route handing out the seek previews of indexed videos, generating them on first request.
+pub(super) fn router(pool: Pool<Sqlite>, app_data_dir: &Path) -> Router {
+    Router::new()
+        .route("/", get(get_trickplay))
+        .with_state(TrickplayState {
+            pool,
+            app_data_dir: app_data_dir.to_path_buf(),
+            jobs: Arc::new(TrickplayJobs::new()),
+        })
+}
+
+/// Redirect to the WebVTT index of the video once its sprites are there. Otherwise they are
+/// generated in the background and the player has to ask again later. Sprites that failed are not
+/// generated again until the video changes.
+async fn get_trickplay(
+    State(state): State<TrickplayState>,
+    Query(query): Query<TrickplayQuery>,
+) -> Result<Response, ApiError> {
+    // loose movies are listed by their video
+    let relative_path = match query.file.as_str() {
+        "" => PathBuf::from(&query.path),
+        file => Path::new(&query.path).join(file),
+    };
+    if relative_path
+        .components()
+        .any(|o| !matches!(o, Component::Normal(_)))
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("{:?} is outside of the library", relative_path),
+        ));
+    }
+    let video = resolve_video(&state.pool, &query.folder, &query.path, &relative_path).await?;
+
+    let dest_dir = get_trickplay_dir(&state.app_data_dir, &query.folder, &relative_path);
+    if is_current(&dest_dir, &video) {
+        let url = get_index_url(&query.folder, &relative_path);
+        return Ok(Redirect::temporary(&url).into_response());
+    }
+    let fingerprint =
+        get_video_fingerprint(&video).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if let Some(e) = state.jobs.failure(&dest_dir, &fingerprint) {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+    state.jobs.queue(video, dest_dir, fingerprint);
+    Ok((StatusCode::ACCEPTED, [(header::CACHE_CONTROL, "no-store")]).into_response())
+}
+
+/// Absolute path of the video of an indexed movie or episode. Only the videos listed in the movie
+/// or the show holding `path` are served.
+async fn resolve_video(
+    pool: &Pool<Sqlite>,
+    folder_name: &str,
+    path: &str,
+    relative_path: &Path,
+) -> Result<PathBuf, ApiError> {
+    let Some((root_path, videos)) = db::main::get_media_videos(pool, folder_name, path)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Video {} is not indexed in {}", path, folder_name),
+        ));
+    };
+    if !videos.iter().any(|o| o == relative_path) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("{:?} is not a video of {}", relative_path, path),
+        ));
+    }
+    resolve_library_path(Path::new(&root_path), &relative_path.to_string_lossy())
+}
+
+fn get_index_url(folder_name: &str, relative_path: &Path) -> String {
+    let path = Path::new(folder_name)
+        .join(relative_path)
+        .join(INDEX_FILE)
+        .components()
+        .map(|o| urlencoding::encode(&o.as_os_str().to_string_lossy()).into_owned())
+        .collect::<Vec<String>>()
+        .join("/");
+    format!("/trickplay/{}", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::main::{create_pool, create_tables, insert_folder_data, update_media};
+    use crate::model::parser::{Media, MediaType, ScanResult};
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::ffi::OsString;
+    use std::fs;
+    use tower::ServiceExt;
+
+    async fn setup(library: &Path) -> Pool<Sqlite> {
+        fs::create_dir_all(library.join("Heat")).unwrap();
+        fs::write(library.join("Heat").join("Heat.mkv"), "video").unwrap();
+
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        insert_folder_data(&pool, "Movies", &library.to_string_lossy())
+            .await
+            .unwrap();
+
+        let mut movie = Media::default();
+        movie.set_media_type(MediaType::Movie);
+        movie.set_title("Heat".to_string());
+        movie.set_relative_path(OsString::from("Heat"));
+        movie.set_file("Heat.mkv".to_string());
+        let items = vec![movie.movie().unwrap()];
+        update_media(
+            &pool,
+            "Movies",
+            &ScanResult::new(items, vec![], vec![], vec![], true),
+        )
+        .await
+        .unwrap();
+        pool
+    }
+
+    async fn request(pool: Pool<Sqlite>, app_data_dir: &Path, uri: &str) -> Response {
+        router(pool, app_data_dir)
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn redirects_to_the_index_of_generated_sprites() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("library");
+        let pool = setup(&library).await;
+        let dest_dir = get_trickplay_dir(dir.path(), "Movies", &Path::new("Heat").join("Heat.mkv"));
+        fs::create_dir_all(&dest_dir).unwrap();
+        fs::write(dest_dir.join(INDEX_FILE), "WEBVTT\n").unwrap();
+
+        let response = request(pool, dir.path(), "/?folder=Movies&path=Heat&file=Heat.mkv").await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "/trickplay/Movies/Heat/Heat.mkv/index.vtt"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_failed_sprites_until_the_video_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("library");
+        let pool = setup(&library).await;
+        let app = router(pool, dir.path());
+        let uri = "/?folder=Movies&path=Heat&file=Heat.mkv";
+        let request = |app: Router| async move {
+            app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+                .status()
+        };
+
+        // the video is no video, so probing it fails
+        assert_eq!(request(app.clone()).await, StatusCode::ACCEPTED);
+        let mut status = StatusCode::ACCEPTED;
+        for _ in 0..100 {
+            status = request(app.clone()).await;
+            if status != StatusCode::ACCEPTED {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            request(app.clone()).await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        fs::write(library.join("Heat").join("Heat.mkv"), "another video").unwrap();
+        assert_eq!(request(app).await, StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn refuses_videos_that_are_not_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("library");
+        let pool = setup(&library).await;
+        fs::write(library.join("Ronin.mkv"), "video").unwrap();
+
+        let response = request(pool.clone(), dir.path(), "/?folder=Movies&path=Ronin.mkv").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let uri = "/?folder=Movies&path=Heat&file=../Ronin.mkv";
+        let response = request(pool.clone(), dir.path(), uri).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // files next to the movie are not its videos
+        fs::write(library.join("Heat").join("Heat.nfo"), "nfo").unwrap();
+        let uri = "/?folder=Movies&path=Heat&file=Heat.nfo";
+        let response = request(pool.clone(), dir.path(), uri).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = request(pool, dir.path(), "/?folder=Movies&path=Heat").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
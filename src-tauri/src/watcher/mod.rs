@@ -0,0 +1,220 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, error};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use tokio::sync::OwnedMutexGuard;
+
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+type FolderDebouncer = Debouncer<RecommendedWatcher, RecommendedCache>;
+
+/// Keeps one recursive filesystem watcher per library folder, keyed by folder name.
+#[derive(Default)]
+pub struct LibraryWatcher {
+    debouncers: Mutex<HashMap<String, FolderDebouncer>>,
+}
+
+impl LibraryWatcher {
+    /// Start watching `path`, replacing any watcher already registered for `name`. `on_change` is
+    /// called from the watcher thread with the directories, relative to `path`, that need rescanning.
+    pub fn watch<F>(&self, name: &str, path: &str, on_change: F) -> Result<(), String>
+    where
+        F: Fn(Vec<PathBuf>) + Send + 'static,
+    {
+        let root_path = PathBuf::from(path);
+        let mut debouncer = new_debouncer(DEBOUNCE_TIMEOUT, None, {
+            let root_path = root_path.clone();
+            move |result: DebounceEventResult| match result {
+                Ok(events) => {
+                    let paths = events
+                        .iter()
+                        .filter(|o| !matches!(o.kind, EventKind::Access(_)))
+                        .flat_map(|o| o.paths.iter().cloned())
+                        .collect::<Vec<PathBuf>>();
+                    let dirs = get_affected_dirs(&root_path, &paths);
+                    if !dirs.is_empty() {
+                        debug!("Detected changes in {:?}: {:?}", root_path, dirs);
+                        on_change(dirs);
+                    }
+                }
+                Err(errors) => {
+                    for e in errors {
+                        error!("Failed to watch {:?}: {}", root_path, e);
+                    }
+                }
+            }
+        })
+        .map_err(|e| format!("Fail to create watcher for {}. Raising error {}", path, e))?;
+
+        debouncer
+            .watch(&root_path, RecursiveMode::Recursive)
+            .map_err(|e| format!("Fail to watch directory {}. Raising error {}", path, e))?;
+
+        self.debouncers
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), debouncer);
+        Ok(())
+    }
+
+    pub fn unwatch(&self, name: &str) {
+        // dropping the debouncer stops its watcher thread
+        self.debouncers.lock().unwrap().remove(name);
+    }
+
+    pub fn is_watching(&self, name: &str) -> bool {
+        self.debouncers.lock().unwrap().contains_key(name)
+    }
+}
+
+/// Serializes the scans of each library folder, keyed by folder name. Directories changed while
+/// the folder is scanned are merged and rescanned together once the running scan is done.
+#[derive(Default)]
+pub struct ScanQueue {
+    folders: Mutex<HashMap<String, Arc<FolderScans>>>,
+}
+
+#[derive(Default)]
+struct FolderScans {
+    running: Arc<tokio::sync::Mutex<()>>,
+    pending: Mutex<Option<BTreeSet<PathBuf>>>,
+}
+
+impl ScanQueue {
+    /// Wait for the running scan of the folder and hold it off other scans until the guard drops.
+    pub async fn lock(&self, name: &str) -> OwnedMutexGuard<()> {
+        self.get(name).running.clone().lock_owned().await
+    }
+
+    /// Queue `dirs` for rescanning. Returns whether the caller has to run the rescan with `next`,
+    /// otherwise the directories were merged into a rescan that is still waiting.
+    pub fn push(&self, name: &str, dirs: Vec<PathBuf>) -> bool {
+        let folder = self.get(name);
+        let mut pending = folder.pending.lock().unwrap();
+        let is_new = pending.is_none();
+        pending.get_or_insert_with(BTreeSet::new).extend(dirs);
+        is_new
+    }
+
+    /// Wait for the running scan of the folder, then take the directories queued for rescanning.
+    pub async fn next(&self, name: &str) -> (OwnedMutexGuard<()>, Vec<PathBuf>) {
+        let guard = self.lock(name).await;
+        let dirs = self.get(name).pending.lock().unwrap().take();
+        (guard, collapse_dirs(dirs.unwrap_or_default()))
+    }
+
+    fn get(&self, name: &str) -> Arc<FolderScans> {
+        self.folders
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+}
+
+/// Map changed paths to the smallest set of existing directories that covers them. A changed path
+/// that no longer exists is represented by its nearest existing ancestor, so removals are picked up.
+fn get_affected_dirs(root_path: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let dirs = paths
+        .iter()
+        .filter_map(|path| {
+            let dir = path
+                .ancestors()
+                .find(|o| o.is_dir())
+                .filter(|o| o.starts_with(root_path))?;
+            dir.strip_prefix(root_path).ok().map(|o| o.to_path_buf())
+        })
+        .collect::<BTreeSet<PathBuf>>();
+    collapse_dirs(dirs)
+}
+
+fn collapse_dirs(dirs: BTreeSet<PathBuf>) -> Vec<PathBuf> {
+    // sorted order puts every directory before its descendants
+    let mut result: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !result.iter().any(|o| dir.starts_with(o)) {
+            result.push(dir);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn affected_dirs_uses_parent_of_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+        fs::write(dir.path().join("Heat").join("movie.nfo"), "").unwrap();
+
+        let result = get_affected_dirs(dir.path(), &[dir.path().join("Heat").join("movie.nfo")]);
+        assert_eq!(result, vec![PathBuf::from("Heat")]);
+    }
+
+    #[test]
+    fn affected_dirs_falls_back_to_existing_ancestor_for_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+
+        let removed = dir.path().join("Heat").join("Extras").join("trailer.mkv");
+        let result = get_affected_dirs(dir.path(), &[removed]);
+        assert_eq!(result, vec![PathBuf::from("Heat")]);
+    }
+
+    #[test]
+    fn affected_dirs_collapses_nested_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Show").join("Season 01")).unwrap();
+        fs::create_dir_all(dir.path().join("Heat")).unwrap();
+
+        let result = get_affected_dirs(
+            dir.path(),
+            &[
+                dir.path().join("Show").join("Season 01"),
+                dir.path().join("Show").join("tvshow.nfo"),
+                dir.path().join("Heat"),
+            ],
+        );
+        assert_eq!(result, vec![PathBuf::from("Heat"), PathBuf::from("Show")]);
+    }
+
+    #[tokio::test]
+    async fn scan_queue_merges_dirs_changed_during_a_scan() {
+        let queue = ScanQueue::default();
+        let guard = queue.lock("Movies").await;
+
+        // the first change starts a rescan, later ones join it while it waits for the full scan
+        assert!(queue.push("Movies", vec![PathBuf::from("Show").join("Season 01")]));
+        assert!(!queue.push("Movies", vec![PathBuf::from("Heat")]));
+        assert!(!queue.push("Movies", vec![PathBuf::from("Show")]));
+        assert!(queue.push("Shows", vec![PathBuf::from("Heat")]));
+
+        drop(guard);
+        let (guard, dirs) = queue.next("Movies").await;
+        assert_eq!(dirs, vec![PathBuf::from("Heat"), PathBuf::from("Show")]);
+
+        // changes during the rescan are queued for another one
+        assert!(queue.push("Movies", vec![PathBuf::from("Heat")]));
+        drop(guard);
+        let (_, dirs) = queue.next("Movies").await;
+        assert_eq!(dirs, vec![PathBuf::from("Heat")]);
+    }
+
+    #[test]
+    fn affected_dirs_ignores_paths_outside_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("library");
+        fs::create_dir_all(&library).unwrap();
+
+        let result = get_affected_dirs(&library, &[dir.path().join("other.txt")]);
+        assert!(result.is_empty());
+    }
+}
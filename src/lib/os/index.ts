@@ -26,7 +26,9 @@ export async function openMedia(
 			await openFile([folderPath, media.file].join('/'));
 			break;
 		case MediaType.MOVIE:
-			await openFile([folderPath, media.path, media.file].join('/'));
+			await openFile(
+				[folderPath, media.path, media.file].filter(Boolean).join('/')
+			);
 			break;
 		case MediaType.TV_SERIES:
 			openModal('tv-show-menu');
@@ -5,6 +5,7 @@ import {
 } from '@tanstack/solid-query';
 import { buildDirectory, notify } from '@/lib/os';
 import {
+	changeCoverFormat,
 	changeSkipFolders,
 	getFolderInfo,
 	getFolderList,
@@ -16,7 +17,13 @@ import {
 	switchFolderFilterType,
 	updateFolderSortType,
 } from '@/lib/storage';
-import type { FilterOption, FilterType, IFolder, SORT } from '@/type';
+import type {
+	CoverFormat,
+	FilterOption,
+	FilterType,
+	IFolder,
+	SORT,
+} from '@/type';
 
 const FOLDER_LIST = 'folderList';
 const SETTING = 'setting';
@@ -122,6 +129,11 @@ export async function updateSkipFolder(skipFolders: string) {
 	invalidSetting();
 }
 
+export async function updateCoverFormat(coverFormat: CoverFormat) {
+	await changeCoverFormat(coverFormat);
+	invalidSetting();
+}
+
 function invalidSetting() {
 	queryClient.invalidateQueries({ queryKey: [SETTING] });
 }
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
 import type {
+	CoverFormat,
 	FilterOption,
 	FilterType,
 	GroupedOption,
@@ -68,6 +69,12 @@ export async function changeSkipFolders(skipFolders: string): Promise<void> {
 	await invoke('update_skip_folders', { skipFolders });
 }
 
+export async function changeCoverFormat(
+	coverFormat: CoverFormat
+): Promise<void> {
+	await invoke('update_cover_format', { coverFormat });
+}
+
 export async function switchFolderFilterType(position: number) {
 	await invoke<IFolderData>('update_folder_filter_type', { position });
 }
@@ -8,7 +8,12 @@ import FolderList from '@/components/Setting/FolderList';
 import SkipFolderList from '@/components/Setting/SkipFolderList';
 import { DirectoryButton, SkipFolderButton } from '@/components/Shares';
 import { notify } from '@/lib/os';
-import { changePanelDisplay, settingQueryOptions } from '@/lib/queries';
+import {
+	changePanelDisplay,
+	settingQueryOptions,
+	updateCoverFormat,
+} from '@/lib/queries';
+import type { CoverFormat } from '@/type';
 
 export const Route = createFileRoute('/setting')({
 	loader: async () => await getVersion(),
@@ -28,6 +33,16 @@ export const Route = createFileRoute('/setting')({
 			}
 		}
 
+		async function handleCoverFormat(
+			e: Event & { currentTarget: HTMLSelectElement; target: HTMLSelectElement }
+		) {
+			try {
+				await updateCoverFormat(e.target.value as CoverFormat);
+			} catch (e) {
+				await notify(`Cover Format Error: ${e}`);
+			}
+		}
+
 		return (
 			<Show when={settingQuery.isSuccess} fallback={<Loading />}>
 				<div class="mx-auto size-full max-w-4xl cursor-default py-5">
@@ -46,6 +61,22 @@ export const Route = createFileRoute('/setting')({
 							</span>
 						</label>
 					</div>
+					<div class="flex items-center gap-2 p-2">
+						<span class="text-base font-medium">Cover Format</span>
+						<select
+							class="select select-sm w-auto"
+							value={settingQuery.data?.coverFormat}
+							onChange={handleCoverFormat}
+						>
+							<option value="jpeg">JPEG</option>
+							<option value="png">PNG</option>
+							<option value="webp">WebP</option>
+							<option value="avif">AVIF (requires ffmpeg)</option>
+						</select>
+						<span class="text-sm opacity-60">
+							Covers are regenerated on the next scan.
+						</span>
+					</div>
 					<div class="flex w-full flex-row items-center justify-between px-4">
 						<span class="py-3 text-lg opacity-30">Imported Folders</span>
 						<DirectoryButton
@@ -58,9 +58,12 @@ export interface IComicData extends IBaseData {
 
 export type IMediaData = IMovieData | ITVShowData | IComicData;
 
+export type CoverFormat = 'jpeg' | 'png' | 'webp' | 'avif';
+
 export interface ISetting {
 	readonly showSidePanel: boolean;
 	readonly skipFolders: string[];
+	readonly coverFormat: CoverFormat;
 }
 
 export interface IFolder {